@@ -0,0 +1,241 @@
+//! Request latency, throughput and allocation benchmarks over a pty loopback.
+//!
+//! The arbiter opens the slave side of a pseudo terminal while a thread on
+//! the master side echoes everything back. A pty transfers data as fast as
+//! the CPU allows, so the echo thread paces the data at the simulated baud
+//! rate (10 bits per character, as with 8N1) to make the throughput figures
+//! comparable with real hardware.
+//!
+//! Run with `cargo bench`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs::File,
+    hint::black_box,
+    io::{Read, Write},
+    os::fd::{AsFd, OwnedFd},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{
+    criterion_group, criterion_main,
+    measurement::{Measurement, ValueFormatter},
+    BenchmarkId, Criterion, Throughput,
+};
+use nix::pty::openpty;
+use serial_arbiter::Arbiter;
+
+/// Size of the frame echoed in the throughput benchmark.
+const PAYLOAD_LEN: usize = 4096;
+
+/// Counts every allocation made by the process, including the worker thread.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Criterion measurement reporting the number of allocations instead of time.
+struct Allocations;
+
+impl Measurement for Allocations {
+    type Intermediate = usize;
+    type Value = usize;
+
+    fn start(&self) -> usize {
+        ALLOCATIONS.load(Ordering::Relaxed)
+    }
+
+    fn end(&self, start: usize) -> usize {
+        ALLOCATIONS.load(Ordering::Relaxed) - start
+    }
+
+    fn add(&self, v1: &usize, v2: &usize) -> usize {
+        v1 + v2
+    }
+
+    fn zero(&self) -> usize {
+        0
+    }
+
+    fn to_f64(&self, value: &usize) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &AllocationsFormatter
+    }
+}
+
+struct AllocationsFormatter;
+
+impl ValueFormatter for AllocationsFormatter {
+    fn scale_values(&self, _typical_value: f64, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        if let Throughput::Bytes(bytes) | Throughput::BytesDecimal(bytes) = throughput {
+            values.iter_mut().for_each(|v| *v /= *bytes as f64);
+        }
+        "allocs/byte"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+}
+
+/// Pseudo terminal with a thread echoing back everything sent to the slave.
+struct Loopback {
+    path: PathBuf,
+    /// Simulated baud rate, zero for no pacing.
+    baud: Arc<AtomicU64>,
+    // Keeps the pty alive while the arbiter reconnects
+    _slave: OwnedFd,
+}
+
+impl Loopback {
+    fn new() -> Self {
+        let pty = openpty(None, None).expect("openpty");
+        let path = nix::unistd::ttyname(pty.slave.as_fd()).expect("ttyname");
+        let baud = Arc::new(AtomicU64::new(0));
+        let mut master = File::from(pty.master);
+        let pacing = baud.clone();
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while let Ok(len @ 1..) = master.read(&mut buf) {
+                let baud = pacing.load(Ordering::Relaxed);
+                if baud > 0 {
+                    let secs = (len * 10) as f64 / baud as f64;
+                    thread::sleep(Duration::from_secs_f64(secs));
+                }
+                if master.write_all(&buf[..len]).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            path,
+            baud,
+            _slave: pty.slave,
+        }
+    }
+
+    fn set_baud(&self, baud: u64) {
+        self.baud.store(baud, Ordering::Relaxed);
+    }
+}
+
+fn open(loopback: &Loopback) -> Arbiter {
+    let port = Arbiter::new();
+    port.open(&loopback.path).expect("open pty");
+    port
+}
+
+fn deadline() -> Instant {
+    Instant::now() + Duration::from_secs(10)
+}
+
+fn latency(c: &mut Criterion) {
+    let loopback = Loopback::new();
+    let port = open(&loopback);
+    let request: Arc<[u8]> = Arc::from(&b"PING\n"[..]);
+
+    let mut group = c.benchmark_group("latency");
+    group.bench_function("transact", |b| {
+        b.iter(|| {
+            port.transact(black_box(request.clone()), b'\n', deadline())
+                .unwrap()
+        })
+    });
+    group.bench_function("transmit_receive", |b| {
+        b.iter(|| {
+            port.transmit(black_box(request.clone()), deadline())
+                .unwrap();
+            port.receive(Some(b'\n'), Some(deadline())).unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn throughput(c: &mut Criterion) {
+    let loopback = Loopback::new();
+    let port = open(&loopback);
+
+    let mut group = c.benchmark_group("throughput");
+    group.sample_size(10);
+    for baud in [115_200, 921_600, 4_000_000, 0] {
+        loopback.set_baud(baud);
+        let mut payload = vec![b'x'; PAYLOAD_LEN - 1];
+        payload.push(b'\n');
+        let payload: Arc<[u8]> = Arc::from(payload);
+        let label = if baud == 0 {
+            "unpaced".to_string()
+        } else {
+            baud.to_string()
+        };
+        group.throughput(Throughput::Bytes(PAYLOAD_LEN as u64));
+        group.bench_with_input(BenchmarkId::new("echo", label), &payload, |b, payload| {
+            b.iter(|| {
+                let echo = port.transact(payload.clone(), b'\n', deadline()).unwrap();
+                assert_eq!(echo.len(), payload.len());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn allocations(c: &mut Criterion<Allocations>) {
+    let loopback = Loopback::new();
+    let port = open(&loopback);
+    let request: Arc<[u8]> = Arc::from(&b"PING\n"[..]);
+
+    let mut group = c.benchmark_group("allocations");
+    group.bench_function("transact", |b| {
+        b.iter(|| port.transact(request.clone(), b'\n', deadline()).unwrap())
+    });
+    group.bench_function("transmit_receive", |b| {
+        b.iter(|| {
+            port.transmit(request.clone(), deadline()).unwrap();
+            port.receive(Some(b'\n'), Some(deadline())).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(timing, latency, throughput);
+criterion_group! {
+    name = allocs;
+    config = Criterion::default().with_measurement(Allocations);
+    targets = allocations
+}
+criterion_main!(timing, allocs);
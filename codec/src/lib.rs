@@ -0,0 +1,324 @@
+//! Framing of messages on a serial link, shared by the `serial-arbiter`
+//! host and the firmware on the other end of the link.
+//!
+//! Works without `std`. Frames are encoded into and decoded from caller
+//! provided buffers; with the `alloc` feature (default) also into vectors.
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+/// Failure to encode or decode a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The message contains the delimiter, so it cannot be framed.
+    DelimiterInMessage,
+    /// The frame is not a valid encoding of any message.
+    InvalidFrame,
+    /// The output buffer is too small.
+    BufferTooSmall,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DelimiterInMessage => write!(f, "Message contains the delimiter"),
+            Error::InvalidFrame => write!(f, "Invalid frame"),
+            Error::BufferTooSmall => write!(f, "Buffer too small"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Frames terminated by a delimiter byte which never occurs in the messages,
+/// e.g. newline terminated text lines.
+#[derive(Debug, Clone, Copy)]
+pub struct LineCodec {
+    pub delimiter: u8,
+}
+
+impl LineCodec {
+    /// Length of the frame of a message of the given length.
+    pub const fn frame_len(msg_len: usize) -> usize {
+        msg_len + 1
+    }
+
+    /// Encodes a message into a frame, including the delimiter.
+    /// Returns the length of the frame.
+    pub fn encode_into(&self, msg: &[u8], frame: &mut [u8]) -> Result<usize, Error> {
+        if msg.contains(&self.delimiter) {
+            return Err(Error::DelimiterInMessage);
+        }
+        let len = Self::frame_len(msg.len());
+        let frame = frame.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        frame[..msg.len()].copy_from_slice(msg);
+        frame[msg.len()] = self.delimiter;
+        Ok(len)
+    }
+
+    /// Decodes a frame, without the delimiter, back into the message.
+    /// Returns the length of the message.
+    pub fn decode_into(&self, frame: &[u8], msg: &mut [u8]) -> Result<usize, Error> {
+        let msg = msg.get_mut(..frame.len()).ok_or(Error::BufferTooSmall)?;
+        msg.copy_from_slice(frame);
+        Ok(frame.len())
+    }
+
+    /// Encodes a message into a frame, including the delimiter.
+    #[cfg(feature = "alloc")]
+    pub fn encode(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut frame = vec![0; Self::frame_len(msg.len())];
+        self.encode_into(msg, &mut frame)?;
+        Ok(frame)
+    }
+
+    /// Decodes a frame, without the delimiter, back into the message.
+    #[cfg(feature = "alloc")]
+    pub fn decode(&self, frame: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(frame.to_vec())
+    }
+}
+
+/// Consistent Overhead Byte Stuffing. Frames binary messages of any content
+/// with a zero delimiter, at a cost of one byte per 254 bytes of message.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CobsCodec;
+
+impl CobsCodec {
+    /// The byte terminating every frame.
+    pub const DELIMITER: u8 = 0x00;
+
+    /// Largest frame, including the delimiter, of a message of the given length.
+    pub const fn max_frame_len(msg_len: usize) -> usize {
+        msg_len + msg_len / 254 + 2
+    }
+
+    /// Encodes a message into a frame, including the delimiter.
+    /// Returns the length of the frame.
+    pub fn encode_into(&self, msg: &[u8], frame: &mut [u8]) -> Result<usize, Error> {
+        if frame.len() < Self::max_frame_len(msg.len()) {
+            return Err(Error::BufferTooSmall);
+        }
+        let mut code_idx = 0;
+        let mut code = 1u8;
+        let mut len = 1;
+        for &byte in msg {
+            if byte != 0 {
+                frame[len] = byte;
+                len += 1;
+                code += 1;
+            }
+            if byte == 0 || code == 0xFF {
+                frame[code_idx] = code;
+                code_idx = len;
+                code = 1;
+                len += 1;
+            }
+        }
+        frame[code_idx] = code;
+        frame[len] = Self::DELIMITER;
+        Ok(len + 1)
+    }
+
+    /// Decodes a frame, without the delimiter, back into the message.
+    /// Returns the length of the message, which is never longer than the frame.
+    pub fn decode_into(&self, frame: &[u8], msg: &mut [u8]) -> Result<usize, Error> {
+        let mut len = 0;
+        let mut idx = 0;
+        while idx < frame.len() {
+            let code = frame[idx] as usize;
+            let end = idx + code;
+            if code == 0 || end > frame.len() {
+                return Err(Error::InvalidFrame);
+            }
+            let block = &frame[idx + 1..end];
+            let out = msg
+                .get_mut(len..len + block.len())
+                .ok_or(Error::BufferTooSmall)?;
+            out.copy_from_slice(block);
+            len += block.len();
+            idx = end;
+            if code != 0xFF && idx < frame.len() {
+                *msg.get_mut(len).ok_or(Error::BufferTooSmall)? = 0;
+                len += 1;
+            }
+        }
+        Ok(len)
+    }
+
+    /// Encodes a message into a frame, including the delimiter.
+    #[cfg(feature = "alloc")]
+    pub fn encode(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut frame = vec![0; Self::max_frame_len(msg.len())];
+        let len = self.encode_into(msg, &mut frame)?;
+        frame.truncate(len);
+        Ok(frame)
+    }
+
+    /// Decodes a frame, without the delimiter, back into the message.
+    #[cfg(feature = "alloc")]
+    pub fn decode(&self, frame: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut msg = vec![0; frame.len()];
+        let len = self.decode_into(frame, &mut msg)?;
+        msg.truncate(len);
+        Ok(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes into a buffer of the largest frame and decodes back.
+    fn cobs_round_trip(msg: &[u8]) {
+        let mut frame = [0xAA; 1024];
+        let len = CobsCodec.encode_into(msg, &mut frame).unwrap();
+        let frame = &frame[..len];
+        assert!(len <= CobsCodec::max_frame_len(msg.len()));
+        assert_eq!(frame.last(), Some(&CobsCodec::DELIMITER));
+        assert!(!frame[..len - 1].contains(&CobsCodec::DELIMITER));
+        let mut decoded = [0xAA; 1024];
+        let len = CobsCodec
+            .decode_into(&frame[..len - 1], &mut decoded)
+            .unwrap();
+        assert_eq!(&decoded[..len], msg);
+    }
+
+    #[test]
+    fn cobs_encodes_the_reference_vectors() {
+        let vectors: [(&[u8], &[u8]); 6] = [
+            (&[], &[0x01, 0x00]),
+            (&[0x00], &[0x01, 0x01, 0x00]),
+            (&[0x00, 0x00], &[0x01, 0x01, 0x01, 0x00]),
+            (
+                &[0x11, 0x22, 0x00, 0x33],
+                &[0x03, 0x11, 0x22, 0x02, 0x33, 0x00],
+            ),
+            (
+                &[0x11, 0x22, 0x33, 0x44],
+                &[0x05, 0x11, 0x22, 0x33, 0x44, 0x00],
+            ),
+            (
+                &[0x11, 0x00, 0x00, 0x00],
+                &[0x02, 0x11, 0x01, 0x01, 0x01, 0x00],
+            ),
+        ];
+        for (msg, expected) in vectors {
+            let mut frame = [0; 16];
+            let len = CobsCodec.encode_into(msg, &mut frame).unwrap();
+            assert_eq!(&frame[..len], expected, "encoding {msg:02X?}");
+            cobs_round_trip(msg);
+        }
+    }
+
+    #[test]
+    fn cobs_round_trips_around_the_block_length() {
+        let mut msg = [0; 600];
+        for (i, byte) in msg.iter_mut().enumerate() {
+            *byte = (i % 255) as u8 + 1;
+        }
+        for len in [1, 253, 254, 255, 256, 508, 509, 600] {
+            cobs_round_trip(&msg[..len]);
+        }
+        msg[254] = 0;
+        msg[300] = 0;
+        msg[599] = 0;
+        for len in [254, 255, 256, 301, 600] {
+            cobs_round_trip(&msg[..len]);
+        }
+        cobs_round_trip(&[0; 300]);
+    }
+
+    #[test]
+    fn cobs_decodes_a_full_block_without_a_trailing_code() {
+        // As encoded by the implementations which omit the empty last block
+        let mut frame = [0xFF; 255];
+        for (i, byte) in frame[1..].iter_mut().enumerate() {
+            *byte = i as u8 + 1;
+        }
+        let mut msg = [0; 255];
+        let len = CobsCodec.decode_into(&frame, &mut msg).unwrap();
+        assert_eq!(&msg[..len], &frame[1..]);
+    }
+
+    #[test]
+    fn cobs_rejects_invalid_frames() {
+        let mut msg = [0; 16];
+        for frame in [&[0x00, 0x11][..], &[0x03, 0x11], &[0x02, 0x11, 0x05, 0x22]] {
+            assert_eq!(
+                CobsCodec.decode_into(frame, &mut msg),
+                Err(Error::InvalidFrame),
+                "decoding {frame:02X?}"
+            );
+        }
+    }
+
+    #[test]
+    fn cobs_reports_small_buffers() {
+        let msg = [0x11, 0x00, 0x22];
+        let mut frame = [0; CobsCodec::max_frame_len(3)];
+        assert_eq!(
+            CobsCodec.encode_into(&msg, &mut frame[..4]),
+            Err(Error::BufferTooSmall)
+        );
+        let len = CobsCodec.encode_into(&msg, &mut frame).unwrap();
+        let mut short = [0; 2];
+        assert_eq!(
+            CobsCodec.decode_into(&frame[..len - 1], &mut short),
+            Err(Error::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn line_round_trips() {
+        let codec = LineCodec { delimiter: b'\n' };
+        let mut frame = [0; 8];
+        let len = codec.encode_into(b"AT+OK", &mut frame).unwrap();
+        assert_eq!(&frame[..len], b"AT+OK\n");
+        let mut msg = [0; 8];
+        let len = codec.decode_into(&frame[..len - 1], &mut msg).unwrap();
+        assert_eq!(&msg[..len], b"AT+OK");
+    }
+
+    #[test]
+    fn line_rejects_the_delimiter_and_small_buffers() {
+        let codec = LineCodec { delimiter: b'\n' };
+        let mut frame = [0; 8];
+        assert_eq!(
+            codec.encode_into(b"A\nB", &mut frame),
+            Err(Error::DelimiterInMessage)
+        );
+        assert_eq!(
+            codec.encode_into(b"ABCDEFGH", &mut frame),
+            Err(Error::BufferTooSmall)
+        );
+        let mut msg = [0; 2];
+        assert_eq!(
+            codec.decode_into(b"ABC", &mut msg),
+            Err(Error::BufferTooSmall)
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn vectors_match_the_buffers() {
+        let msg = [0x11, 0x00, 0x00, 0x22, 0x33];
+        let frame = CobsCodec.encode(&msg).unwrap();
+        let mut buffer = [0; 16];
+        let len = CobsCodec.encode_into(&msg, &mut buffer).unwrap();
+        assert_eq!(frame, &buffer[..len]);
+        assert_eq!(CobsCodec.decode(&frame[..len - 1]).unwrap(), msg);
+        assert_eq!(CobsCodec.decode(&[0x00]), Err(Error::InvalidFrame));
+
+        let codec = LineCodec { delimiter: b'\r' };
+        assert_eq!(codec.encode(b"PING").unwrap(), b"PING\r");
+        assert_eq!(codec.decode(b"PING").unwrap(), b"PING");
+        assert_eq!(codec.encode(b"P\rNG"), Err(Error::DelimiterInMessage));
+    }
+}
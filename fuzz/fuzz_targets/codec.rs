@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serial_arbiter_codec::{CobsCodec, LineCodec};
+
+fuzz_target!(|data: &[u8]| {
+    // Any message survives the round trip and its frame has no inner delimiter
+    let frame = CobsCodec.encode(data).unwrap();
+    assert!(frame.len() <= CobsCodec::max_frame_len(data.len()));
+    let (delimiter, inner) = frame.split_last().unwrap();
+    assert_eq!(*delimiter, CobsCodec::DELIMITER);
+    assert!(!inner.contains(&CobsCodec::DELIMITER));
+    assert_eq!(CobsCodec.decode(inner).unwrap(), data);
+
+    // Any frame decodes or fails cleanly, also into buffers too small for it
+    let _ = CobsCodec.decode(data);
+    for len in [0, 1, data.len() / 2] {
+        let mut msg = vec![0; len];
+        let _ = CobsCodec.decode_into(data, &mut msg);
+    }
+
+    let codec = LineCodec { delimiter: b'\n' };
+    match codec.encode(data) {
+        Ok(frame) => assert_eq!(codec.decode(&frame[..frame.len() - 1]).unwrap(), data),
+        Err(_) => assert!(data.contains(&b'\n')),
+    }
+});
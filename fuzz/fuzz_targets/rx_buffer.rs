@@ -0,0 +1,95 @@
+#![no_main]
+
+use std::collections::VecDeque;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use serial_arbiter::{
+    fuzzing::{MultidropDecoder, RxBuffer},
+    Timestamped,
+};
+
+/// Operations of the worker on its Rx buffer.
+#[derive(Debug, Arbitrary)]
+enum Op {
+    /// A chunk read from the port
+    Extend(Vec<u8>),
+    /// A receive without a delimiter
+    Everything,
+    /// A receive until the delimiter or of everything
+    UntilOrEverything(u8),
+    /// A receive of a complete frame
+    UntilOrNothing(u8),
+    /// A verified echo
+    Remove(u8, u8),
+    Clear,
+    /// Enabling or disabling the multidrop mode, with an address filter
+    Multidrop(Option<Option<u8>>),
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let mut buff = RxBuffer::new();
+    // The expected content, while it can be modeled without the decoder
+    let mut model = Some(VecDeque::new());
+    for op in ops {
+        let taken = match op {
+            Op::Extend(bytes) => {
+                buff.extend(&bytes);
+                if let Some(model) = &mut model {
+                    model.extend(bytes);
+                }
+                None
+            }
+            Op::Everything => Some(buff.take_all()),
+            Op::UntilOrEverything(delimiter) => match buff.position(delimiter) {
+                Some(pos) => Some(buff.take(pos + 1)),
+                None => Some(buff.take_all()),
+            },
+            Op::UntilOrNothing(delimiter) => buff.position(delimiter).map(|pos| buff.take(pos + 1)),
+            Op::Remove(start, end) => {
+                let removed = buff.remove(start as usize..end as usize);
+                if let Some(model) = &mut model {
+                    let end = (end as usize).min(model.len());
+                    let start = (start as usize).min(end);
+                    assert_eq!(removed, model.drain(start..end).collect::<Vec<_>>());
+                }
+                None
+            }
+            Op::Clear => {
+                buff.clear();
+                if let Some(model) = &mut model {
+                    model.clear();
+                }
+                None
+            }
+            Op::Multidrop(decoder) => {
+                buff.set_multidrop(decoder.map(MultidropDecoder::new));
+                model = None;
+                None
+            }
+        };
+        if let Some(taken) = taken {
+            check_chunks(&taken);
+            if let Some(model) = &mut model {
+                assert_eq!(taken.data, model.drain(..taken.data.len()).collect::<Vec<_>>());
+            }
+        }
+        if let Some(model) = &model {
+            assert_eq!(buff.len(), model.len());
+        }
+    }
+    check_chunks(&buff.take_all());
+});
+
+/// Every byte belongs to a chunk and the chunks are in order.
+fn check_chunks(taken: &Timestamped) {
+    if taken.data.is_empty() {
+        return;
+    }
+    assert_eq!(taken.chunks.first().map(|(start, _)| *start), Some(0));
+    for pair in taken.chunks.windows(2) {
+        assert!(pair[0].0 < pair[1].0);
+        assert!(pair[0].1 <= pair[1].1);
+    }
+    assert!(taken.chunks.iter().all(|(start, _)| *start < taken.data.len()));
+}
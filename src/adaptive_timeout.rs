@@ -0,0 +1,139 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::ping::percentile;
+
+/// Derives the timeout of the transactions from their round-trip times, see
+/// [`Arbiter::set_adaptive_timeout`](crate::Arbiter::set_adaptive_timeout):
+/// a percentile of the recent round-trip times times a factor, kept between
+/// the bounds.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use serial_arbiter::{AdaptiveTimeout, Arbiter};
+/// # let arbiter = Arbiter::new();
+/// let adaptive = AdaptiveTimeout::new(Duration::from_millis(20), Duration::from_secs(5))
+///     .with_percentile(0.99)
+///     .with_factor(3.0);
+/// arbiter.set_adaptive_timeout(Some(adaptive));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveTimeout {
+    min: Duration,
+    max: Duration,
+    percentile: f64,
+    factor: f64,
+    window: usize,
+}
+
+impl AdaptiveTimeout {
+    /// Keeps the timeout between the bounds, starting at the upper one until
+    /// a round trip is measured. Takes the 99th percentile of the last 100
+    /// round trips times 2 by default.
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max: max.max(min),
+            percentile: 0.99,
+            factor: 2.0,
+            window: 100,
+        }
+    }
+
+    /// The percentile of the round-trip times, as a fraction, e.g. 0.99.
+    pub fn with_percentile(mut self, percentile: f64) -> Self {
+        self.percentile = percentile.clamp(0.0, 1.0);
+        self
+    }
+
+    /// The factor the percentile is multiplied by, leaving a margin for the
+    /// round trips slower than any seen so far.
+    pub fn with_factor(mut self, factor: f64) -> Self {
+        self.factor = factor.max(1.0);
+        self
+    }
+
+    /// Number of the most recent round trips the percentile is taken over.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+}
+
+/// The recent round-trip times of the transactions.
+pub struct RoundTrips {
+    config: AdaptiveTimeout,
+    rtts: VecDeque<Duration>,
+}
+
+impl RoundTrips {
+    pub fn new(config: AdaptiveTimeout) -> Self {
+        Self {
+            config,
+            rtts: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, rtt: Duration) {
+        if self.rtts.len() >= self.config.window {
+            self.rtts.pop_front();
+        }
+        self.rtts.push_back(rtt);
+    }
+
+    pub fn timeout(&self) -> Duration {
+        let config = &self.config;
+        let Some(rtt) = percentile(self.rtts.iter().copied(), config.percentile) else {
+            return config.max;
+        };
+        let timeout = Duration::try_from_secs_f64(rtt.as_secs_f64() * config.factor);
+        timeout.unwrap_or(config.max).clamp(config.min, config.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{AdaptiveTimeout, RoundTrips};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn round_trips(config: AdaptiveTimeout, rtts: &[u64]) -> RoundTrips {
+        let mut round_trips = RoundTrips::new(config);
+        rtts.iter().for_each(|&rtt| round_trips.record(ms(rtt)));
+        round_trips
+    }
+
+    #[test]
+    fn timeout_is_the_percentile_times_the_factor() {
+        let config = AdaptiveTimeout::new(ms(1), ms(1000))
+            .with_percentile(0.9)
+            .with_factor(2.0);
+        let round_trips = round_trips(config, &[7, 3, 10, 1, 5, 9, 2, 8, 6, 4]);
+        assert_eq!(round_trips.timeout(), ms(18));
+    }
+
+    #[test]
+    fn timeout_is_the_upper_bound_until_a_round_trip_is_measured() {
+        let round_trips = round_trips(AdaptiveTimeout::new(ms(1), ms(1000)), &[]);
+        assert_eq!(round_trips.timeout(), ms(1000));
+    }
+
+    #[test]
+    fn timeout_is_kept_between_the_bounds() {
+        let config = AdaptiveTimeout::new(ms(20), ms(1000)).with_factor(3.0);
+        assert_eq!(round_trips(config.clone(), &[1, 2]).timeout(), ms(20));
+        assert_eq!(round_trips(config, &[100, 400]).timeout(), ms(1000));
+    }
+
+    #[test]
+    fn timeout_follows_the_most_recent_round_trips() {
+        let config = AdaptiveTimeout::new(ms(1), ms(1000))
+            .with_percentile(1.0)
+            .with_window(3);
+        let round_trips = round_trips(config, &[100, 10, 20, 30]);
+        assert_eq!(round_trips.timeout(), ms(60));
+    }
+}
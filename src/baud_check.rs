@@ -0,0 +1,119 @@
+use std::{
+    ffi::c_int,
+    fs::File,
+    mem::MaybeUninit,
+    os::fd::AsRawFd,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use nix::libc;
+
+use crate::{serial_port::port_baud, sync::Mutex, BaudMismatch};
+
+/// Number of bytes received after opening the port which are judged.
+const SAMPLE_LEN: usize = 64;
+/// Time after opening the port after which a smaller sample is judged.
+const SAMPLE_WINDOW: Duration = Duration::from_secs(3);
+/// The smallest sample which is judged.
+const MIN_SAMPLE_LEN: usize = 16;
+
+/// `struct serial_icounter_struct` from `linux/serial.h`.
+#[repr(C)]
+struct SerialIcounter {
+    cts: c_int,
+    dsr: c_int,
+    rng: c_int,
+    dcd: c_int,
+    rx: c_int,
+    tx: c_int,
+    frame: c_int,
+    overrun: c_int,
+    parity: c_int,
+    brk: c_int,
+    buf_overrun: c_int,
+    reserved: [c_int; 9],
+}
+
+nix::ioctl_read_bad!(tiocgicount, libc::TIOCGICOUNT, SerialIcounter);
+
+/// Framing errors counted by the driver, if it counts them.
+fn port_frame_errors(port: &File) -> Option<u32> {
+    let mut counters = MaybeUninit::<SerialIcounter>::zeroed();
+    // SAFETY: The kernel fills the struct, which matches its layout
+    unsafe { tiocgicount(port.as_raw_fd(), counters.as_mut_ptr()) }.ok()?;
+    let counters = unsafe { counters.assume_init() };
+    Some(counters.frame as u32)
+}
+
+/// The outcome of the check.
+pub enum Verdict {
+    /// Not enough data yet.
+    Pending,
+    Fine,
+    Mismatch(BaudMismatch),
+}
+
+/// Looks for the symptoms of a wrong baud rate in the data received right
+/// after the port was opened. A byte sent at a different baud rate is mostly
+/// read as 0x00 or 0xFF, or not at all because of a framing error.
+pub struct BaudCheck {
+    port: Weak<Mutex<File>>,
+    opened: Instant,
+    baud: Option<u32>,
+    /// Framing errors counted by the driver when the port was opened.
+    frame_errors: Option<u32>,
+    received: usize,
+    suspicious: usize,
+}
+
+impl BaudCheck {
+    pub fn new(port: &Arc<Mutex<File>>) -> Self {
+        let file = port.lock().unwrap();
+        Self {
+            port: Arc::downgrade(port),
+            opened: Instant::now(),
+            baud: port_baud(&file).ok().flatten(),
+            frame_errors: port_frame_errors(&file),
+            received: 0,
+            suspicious: 0,
+        }
+    }
+
+    /// Account the bytes read from the port, returning the verdict once
+    /// enough data was received or the time for it is over.
+    pub fn observe(&mut self, data: &[u8]) -> Verdict {
+        let data = &data[..data.len().min(SAMPLE_LEN - self.received)];
+        self.received += data.len();
+        self.suspicious += data
+            .iter()
+            .filter(|&&byte| byte == 0x00 || byte == 0xFF)
+            .count();
+
+        let window_over = self.opened.elapsed() >= SAMPLE_WINDOW;
+        if self.received < SAMPLE_LEN && !window_over {
+            return Verdict::Pending;
+        }
+        if self.received < MIN_SAMPLE_LEN {
+            // Too quiet to tell
+            return Verdict::Fine;
+        }
+
+        let frame_errors = self.frame_errors.and_then(|before| {
+            let port = self.port.upgrade()?;
+            let now = port_frame_errors(&port.lock().unwrap())?;
+            Some(now.wrapping_sub(before))
+        });
+        let garbled = self.suspicious * 2 >= self.received;
+        let misframed = frame_errors.is_some_and(|count| count as usize * 4 >= self.received);
+        if !garbled && !misframed {
+            return Verdict::Fine;
+        }
+        Verdict::Mismatch(BaudMismatch {
+            baud: self.baud,
+            received: self.received,
+            suspicious: self.suspicious,
+            frame_errors,
+        })
+    }
+}
@@ -0,0 +1,81 @@
+//! Minimal serial terminal, e.g. `miniterm /dev/ttyUSB0 --baud 115200 --echo`.
+//! Press Ctrl-A Ctrl-X to exit, see [`serial_arbiter::Miniterm`] for other commands.
+
+use std::{
+    env,
+    io::{self, IsTerminal},
+    os::fd::AsRawFd,
+    process::ExitCode,
+};
+
+use serial_arbiter::{baud_speed, termios, Arbiter, Miniterm};
+use termios::Termios;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("miniterm: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> io::Result<()> {
+    let mut path = None;
+    let mut baud = None;
+    let mut echo = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--baud" | "-b" => baud = args.next(),
+            "--echo" | "-e" => echo = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(usage()),
+        }
+    }
+    let path = path.ok_or_else(usage)?;
+
+    let arbiter = Arbiter::new();
+    if let Some(baud) = baud {
+        let speed =
+            baud.parse().ok().and_then(baud_speed).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Unsupported baud rate")
+            })?;
+        arbiter.set_termios_hook(move |termios| {
+            let _ = termios::cfsetspeed(termios, speed);
+        });
+    }
+    arbiter.open(&path)?;
+    eprintln!("Connected to {path}. Press Ctrl-A Ctrl-X to exit.");
+
+    // Pass every key press to the port rather than letting the terminal handle it
+    let stdin = io::stdin();
+    let original = if stdin.is_terminal() {
+        let fd = stdin.as_raw_fd();
+        let original = Termios::from_fd(fd)?;
+        let mut raw = original;
+        termios::cfmakeraw(&mut raw);
+        termios::tcsetattr(fd, termios::TCSANOW, &raw)?;
+        Some(original)
+    } else {
+        None
+    };
+
+    let result = Miniterm::new(arbiter)
+        .with_local_echo(echo)
+        .run(stdin, io::stdout());
+
+    if let Some(original) = original {
+        termios::tcsetattr(io::stdin().as_raw_fd(), termios::TCSANOW, &original)?;
+    }
+    eprintln!();
+    result
+}
+
+fn usage() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Usage: miniterm <PORT> [--baud <RATE>] [--echo]",
+    )
+}
@@ -0,0 +1,150 @@
+use std::{
+    io,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::channel::{Receiver, Sender, TryRecvError};
+use log::warn;
+
+use crate::{Arbiter, Direction, SessionLog};
+
+/// How long to wait for data on one side before checking the other.
+const RECEIVE_SLICE: Duration = Duration::from_millis(1);
+
+/// Time given to every forwarded chunk to be transmitted.
+const TRANSMIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Transforms the data forwarded in one direction. Returns the data to
+/// forward, possibly modified, or None to drop it.
+pub type BridgeFilter = Box<dyn FnMut(&[u8]) -> Option<Vec<u8>> + Send>;
+
+/// Direction of the data pumped by a [`bridge`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeDirection {
+    AToB,
+    BToA,
+}
+
+/// Options of a [`bridge`].
+#[derive(Default)]
+pub struct BridgeOptions {
+    a_to_b: Option<BridgeFilter>,
+    b_to_a: Option<BridgeFilter>,
+    tap: Option<Sender<(BridgeDirection, Vec<u8>)>>,
+    log: Option<SessionLog>,
+    stop: Option<Receiver<()>>,
+}
+
+impl BridgeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the filter of the data forwarded from port A to port B.
+    pub fn with_filter_a_to_b(
+        mut self,
+        filter: impl FnMut(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+    ) -> Self {
+        self.a_to_b = Some(Box::new(filter));
+        self
+    }
+
+    /// Sets the filter of the data forwarded from port B to port A.
+    pub fn with_filter_b_to_a(
+        mut self,
+        filter: impl FnMut(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+    ) -> Self {
+        self.b_to_a = Some(Box::new(filter));
+        self
+    }
+
+    /// Sends a copy of all data received on either side, before filtering,
+    /// to the channel. Meant for protocol analyzers.
+    pub fn with_tap(mut self, tap: Sender<(BridgeDirection, Vec<u8>)>) -> Self {
+        self.tap = Some(tap);
+        self
+    }
+
+    /// Writes all data received on either side, before filtering, to the
+    /// session log. Data from A to B is logged as TX, from B to A as RX.
+    pub fn with_log(mut self, log: SessionLog) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    /// Stops the bridge when a message arrives on the channel or all its
+    /// senders are dropped. Without it the bridge runs forever.
+    pub fn with_stop(mut self, stop: Receiver<()>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+}
+
+/// Pumps data between two ports in both directions until stopped, e.g. to
+/// build a man-in-the-middle protocol analyzer or a baud rate converter.
+/// Errors of either port are logged and the bridge carries on, so it
+/// survives reconnects.
+pub fn bridge(a: &Arbiter, b: &Arbiter, mut options: BridgeOptions) -> io::Result<()> {
+    loop {
+        if let Some(stop) = &options.stop {
+            match stop.try_recv() {
+                Err(TryRecvError::Empty) => {}
+                _ => return Ok(()),
+            }
+        }
+        let idle_a = pump(a, b, BridgeDirection::AToB, &mut options);
+        let idle_b = pump(b, a, BridgeDirection::BToA, &mut options);
+        if idle_a && idle_b {
+            // Both ports failed, do not spin while they reconnect
+            thread::sleep(RECEIVE_SLICE);
+        }
+    }
+}
+
+/// Forward the data received from one port to the other. Returns true if
+/// receiving failed.
+fn pump(
+    from: &Arbiter,
+    to: &Arbiter,
+    direction: BridgeDirection,
+    options: &mut BridgeOptions,
+) -> bool {
+    let data = match from.receive(None, Some(Instant::now() + RECEIVE_SLICE)) {
+        Ok(Some(data)) => data,
+        Ok(None) => return false,
+        Err(_) => return true,
+    };
+    if let Some(tap) = &options.tap {
+        let _ = tap.try_send((direction, data.clone()));
+    }
+    if let Some(log) = &mut options.log {
+        let log_direction = match direction {
+            BridgeDirection::AToB => Direction::Tx,
+            BridgeDirection::BToA => Direction::Rx,
+        };
+        if let Err(err) = log.write(log_direction, &data) {
+            warn!("Disabling the bridge log after an error: {err}");
+            options.log = None;
+        }
+    }
+    let filter = match direction {
+        BridgeDirection::AToB => &mut options.a_to_b,
+        BridgeDirection::BToA => &mut options.b_to_a,
+    };
+    let data = match filter {
+        Some(filter) => match filter(&data) {
+            Some(data) => data,
+            None => return false,
+        },
+        None => data,
+    };
+    if data.is_empty() {
+        return false;
+    }
+    if let Err(err) = to.transmit(Arc::from(data), Instant::now() + TRANSMIT_TIMEOUT) {
+        warn!("Bridge dropped data ({direction:?}): {err}");
+    }
+    false
+}
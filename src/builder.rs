@@ -0,0 +1,386 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use termios::Termios;
+
+use crate::{
+    channel::{bounded, Sender},
+    clock::Clock,
+    codec::Codec,
+    connection::Connection,
+    dedup::DuplicateFilter,
+    handler::FrameHandler,
+    history::TrafficHistory,
+    idle_line::IdleLine,
+    meter::LinkMeter,
+    modem::CarrierState,
+    sync::Mutex,
+    usage::UsageRegistry,
+    AdaptiveTimeout, Arbiter, CarrierWatch, CircuitBreaker, DirectionGpio, EmptyTransmit, Envelope,
+    InputPolicy, LineMode, ReconnectRx, RxFilter, SerialConfig, SessionLog, ThreadTuning,
+    WorkerThread,
+};
+
+/// Configures an [`Arbiter`] in one place, before its worker starts, so no
+/// request can see it half configured:
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use serial_arbiter::{Arbiter, LineMode, ThreadTuning};
+/// let arbiter = Arbiter::builder()
+///     .with_path("/dev/ttyUSB0")
+///     .with_line_mode(LineMode::Raw)
+///     .with_escalating_cooloff([Duration::from_millis(100), Duration::from_secs(2)])
+///     .with_read_limits(4096, Some(64 * 1024))
+///     .with_rx_pump(true)
+///     .with_thread_tuning(ThreadTuning::new().with_cpus([3]))
+///     .build()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// Every option is the same as the setter of the arbiter named after it,
+/// which changes it later. The codecs are not configured here, as every
+/// call encoding or decoding frames takes its own.
+pub struct ArbiterBuilder {
+    conn: Arc<Connection>,
+    meter: Arc<Mutex<LinkMeter>>,
+    history: Arc<Mutex<TrafficHistory>>,
+    chan: Sender<Envelope>,
+    worker: WorkerThread,
+    path: Option<PathBuf>,
+    tuning: Option<ThreadTuning>,
+    enqueue_timeout: Option<Duration>,
+}
+
+impl ArbiterBuilder {
+    pub(crate) fn new() -> Self {
+        let conn = Arc::new(Connection::new());
+        let meter = Arc::new(Mutex::new(LinkMeter::new()));
+        let history = Arc::new(Mutex::new(TrafficHistory::default()));
+        let (chan, requests) = bounded::<Envelope>(0);
+        let worker = WorkerThread::new(conn.clone(), meter.clone(), history.clone(), requests);
+        Self {
+            conn,
+            meter,
+            history,
+            chan,
+            worker,
+            path: None,
+            tuning: None,
+            enqueue_timeout: None,
+        }
+    }
+
+    /// Opens the port at the path when built, see [`Arbiter::open`].
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().into());
+        self
+    }
+
+    /// See [`Arbiter::set_line_mode`].
+    pub fn with_line_mode(self, mode: LineMode) -> Self {
+        self.conn.set_line_mode(mode);
+        self
+    }
+
+    /// See [`Arbiter::set_config`].
+    pub fn with_config(self, config: SerialConfig) -> Self {
+        self.conn.set_config(config);
+        self
+    }
+
+    /// See [`Arbiter::set_clocal`].
+    pub fn with_clocal(self, clocal: bool) -> Self {
+        self.conn.set_clocal(Some(clocal));
+        self
+    }
+
+    /// See [`Arbiter::set_hupcl`].
+    pub fn with_hupcl(self, hupcl: bool) -> Self {
+        self.conn.set_hupcl(Some(hupcl));
+        self
+    }
+
+    /// See [`Arbiter::set_break_input`].
+    pub fn with_break_input(self, policy: InputPolicy) -> Self {
+        self.conn.set_break_input(Some(policy));
+        self
+    }
+
+    /// See [`Arbiter::set_parity_error_input`].
+    pub fn with_parity_error_input(self, policy: InputPolicy) -> Self {
+        self.conn.set_parity_error_input(Some(policy));
+        self
+    }
+
+    /// See [`Arbiter::set_sync_io`].
+    pub fn with_sync_io(self, sync_io: bool) -> Self {
+        self.conn.set_sync_io(sync_io);
+        self
+    }
+
+    /// See [`Arbiter::set_termios_hook`].
+    pub fn with_termios_hook(self, hook: impl FnMut(&mut Termios) + Send + 'static) -> Self {
+        self.conn.set_termios_hook(Some(Box::new(hook)));
+        self
+    }
+
+    /// See [`Arbiter::set_restore_termios`].
+    pub fn with_restore_termios(self, restore: bool) -> Self {
+        self.conn.set_restore_termios(restore);
+        self
+    }
+
+    /// See [`Arbiter::set_custom_divisor`].
+    pub fn with_custom_divisor(self, divisor: u32) -> Self {
+        self.conn.set_custom_divisor(Some(divisor));
+        self
+    }
+
+    /// See [`Arbiter::set_uart_type`].
+    pub fn with_uart_type(self, uart_type: i32) -> Self {
+        self.conn.set_uart_type(Some(uart_type));
+        self
+    }
+
+    /// See [`Arbiter::set_latency_timer`].
+    pub fn with_latency_timer(self, latency: u8) -> Self {
+        self.conn.set_latency_timer(Some(latency));
+        self
+    }
+
+    /// See [`Arbiter::set_reconnect_rx`].
+    pub fn with_reconnect_rx(self, policy: ReconnectRx) -> Self {
+        self.conn.set_reconnect_rx(policy);
+        self
+    }
+
+    /// See [`Arbiter::set_max_reconnect_attempts`].
+    pub fn with_max_reconnect_attempts(self, max_attempts: usize) -> Self {
+        self.conn.set_max_attempts(Some(max_attempts));
+        self
+    }
+
+    /// See [`Arbiter::set_auto_reopen`].
+    pub fn with_auto_reopen(self, auto_reopen: bool) -> Self {
+        self.conn.set_auto_reopen(auto_reopen);
+        self
+    }
+
+    /// See [`Arbiter::set_cooloff_duration`].
+    pub fn with_cooloff_duration(self, cooloff: Option<Duration>) -> Self {
+        self.conn.set_cooloff_duration(cooloff);
+        self
+    }
+
+    /// See [`Arbiter::set_escalating_cooloff`].
+    pub fn with_escalating_cooloff(self, steps: impl Into<Vec<Duration>>) -> Self {
+        self.conn.set_escalating_cooloff(steps.into());
+        self
+    }
+
+    /// See [`Arbiter::set_connect_timeout`].
+    pub fn with_connect_timeout(self, timeout: Duration) -> Self {
+        self.conn.set_connect_timeout(Some(timeout));
+        self
+    }
+
+    /// See [`Arbiter::set_open_retries`].
+    pub fn with_open_retries(self, retries: usize, delay: Duration) -> Self {
+        self.conn.set_open_retries(retries, delay);
+        self
+    }
+
+    /// See [`Arbiter::set_reopen_on_resume`].
+    pub fn with_reopen_on_resume(self, reopen: bool) -> Self {
+        self.conn.set_reopen_on_resume(reopen);
+        self
+    }
+
+    /// Replaces the real time in the timing of the cooloffs, the retries,
+    /// the connect timeouts, the schedule and the duplicate window, see
+    /// [`Clock`]. The deadlines of the I/O stay in real time. Set only here,
+    /// as changing the clock of a running arbiter would skew that timing.
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        self.conn.set_clock(clock);
+        self
+    }
+
+    /// See [`Arbiter::set_watchdog`].
+    pub fn with_watchdog(self, window: Duration) -> Self {
+        self.conn.set_watchdog(Some(window));
+        self
+    }
+
+    /// See [`Arbiter::set_adaptive_timeout`].
+    pub fn with_adaptive_timeout(self, adaptive: AdaptiveTimeout) -> Self {
+        self.conn.set_adaptive_timeout(Some(adaptive));
+        self
+    }
+
+    /// See [`Arbiter::set_circuit_breaker`].
+    pub fn with_circuit_breaker(self, policy: CircuitBreaker) -> Self {
+        self.conn.set_circuit_breaker(Some(policy));
+        self
+    }
+
+    /// See [`Arbiter::with_enqueue_timeout`], which the built arbiter is
+    /// like, and so are its clones.
+    pub fn with_enqueue_timeout(mut self, timeout: Duration) -> Self {
+        self.enqueue_timeout = Some(timeout);
+        self
+    }
+
+    /// See [`Arbiter::set_tx_queue_limit`].
+    pub fn with_tx_queue_limit(self, limit: usize) -> Self {
+        self.conn.set_tx_queue_limit(Some(limit));
+        self
+    }
+
+    /// See [`Arbiter::set_read_limits`].
+    pub fn with_read_limits(mut self, chunk_size: usize, budget: Option<usize>) -> Self {
+        self.worker.buff.set_read_limits(chunk_size, budget);
+        self
+    }
+
+    /// See [`Arbiter::set_utilization_window`].
+    pub fn with_utilization_window(self, window: Duration) -> Self {
+        self.meter.lock().unwrap().set_window(window);
+        self
+    }
+
+    /// See [`Arbiter::set_traffic_history`].
+    pub fn with_traffic_history(mut self, capacity: usize) -> Self {
+        self.history.lock().unwrap().set_capacity(capacity);
+        self.worker.update_capture();
+        self
+    }
+
+    /// See [`Arbiter::set_empty_transmit`].
+    pub fn with_empty_transmit(mut self, policy: EmptyTransmit) -> Self {
+        self.worker.empty_transmit = policy;
+        self
+    }
+
+    /// See [`Arbiter::set_idle_line_timeout`].
+    pub fn with_idle_line_timeout(mut self, timeout: Duration) -> Self {
+        self.worker.idle_line = Some(IdleLine::new(timeout, self.conn.clock().now()));
+        self
+    }
+
+    /// See [`Arbiter::set_rx_pump`].
+    pub fn with_rx_pump(mut self, enabled: bool) -> Self {
+        self.worker.rx_pump_enabled = enabled;
+        self
+    }
+
+    /// See [`Arbiter::set_thread_tuning`]. Building fails if the tuning
+    /// cannot be applied.
+    pub fn with_thread_tuning(mut self, tuning: ThreadTuning) -> Self {
+        self.tuning = Some(tuning);
+        self
+    }
+
+    /// See [`Arbiter::set_rx_filter`].
+    pub fn with_rx_filter(mut self, filter: RxFilter) -> Self {
+        self.worker.buff.set_filter(Some(filter));
+        self
+    }
+
+    /// See [`Arbiter::set_rx_transform`].
+    pub fn with_rx_transform(
+        mut self,
+        transform: impl FnMut(&[u8], &mut Vec<u8>) + Send + 'static,
+    ) -> Self {
+        self.worker.buff.set_transform(Some(Box::new(transform)));
+        self
+    }
+
+    /// See [`Arbiter::set_tx_transform`].
+    pub fn with_tx_transform(
+        mut self,
+        transform: impl FnMut(&[u8], &mut Vec<u8>) + Send + 'static,
+    ) -> Self {
+        self.worker.tx_transform = Some(Box::new(transform));
+        self
+    }
+
+    /// See [`Arbiter::on_frame`].
+    pub fn with_frame_handler(
+        mut self,
+        codec: impl Codec + 'static,
+        callback: impl FnMut(io::Result<Vec<u8>>) + Send + 'static,
+    ) -> Self {
+        self.worker.frame_handler = Some(FrameHandler::new(Box::new(codec), Box::new(callback)));
+        self
+    }
+
+    /// See [`Arbiter::set_session_log`].
+    pub fn with_session_log(mut self, log: SessionLog) -> Self {
+        self.worker.session_log = Some(log);
+        self.worker.update_capture();
+        self
+    }
+
+    /// See [`Arbiter::set_duplicate_window`].
+    pub fn with_duplicate_window(mut self, window: Duration) -> Self {
+        self.worker.duplicates = Some(DuplicateFilter::new(window));
+        self
+    }
+
+    /// See [`Arbiter::set_schedule_gap`].
+    pub fn with_schedule_gap(mut self, gap: Duration) -> Self {
+        self.worker.scheduler.set_gap(gap);
+        self
+    }
+
+    /// See [`Arbiter::set_direction_gpio`].
+    pub fn with_direction_gpio(mut self, gpio: DirectionGpio) -> Self {
+        self.worker.direction_gpio = Some(gpio);
+        self
+    }
+
+    /// See [`Arbiter::set_carrier_watch`].
+    pub fn with_carrier_watch(mut self, watch: CarrierWatch) -> Self {
+        self.worker.carrier = Some(CarrierState::new(watch));
+        self
+    }
+
+    /// Starts the worker and opens the port if the path is given. Fails
+    /// with the error of opening the port or of tuning the threads.
+    pub fn build(mut self) -> io::Result<Arbiter> {
+        // Before the worker is started or the port touched
+        self.conn.config().validate()?;
+        let path = self.path.take();
+        let tuning = self.tuning.take();
+        let arbiter = self.spawn();
+        if let Some(tuning) = tuning {
+            arbiter.set_thread_tuning(tuning)?;
+        }
+        if let Some(path) = path {
+            arbiter.open(path)?;
+        }
+        Ok(arbiter)
+    }
+
+    /// Starts the worker, leaving the path and the tuning to the caller.
+    pub(crate) fn spawn(self) -> Arbiter {
+        self.worker.spawn();
+        let clients = Arc::new(Mutex::new(UsageRegistry::default()));
+        let usage = clients.lock().unwrap().register(None);
+        Arbiter {
+            conn: self.conn,
+            chan: self.chan,
+            meter: self.meter,
+            history: self.history,
+            clients,
+            usage,
+            enqueue_timeout: self.enqueue_timeout,
+            tag: None,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Channels of the public API, e.g. of [`Arbiter::events`](crate::Arbiter::events).
+//!
+//! These are the crossbeam channels, unless the default `crossbeam` feature
+//! is disabled, in which case `std::sync::mpsc` is used under the same names.
+//! Under loom they are modeled by the `sync` shim.
+
+#[cfg(all(feature = "crossbeam", not(loom)))]
+pub use crossbeam::channel::{
+    bounded, unbounded, Receiver, RecvError, RecvTimeoutError, SendError, SendTimeoutError, Sender,
+    TryRecvError, TrySendError,
+};
+
+#[cfg(loom)]
+pub use crate::sync::channel::{
+    bounded, unbounded, Receiver, RecvError, RecvTimeoutError, SendError, Sender, TryRecvError,
+    TrySendError,
+};
+
+#[cfg(not(any(feature = "crossbeam", loom)))]
+pub use std::sync::mpsc::{
+    Receiver, RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError,
+};
+
+#[cfg(not(any(feature = "crossbeam", loom)))]
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Interval of retrying to send to a full bounded channel until a timeout.
+#[cfg(not(any(feature = "crossbeam", loom)))]
+const SEND_RETRY_INTERVAL: Duration = Duration::from_micros(100);
+
+/// An error returned from [`Sender::send_timeout`].
+#[cfg(any(not(feature = "crossbeam"), loom))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SendTimeoutError<T> {
+    Timeout(T),
+    Disconnected(T),
+}
+
+/// The sending side of a bounded or an unbounded channel.
+#[cfg(not(any(feature = "crossbeam", loom)))]
+#[derive(Debug)]
+pub struct Sender<T>(Flavor<T>);
+
+#[cfg(not(any(feature = "crossbeam", loom)))]
+#[derive(Debug)]
+enum Flavor<T> {
+    Bounded(mpsc::SyncSender<T>),
+    Unbounded(mpsc::Sender<T>),
+}
+
+#[cfg(not(any(feature = "crossbeam", loom)))]
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        match &self.0 {
+            Flavor::Bounded(sender) => Sender(Flavor::Bounded(sender.clone())),
+            Flavor::Unbounded(sender) => Sender(Flavor::Unbounded(sender.clone())),
+        }
+    }
+}
+
+#[cfg(not(any(feature = "crossbeam", loom)))]
+impl<T> Sender<T> {
+    /// Sends the message, blocking while the channel is full.
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        match &self.0 {
+            Flavor::Bounded(sender) => sender.send(msg),
+            Flavor::Unbounded(sender) => sender.send(msg),
+        }
+    }
+
+    /// Sends the message, blocking while the channel is full, but for no
+    /// longer than the timeout.
+    pub fn send_timeout(&self, msg: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        let deadline = Instant::now() + timeout;
+        let mut msg = msg;
+        loop {
+            msg = match self.try_send(msg) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(msg)) => {
+                    return Err(SendTimeoutError::Disconnected(msg))
+                }
+                Err(TrySendError::Full(msg)) => msg,
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(SendTimeoutError::Timeout(msg));
+            }
+            thread::sleep(SEND_RETRY_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Sends the message if the channel is not full.
+    pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        match &self.0 {
+            Flavor::Bounded(sender) => sender.try_send(msg),
+            Flavor::Unbounded(sender) => sender
+                .send(msg)
+                .map_err(|SendError(msg)| TrySendError::Disconnected(msg)),
+        }
+    }
+}
+
+/// Creates a channel holding at most `cap` messages. With zero capacity
+/// every send waits for a receiver.
+#[cfg(not(any(feature = "crossbeam", loom)))]
+pub fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    let (sender, receiver) = mpsc::sync_channel(cap);
+    (Sender(Flavor::Bounded(sender)), receiver)
+}
+
+/// Creates a channel of unlimited capacity.
+#[cfg(not(any(feature = "crossbeam", loom)))]
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    let (sender, receiver) = mpsc::channel();
+    (Sender(Flavor::Unbounded(sender)), receiver)
+}
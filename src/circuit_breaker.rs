@@ -0,0 +1,225 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::{ArbiterError, Event};
+
+/// Stops the transactions from hitting a device which keeps failing, see
+/// [`Arbiter::set_circuit_breaker`](crate::Arbiter::set_circuit_breaker).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    /// Number of transactions failing in a row which opens the circuit.
+    pub failures: u32,
+    /// Time the circuit stays open before a transaction is let through to
+    /// try the device again.
+    pub backoff: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(failures: u32, backoff: Duration) -> Self {
+        Self {
+            failures: failures.max(1),
+            backoff,
+        }
+    }
+}
+
+enum State {
+    Closed,
+    /// Until the time the next transaction may try the device.
+    Open(Instant),
+    /// A transaction is trying the device.
+    HalfOpen,
+}
+
+/// The state of the circuit breaker of a connection.
+pub struct Circuit {
+    policy: CircuitBreaker,
+    state: State,
+    /// Transactions failed in a row while closed.
+    failures: u32,
+}
+
+impl Circuit {
+    pub fn new(policy: CircuitBreaker) -> Self {
+        Self {
+            policy,
+            state: State::Closed,
+            failures: 0,
+        }
+    }
+
+    /// Lets a transaction through, or fails it right away while the circuit
+    /// is open. Returns the event of the transition to half-open.
+    pub fn admit(&mut self, now: Instant) -> io::Result<Option<Event>> {
+        match self.state {
+            State::Closed => Ok(None),
+            State::Open(until) if now < until => Err(ArbiterError::CircuitOpen {
+                retry_in: until - now,
+            }
+            .into()),
+            State::Open(_) => {
+                self.state = State::HalfOpen;
+                Ok(Some(Event::CircuitHalfOpen))
+            }
+            // Until the transaction trying the device is done
+            State::HalfOpen => Err(ArbiterError::CircuitOpen {
+                retry_in: Duration::ZERO,
+            }
+            .into()),
+        }
+    }
+
+    /// Account the outcome of a transaction which was let through. Returns
+    /// the event of the transition it caused, if any.
+    pub fn record(&mut self, now: Instant, error: Option<&io::Error>) -> Option<Event> {
+        match (&self.state, error) {
+            // A late outcome of a transaction let through before it opened
+            (State::Open(_), _) => None,
+            (State::HalfOpen, Some(err)) if !is_device_failure(err) => {
+                // Let the next transaction try the device instead
+                self.state = State::Open(now);
+                None
+            }
+            (_, Some(err)) if !is_device_failure(err) => None,
+            (State::Closed, None) => {
+                self.failures = 0;
+                None
+            }
+            (State::HalfOpen, None) => {
+                self.failures = 0;
+                self.state = State::Closed;
+                Some(Event::CircuitClosed)
+            }
+            (State::Closed, Some(_)) if self.failures + 1 < self.policy.failures => {
+                self.failures += 1;
+                None
+            }
+            (_, Some(_)) => {
+                self.failures = 0;
+                self.state = State::Open(now + self.policy.backoff);
+                Some(Event::CircuitOpened(self.policy.backoff))
+            }
+        }
+    }
+}
+
+/// Returns false for the errors which tell nothing about the device, as the
+/// transaction never reached it.
+fn is_device_failure(err: &io::Error) -> bool {
+    !matches!(
+        ArbiterError::of(err),
+        Some(
+            ArbiterError::Paused
+                | ArbiterError::ShuttingDown
+                | ArbiterError::Reentrant
+                | ArbiterError::EnqueueTimeout { .. }
+                | ArbiterError::CircuitOpen { .. }
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::ErrorKind, time::Duration};
+
+    use super::{Circuit, CircuitBreaker};
+    use crate::{ArbiterError, Clock, Event, ManualClock};
+
+    fn retry_in(circuit: &mut Circuit, clock: &ManualClock) -> Option<Duration> {
+        match circuit.admit(clock.now()) {
+            Err(err) => match ArbiterError::of(&err) {
+                Some(ArbiterError::CircuitOpen { retry_in }) => Some(*retry_in),
+                _ => panic!("Unexpected error {err}"),
+            },
+            Ok(_) => None,
+        }
+    }
+
+    #[test]
+    fn opens_after_the_failures_in_a_row_and_closes_after_a_success() {
+        let clock = ManualClock::new();
+        let mut circuit = Circuit::new(CircuitBreaker::new(3, Duration::from_secs(10)));
+        let timed_out = || ErrorKind::TimedOut.into();
+
+        // A success in between starts the count over
+        for _ in 0..2 {
+            assert!(circuit.admit(clock.now()).unwrap().is_none());
+            assert_eq!(circuit.record(clock.now(), Some(&timed_out())), None);
+        }
+        assert_eq!(circuit.record(clock.now(), None), None);
+        for _ in 0..2 {
+            assert_eq!(circuit.record(clock.now(), Some(&timed_out())), None);
+        }
+        assert_eq!(
+            circuit.record(clock.now(), Some(&timed_out())),
+            Some(Event::CircuitOpened(Duration::from_secs(10)))
+        );
+
+        // Failing fast for the backoff
+        assert_eq!(
+            retry_in(&mut circuit, &clock),
+            Some(Duration::from_secs(10))
+        );
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(retry_in(&mut circuit, &clock), Some(Duration::from_secs(6)));
+
+        // Then a single transaction tries the device
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(
+            circuit.admit(clock.now()).unwrap(),
+            Some(Event::CircuitHalfOpen)
+        );
+        assert_eq!(retry_in(&mut circuit, &clock), Some(Duration::ZERO));
+        assert_eq!(
+            circuit.record(clock.now(), None),
+            Some(Event::CircuitClosed)
+        );
+        assert!(circuit.admit(clock.now()).unwrap().is_none());
+    }
+
+    #[test]
+    fn reopens_when_the_trial_transaction_fails() {
+        let clock = ManualClock::new();
+        let mut circuit = Circuit::new(CircuitBreaker::new(1, Duration::from_secs(10)));
+        let timed_out = ErrorKind::TimedOut.into();
+
+        circuit.admit(clock.now()).unwrap();
+        assert!(circuit.record(clock.now(), Some(&timed_out)).is_some());
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(
+            circuit.admit(clock.now()).unwrap(),
+            Some(Event::CircuitHalfOpen)
+        );
+        assert_eq!(
+            circuit.record(clock.now(), Some(&timed_out)),
+            Some(Event::CircuitOpened(Duration::from_secs(10)))
+        );
+        assert_eq!(
+            retry_in(&mut circuit, &clock),
+            Some(Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn errors_which_never_reached_the_device_are_not_counted() {
+        let clock = ManualClock::new();
+        let mut circuit = Circuit::new(CircuitBreaker::new(1, Duration::from_secs(10)));
+        let paused = ArbiterError::Paused.into();
+
+        circuit.admit(clock.now()).unwrap();
+        assert_eq!(circuit.record(clock.now(), Some(&paused)), None);
+        assert!(circuit.admit(clock.now()).unwrap().is_none());
+
+        // Nor do they close or reopen the circuit, the next transaction tries instead
+        circuit.record(clock.now(), Some(&ErrorKind::TimedOut.into()));
+        clock.advance(Duration::from_secs(10));
+        circuit.admit(clock.now()).unwrap();
+        assert_eq!(circuit.record(clock.now(), Some(&paused)), None);
+        assert_eq!(
+            circuit.admit(clock.now()).unwrap(),
+            Some(Event::CircuitHalfOpen)
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::sync::Mutex;
+
+/// Source of the time of the logic around the I/O: the cooloffs, the
+/// retries of opening the port, the connect timeouts, the scheduled frames,
+/// the duplicate window of [`Arbiter::set_duplicate_window`](crate::Arbiter::set_duplicate_window)
+/// and the backoff of [`Arbiter::transact_with_retry`](crate::Arbiter::transact_with_retry).
+/// Replaced by a [`ManualClock`] in tests, see
+/// [`ArbiterBuilder::with_clock`](crate::ArbiterBuilder::with_clock), so that
+/// logic runs deterministically and faster than real time.
+///
+/// Everything timed by the I/O itself stays in real time: the deadlines
+/// and the polling of the port, which the kernel enforces, the time stamps
+/// of the received data and the measured round trips.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real time, which is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Clock which stands still until advanced. Sleeping advances it right away
+/// by the time slept, so waiting for a cooloff takes no real time:
+///
+/// ```no_run
+/// # use std::{sync::Arc, time::Duration};
+/// # use serial_arbiter::{Arbiter, ManualClock};
+/// let clock = Arc::new(ManualClock::new());
+/// let arbiter = Arbiter::builder()
+///     .with_clock(clock.clone())
+///     .with_cooloff_duration(Some(Duration::from_secs(5)))
+///     .build()?;
+/// // Fail the port, then
+/// clock.advance(Duration::from_secs(5));
+/// // and the cooloff is over
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    /// Creates the clock showing the current time.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        io::ErrorKind,
+        os::unix::fs::symlink,
+        process,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use nix::{pty::openpty, unistd::ttyname};
+
+    use super::{Clock, ManualClock};
+    use crate::{Arbiter, ArbiterError, Event};
+
+    #[test]
+    fn cooloff_ends_when_the_manual_clock_is_advanced() {
+        let clock = Arc::new(ManualClock::new());
+        let arbiter = Arbiter::builder()
+            .with_clock(clock.clone())
+            .with_cooloff_duration(Some(Duration::from_secs(5)))
+            .build()
+            .unwrap();
+        let events = arbiter.events();
+        let transmit = || arbiter.transmit(b"AT\r"[..].into(), Instant::now());
+
+        let err = arbiter.open("/dev/serial-arbiter-missing").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(matches!(events.try_recv(), Ok(Event::CooloffStarted(_))));
+        let cooloff = arbiter.cooloff().unwrap();
+        assert_eq!(cooloff.remaining, Duration::from_secs(5));
+
+        // The clock stands still, so the cooloff never ends by itself
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(transmit().unwrap_err().kind(), ErrorKind::QuotaExceeded);
+        let cooloff = arbiter.cooloff().unwrap();
+        assert_eq!(cooloff.remaining, Duration::from_secs(2));
+
+        // Over, so the port is tried again and the next cooloff starts
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(transmit().unwrap_err().kind(), ErrorKind::NotFound);
+        assert!(matches!(events.try_recv(), Ok(Event::CooloffEnded)));
+        assert!(matches!(events.try_recv(), Ok(Event::CooloffStarted(_))));
+        let cooloff = arbiter.cooloff().unwrap();
+        assert_eq!(cooloff.remaining, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn cooloff_escalates_until_the_port_opens() {
+        let clock = Arc::new(ManualClock::new());
+        let arbiter = Arbiter::builder()
+            .with_clock(clock.clone())
+            .build()
+            .unwrap();
+        let steps = [1, 5, 20].map(Duration::from_secs);
+        arbiter.set_escalating_cooloff(steps);
+        let deadline = || Instant::now() + Duration::from_secs(1);
+        let transmit = || arbiter.transmit(b"AT\r"[..].into(), deadline());
+        let remaining = || arbiter.cooloff().unwrap().remaining;
+
+        // The port appears at the path once it is linked to the pty
+        let pty = openpty(None, None).unwrap();
+        let link = env::temp_dir().join(format!("serial-arbiter-escalating-{}", process::id()));
+        let _ = fs::remove_file(&link);
+
+        let err = arbiter.open(&link).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(remaining(), steps[0]);
+        for step in [steps[1], steps[2], steps[2]] {
+            clock.advance(remaining());
+            assert_eq!(transmit().unwrap_err().kind(), ErrorKind::NotFound);
+            assert_eq!(remaining(), step);
+        }
+
+        // Opening the port starts the escalation over
+        symlink(ttyname(&pty.slave).unwrap(), &link).unwrap();
+        clock.advance(remaining());
+        transmit().unwrap();
+        arbiter.close();
+        fs::remove_file(&link).unwrap();
+        assert_eq!(transmit().unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(remaining(), steps[0]);
+    }
+
+    #[test]
+    fn reopening_is_given_up_after_the_max_attempts() {
+        let clock = Arc::new(ManualClock::new());
+        let arbiter = Arbiter::builder()
+            .with_clock(clock.clone())
+            .with_cooloff_duration(Some(Duration::from_secs(5)))
+            .build()
+            .unwrap();
+        arbiter.set_max_reconnect_attempts(Some(2));
+        let events = arbiter.events();
+        let transmit = || arbiter.transmit(b"AT\r"[..].into(), Instant::now());
+
+        let err = arbiter.open("/dev/serial-arbiter-missing").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(matches!(events.try_recv(), Ok(Event::CooloffStarted(_))));
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(transmit().unwrap_err().kind(), ErrorKind::NotFound);
+        assert!(matches!(events.try_recv(), Ok(Event::CooloffEnded)));
+        assert_eq!(events.try_recv(), Ok(Event::Failed));
+        assert!(arbiter.is_failed());
+
+        // Not even tried again once the cooloff is over
+        clock.advance(Duration::from_secs(5));
+        let err = transmit().unwrap_err();
+        assert_eq!(
+            ArbiterError::of(&err),
+            Some(&ArbiterError::Failed { attempts: 2 })
+        );
+        assert!(events.try_recv().is_err());
+
+        // Until asked explicitly
+        let err = arbiter.open("/dev/serial-arbiter-missing").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(!arbiter.is_failed());
+    }
+
+    #[test]
+    fn opening_is_retried_on_a_hotplug_race() {
+        let clock = Arc::new(ManualClock::new());
+        let arbiter = Arbiter::builder()
+            .with_clock(clock.clone())
+            .with_cooloff_duration(Some(Duration::from_secs(5)))
+            .build()
+            .unwrap();
+        arbiter.set_open_retries(3, Duration::from_millis(100));
+        let events = arbiter.events();
+
+        // Not writable even by root, so it fails like a port udev has not
+        // given the permissions to yet
+        let start = clock.now();
+        let err = arbiter.open("/proc/sys/kernel/osrelease").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(clock.now() - start, Duration::from_millis(300));
+        // The retries are a single attempt
+        assert!(matches!(events.try_recv(), Ok(Event::CooloffStarted(_))));
+        assert!(events.try_recv().is_err());
+
+        // Other failures are not retried
+        clock.advance(Duration::from_secs(5));
+        let start = clock.now();
+        let err = arbiter.open("/dev/serial-arbiter-missing").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(clock.now(), start);
+    }
+}
@@ -0,0 +1,110 @@
+use std::io::{self, ErrorKind};
+
+pub use serial_arbiter_codec::{CobsCodec, LineCodec};
+
+/// Turns messages into delimited frames on the wire and back.
+///
+/// Codecs can be layered: a layer transforms the message (e.g. compresses
+/// it) and hands the result to the codec it wraps, which does the framing.
+pub trait Codec: Send {
+    /// The byte terminating every frame on the wire.
+    fn delimiter(&self) -> u8;
+
+    /// Encodes a message into a frame, including the delimiter.
+    fn encode(&mut self, msg: &[u8]) -> io::Result<Vec<u8>>;
+
+    /// Decodes a frame, without the delimiter, back into the message.
+    fn decode(&mut self, frame: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+impl Codec for LineCodec {
+    fn delimiter(&self) -> u8 {
+        self.delimiter
+    }
+
+    fn encode(&mut self, msg: &[u8]) -> io::Result<Vec<u8>> {
+        LineCodec::encode(self, msg).map_err(codec_error)
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> io::Result<Vec<u8>> {
+        LineCodec::decode(self, frame).map_err(codec_error)
+    }
+}
+
+impl Codec for CobsCodec {
+    fn delimiter(&self) -> u8 {
+        CobsCodec::DELIMITER
+    }
+
+    fn encode(&mut self, msg: &[u8]) -> io::Result<Vec<u8>> {
+        CobsCodec::encode(self, msg).map_err(codec_error)
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> io::Result<Vec<u8>> {
+        CobsCodec::decode(self, frame).map_err(codec_error)
+    }
+}
+
+/// Converts the error of the codec core, which works without std.
+fn codec_error(err: serial_arbiter_codec::Error) -> io::Error {
+    let kind = match err {
+        serial_arbiter_codec::Error::InvalidFrame => ErrorKind::InvalidData,
+        _ => ErrorKind::InvalidInput,
+    };
+    io::Error::new(kind, err)
+}
+
+/// Compresses every message with raw DEFLATE before handing it to the inner
+/// codec, e.g. `Deflate::new(CobsCodec)`. Meant for low-bandwidth links;
+/// both ends of the wire must use the same layers.
+#[cfg(feature = "compression")]
+#[derive(Debug, Clone, Copy)]
+pub struct Deflate<C> {
+    inner: C,
+    level: u8,
+    max_size: usize,
+}
+
+#[cfg(feature = "compression")]
+impl<C: Codec> Deflate<C> {
+    /// Wraps the codec using the default compression level and
+    /// a 64 KiB limit of the decompressed message size.
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            level: 6,
+            max_size: 64 * 1024,
+        }
+    }
+
+    /// Sets the compression level from 0 (none) to 10 (best).
+    pub fn with_level(mut self, level: u8) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the limit of the decompressed message size, which protects
+    /// against frames inflating into huge messages.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+#[cfg(feature = "compression")]
+impl<C: Codec> Codec for Deflate<C> {
+    fn delimiter(&self) -> u8 {
+        self.inner.delimiter()
+    }
+
+    fn encode(&mut self, msg: &[u8]) -> io::Result<Vec<u8>> {
+        let compressed = miniz_oxide::deflate::compress_to_vec(msg, self.level);
+        self.inner.encode(&compressed)
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> io::Result<Vec<u8>> {
+        let compressed = self.inner.decode(frame)?;
+        miniz_oxide::inflate::decompress_to_vec_with_limit(&compressed, self.max_size)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err.to_string()))
+    }
+}
@@ -1,24 +1,91 @@
 use std::{
+    cell::RefCell,
+    fmt::Display,
     fs::File,
     io::{self, ErrorKind},
+    os::fd::{AsRawFd, RawFd},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{atomic::Ordering, Arc, OnceLock},
+    thread::{self, ThreadId},
     time::{Duration, Instant},
 };
 
-use crate::serial_port::port_open;
+use log::{debug, error, info, warn};
+use nix::libc;
+use termios::Termios;
+
+use crate::adaptive_timeout::{AdaptiveTimeout, RoundTrips};
+use crate::circuit_breaker::{Circuit, CircuitBreaker};
+use crate::clock::{Clock, SystemClock};
+use crate::error::ArbiterError;
+use crate::events::{BaudMismatch, Cooloff, Event, Events};
+use crate::rx_buffer::ReconnectRx;
+use crate::serial_config::SerialConfig;
+use crate::serial_port::{
+    port_adopt, port_char_rate, port_is_alive, port_open, port_set_termios, termios_hupcl,
+    InputPolicy, LineMode, PortOpenError, PortOptions, TermiosHook,
+};
+use crate::sync::{AtomicU64, Mutex};
+use crate::watchdog::Watchdog;
 
 const DEFAULT_COOLOFF_DURATION: Duration = Duration::from_secs(1);
+/// Pause between connection attempts when there is no cooloff.
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+/// Retries of an open failing right after plug-in, see [`is_hotplug_race`].
+const DEFAULT_OPEN_RETRIES: usize = 3;
+const DEFAULT_OPEN_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+thread_local! {
+    /// The connections whose worker is parked for a closure running on this
+    /// thread, see [`Connection::park_here`].
+    static PARKED: RefCell<Vec<*const Connection>> = const { RefCell::new(Vec::new()) };
+}
 
 pub struct Connection {
     inner: Mutex<ConnectionInner>,
+    pub events: Events,
+    /// The thread of the worker, which must not wait for itself.
+    worker_thread: OnceLock<ThreadId>,
+    /// The id of the last transaction, shared by all the handles.
+    transactions: AtomicU64,
 }
 
 struct ConnectionInner {
     path: Option<PathBuf>,
     file: Option<Arc<Mutex<File>>>,
+    fd: Option<RawFd>,
+    char_rate: Option<f64>,
+    generation: u64,
     last_conn_attempt: Option<Instant>,
     cool_time: Option<Duration>,
+    /// Cooloffs after consecutive failed attempts, overriding `cool_time`.
+    cool_steps: Vec<Duration>,
+    connect_timeout: Option<Duration>,
+    open_retries: usize,
+    open_retry_delay: Duration,
+    tx_queue_limit: Option<usize>,
+    reopen_on_resume: bool,
+    clock: Arc<dyn Clock>,
+    watchdog: Option<Watchdog>,
+    round_trips: Option<RoundTrips>,
+    circuit: Option<Circuit>,
+    /// Time after which no pending request is served, once shut down.
+    shutdown: Option<Instant>,
+    options: PortOptions,
+    /// Shared, so it runs without the state locked.
+    termios_hook: Option<Arc<Mutex<TermiosHook>>>,
+    original_termios: Option<Termios>,
+    restore_termios: bool,
+    reconnect_rx: ReconnectRx,
+    auto_reopen: bool,
+    max_attempts: Option<usize>,
+    failed_attempts: usize,
+    /// Why the connection was last closed due to a failure.
+    last_failure: Option<String>,
+    /// Why the connection is cooling off, if it is.
+    cooloff_reason: Option<String>,
+    /// Symptoms of a wrong baud rate found on the current connection.
+    baud_mismatch: Option<BaudMismatch>,
 }
 
 impl Connection {
@@ -26,54 +93,370 @@ impl Connection {
         let state = ConnectionInner {
             path: None,
             file: None,
+            fd: None,
+            char_rate: None,
+            generation: 0,
             last_conn_attempt: None,
             cool_time: Some(DEFAULT_COOLOFF_DURATION),
+            cool_steps: Vec::new(),
+            connect_timeout: None,
+            open_retries: DEFAULT_OPEN_RETRIES,
+            open_retry_delay: DEFAULT_OPEN_RETRY_DELAY,
+            tx_queue_limit: None,
+            reopen_on_resume: false,
+            clock: Arc::new(SystemClock),
+            watchdog: None,
+            round_trips: None,
+            circuit: None,
+            shutdown: None,
+            options: PortOptions::default(),
+            termios_hook: None,
+            original_termios: None,
+            restore_termios: false,
+            reconnect_rx: ReconnectRx::default(),
+            auto_reopen: true,
+            max_attempts: None,
+            failed_attempts: 0,
+            last_failure: None,
+            cooloff_reason: None,
+            baud_mismatch: None,
         };
         Self {
             inner: Mutex::new(state),
+            events: Events::new(),
+            worker_thread: OnceLock::new(),
+            transactions: AtomicU64::new(0),
         }
     }
 
+    /// Returns a new id for a transaction, which all its attempts carry.
+    pub fn next_transaction_id(&self) -> u64 {
+        self.transactions.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Mark the current thread as the one of the worker.
+    pub fn set_worker_thread(&self) {
+        let _ = self.worker_thread.set(thread::current().id());
+    }
+
+    /// Returns true if called by the worker, e.g. from a callback it runs.
+    pub fn is_worker_thread(&self) -> bool {
+        self.worker_thread.get() == Some(&thread::current().id())
+    }
+
+    /// Mark the worker as parked for a closure running on the current thread
+    /// until the returned guard is dropped.
+    pub fn park_here(&self) -> ParkedHere {
+        let conn: *const Connection = self;
+        PARKED.with(|parked| parked.borrow_mut().push(conn));
+        ParkedHere(conn)
+    }
+
+    /// Returns true if the worker is parked for a closure running on the
+    /// current thread, which it would wait for.
+    pub fn is_parked_here(&self) -> bool {
+        let conn: *const Connection = self;
+        PARKED.with(|parked| parked.borrow().contains(&conn))
+    }
+
+    /// Returns the open port, opening it if it is closed and reopening
+    /// on demand is enabled.
     pub fn open(&self) -> io::Result<Arc<Mutex<File>>> {
+        self.open_port(false)
+    }
+
+    /// Returns the open port, opening it if it is closed.
+    pub fn connect(&self) -> io::Result<Arc<Mutex<File>>> {
+        self.reset_attempts();
+        self.open_port(true)
+    }
+
+    /// Returns the open port, retrying to open it until the deadline.
+    /// Fails right away if opening is left to the application or there
+    /// is no path to open.
+    pub fn connect_until(&self, explicit: bool, deadline: Instant) -> io::Result<Arc<Mutex<File>>> {
+        if explicit {
+            self.reset_attempts();
+        }
+        let mut open_err = None;
+        loop {
+            let err = match self.open_port(explicit) {
+                Ok(file) => return Ok(file),
+                Err(err) => err,
+            };
+            if matches!(
+                err.kind(),
+                ErrorKind::NotConnected | ErrorKind::InvalidFilename
+            ) {
+                return Err(err);
+            }
+            // Report why opening failed rather than the cooloff
+            if err.kind() != ErrorKind::QuotaExceeded || open_err.is_none() {
+                open_err = Some(err);
+            }
+            let clock = self.clock();
+            let now = clock.now();
+            if now >= deadline {
+                return Err(open_err.unwrap());
+            }
+            let retry_at = self.retry_at().unwrap_or(now + CONNECT_RETRY_INTERVAL);
+            clock.sleep(retry_at.min(deadline).saturating_duration_since(now));
+        }
+    }
+
+    /// The ongoing cooloff, if any.
+    pub fn cooloff(&self) -> Option<Cooloff> {
+        let state = self.inner.lock().unwrap();
+        let reason = state.cooloff_reason.clone()?;
+        let end = state.last_conn_attempt? + state.cool_time()?;
+        let remaining = end.checked_duration_since(state.clock.now())?;
+        Some(Cooloff { reason, remaining })
+    }
+
+    /// Start counting the failed attempts anew.
+    fn reset_attempts(&self) {
+        self.inner.lock().unwrap().failed_attempts = 0;
+    }
+
+    /// Time when the cooloff after the last connection attempt ends.
+    fn retry_at(&self) -> Option<Instant> {
+        let state = self.inner.lock().unwrap();
+        Some(state.last_conn_attempt? + state.cool_time()?)
+    }
+
+    fn open_port(&self, explicit: bool) -> io::Result<Arc<Mutex<File>>> {
         let mut state = self.inner.lock().unwrap();
         // Skip if already open
         if let Some(file) = &state.file {
             return Ok(file.clone());
         }
+        if state.shutdown.is_some() {
+            return Err(ArbiterError::ShuttingDown.into());
+        }
+        // Leave reopening to the application if requested
+        if !explicit && !state.auto_reopen {
+            return Err(ErrorKind::NotConnected.into());
+        }
+        // Give up on the device after too many failed attempts, unless asked explicitly
+        if !explicit
+            && state
+                .max_attempts
+                .is_some_and(|max| state.failed_attempts >= max)
+        {
+            let attempts = state.failed_attempts;
+            return Err(ArbiterError::Failed { attempts }.into());
+        }
         // Skip if cool-off ongoing
-        if let Some(cool_time) = state.cool_time {
+        if let Some(cool_time) = state.cool_time() {
             if let Some(last_conn) = state.last_conn_attempt {
-                if Instant::now() < last_conn + cool_time {
-                    return Err(ErrorKind::QuotaExceeded.into());
+                if state.clock.now() < last_conn + cool_time {
+                    let msg = "Cooling off after a failed attempt to open the port";
+                    return Err(io::Error::new(ErrorKind::QuotaExceeded, msg));
                 }
             }
-            state.last_conn_attempt = Some(Instant::now());
+            if state.cooloff_reason.take().is_some() {
+                self.events.emit(Event::CooloffEnded);
+            }
+            state.last_conn_attempt = Some(state.clock.now());
         }
         // Try to open
-        match &state.path {
-            None => Err(ErrorKind::InvalidFilename.into()),
-            Some(path) => match port_open(path) {
-                Ok(file) => {
-                    let file = Arc::new(Mutex::new(file));
-                    state.file = Some(file.clone());
-                    state.last_conn_attempt = None;
-                    Ok(file)
+        let mut retries = state.open_retries;
+        let (path, opened) = loop {
+            let Some(path) = state.path.clone() else {
+                return Err(ErrorKind::InvalidFilename.into());
+            };
+            let options = state.options.clone();
+            let hook = state.termios_hook.clone();
+            let (clock, delay) = (state.clock.clone(), state.open_retry_delay);
+            // Not holding up the handles meanwhile, nor the hook calling back
+            drop(state);
+            let mut hook = hook.as_ref().map(|hook| hook.lock().unwrap());
+            let opened = port_open(&path, &options, hook.as_deref_mut());
+            drop(hook);
+            let retry = match &opened {
+                // Only the open(2) itself races udev, a failing setup is for real
+                Err(PortOpenError::Open(err)) if retries > 0 && is_hotplug_race(err) => {
+                    debug!(port:? = path, error:% = err; "Retrying to open {}: {err}", path.display());
+                    retries -= 1;
+                    clock.sleep(delay);
+                    true
+                }
+                _ => false,
+            };
+            state = self.inner.lock().unwrap();
+            // Opened by another thread meanwhile, or no longer needed
+            if let Some(file) = &state.file {
+                return Ok(file.clone());
+            }
+            if state.shutdown.is_some() {
+                return Err(ArbiterError::ShuttingDown.into());
+            }
+            if retry || state.path.as_ref() != Some(&path) {
+                continue;
+            }
+            break (path, opened.map_err(io::Error::from));
+        };
+        let state = &mut *state;
+        match opened {
+            Ok((file, original)) => {
+                if let Some(trigger) = state.last_failure.take() {
+                    info!(
+                        port:? = path,
+                        attempt = state.failed_attempts + 1,
+                        trigger:% = trigger;
+                        "Reopened {} (attempt {}) after: {trigger}",
+                        path.display(),
+                        state.failed_attempts + 1,
+                    );
+                }
+                // Remember the settings the device had before we first touched it
+                state.original_termios.get_or_insert(original);
+                state.fd = Some(file.as_raw_fd());
+                state.char_rate = port_char_rate(&file).ok().flatten();
+                let file = Arc::new(Mutex::new(file));
+                state.file = Some(file.clone());
+                state.generation += 1;
+                state.baud_mismatch = None;
+                state.last_conn_attempt = None;
+                state.failed_attempts = 0;
+                Ok(file)
+            }
+            Err(err) => {
+                state.failed_attempts += 1;
+                let cooloff = state.cool_time();
+                warn!(
+                    port:? = path,
+                    attempt = state.failed_attempts,
+                    cooloff:? = cooloff,
+                    trigger:? = state.last_failure,
+                    error:% = err;
+                    "Failed to open {} (attempt {}, cooloff {cooloff:?}): {err}",
+                    path.display(),
+                    state.failed_attempts,
+                );
+                if state.max_attempts == Some(state.failed_attempts) {
+                    error!(
+                        port:? = path,
+                        attempts = state.failed_attempts;
+                        "Giving up on {} after {} failed attempts to open it",
+                        path.display(),
+                        state.failed_attempts,
+                    );
+                    self.events.emit(Event::Failed);
+                } else if let Some(cool_time) = cooloff {
+                    let reason = err.to_string();
+                    state.cooloff_reason = Some(reason.clone());
+                    self.events.emit(Event::CooloffStarted(Cooloff {
+                        reason,
+                        remaining: cool_time,
+                    }));
                 }
-                Err(err) => Err(err),
-            },
+                Err(err)
+            }
         }
     }
 
+    /// Use an already open port, e.g. one received from another process,
+    /// switching it to the raw mode if requested, see [`port_adopt`].
+    /// There is no path to reopen it from once it is closed.
+    pub fn adopt(&self, file: File, raw: bool) -> io::Result<()> {
+        let original = port_adopt(&file, raw)?;
+        let mut state = self.inner.lock().unwrap();
+        state.release_file();
+        state.path = None;
+        state.original_termios = Some(original);
+        state.fd = Some(file.as_raw_fd());
+        state.char_rate = port_char_rate(&file).ok().flatten();
+        state.file = Some(Arc::new(Mutex::new(file)));
+        state.generation += 1;
+        state.baud_mismatch = None;
+        state.last_conn_attempt = None;
+        state.failed_attempts = 0;
+        state.last_failure = None;
+        Ok(())
+    }
+
     pub fn close(&self) {
         let mut state = self.inner.lock().unwrap();
         state.last_conn_attempt = None;
-        state.file = None;
+        let was_cooling = state.cooloff_reason.take().is_some();
+        let was_open = state.file.is_some();
+        state.release_file();
+        drop(state);
+        if was_cooling {
+            self.events.emit(Event::CooloffEnded);
+        }
+        if was_open {
+            self.events.emit(Event::Disconnected);
+        }
+    }
+
+    /// Close the connection due to a failure, so the port gets reopened.
+    pub fn fail(&self, reason: &dyn Display) {
+        let mut state = self.inner.lock().unwrap();
+        if state.file.is_none() {
+            return;
+        }
+        let reason = reason.to_string();
+        if let Some(path) = &state.path {
+            warn!(port:? = path, reason:% = reason; "Closing {}: {reason}", path.display());
+        }
+        state.last_failure = Some(reason);
+        drop(state);
+        self.close();
+    }
+
+    /// Close the connection because its device is gone or failed for good,
+    /// and cool off before reopening it. The node of a device which is going
+    /// away often still opens, only to fail right away again.
+    pub fn revoke(&self, reason: &dyn Display) {
+        if self.inner.lock().unwrap().file.is_none() {
+            return;
+        }
+        self.fail(reason);
+        let mut state = self.inner.lock().unwrap();
+        let Some(cool_time) = state.cool_time() else {
+            return;
+        };
+        let reason = reason.to_string();
+        state.last_conn_attempt = Some(state.clock.now());
+        state.cooloff_reason = Some(reason.clone());
+        drop(state);
+        self.events.emit(Event::CooloffStarted(Cooloff {
+            reason,
+            remaining: cool_time,
+        }));
+    }
+
+    /// Returns true if the port is open and its device is alive. Closes the
+    /// connection if the device failed, was removed or its node now refers
+    /// to another device, so the port gets reopened.
+    pub fn check_device(&self) -> bool {
+        let state = self.inner.lock().unwrap();
+        let (Some(fd), Some(path)) = (state.fd, &state.path) else {
+            return false;
+        };
+        // The fd stays valid while the lock is held, as closing takes the lock
+        let alive = port_is_alive(fd, path);
+        drop(state);
+        match alive {
+            Ok(false) => {
+                self.fail(&"The device is no longer present");
+                false
+            }
+            // Inconclusive, e.g. no permission to stat the path
+            _ => true,
+        }
     }
 
     pub fn set_path(&self, path: impl AsRef<Path>) {
         let mut state = self.inner.lock().unwrap();
+        state.release_file();
         state.path = Some(path.as_ref().into());
-        state.file = None;
+        state.original_termios = None;
+        state.last_failure = None;
+        // Another device gets its own attempts
+        state.failed_attempts = 0;
     }
 
     pub fn is_open(&self) -> bool {
@@ -81,6 +464,42 @@ impl Connection {
         state.file.is_some()
     }
 
+    /// The path of the port, if one was given.
+    pub fn path(&self) -> Option<PathBuf> {
+        self.inner.lock().unwrap().path.clone()
+    }
+
+    /// The path of the port as a metrics label.
+    #[cfg(feature = "metrics")]
+    pub fn path_label(&self) -> String {
+        let state = self.inner.lock().unwrap();
+        state
+            .path
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Number of times the port has been opened.
+    pub fn generation(&self) -> u64 {
+        self.inner.lock().unwrap().generation
+    }
+
+    /// Symptoms of a wrong baud rate found on the current connection.
+    pub fn baud_mismatch(&self) -> Option<BaudMismatch> {
+        self.inner.lock().unwrap().baud_mismatch.clone()
+    }
+
+    pub fn set_baud_mismatch(&self, mismatch: BaudMismatch) {
+        self.inner.lock().unwrap().baud_mismatch = Some(mismatch);
+    }
+
+    /// Number of characters per second the open port can transfer.
+    pub fn char_rate(&self) -> Option<f64> {
+        let state = self.inner.lock().unwrap();
+        state.file.as_ref().and(state.char_rate)
+    }
+
     /// Change the duration of cooloff after disconnecting due to an error
     /// and before a new connection attempt is made. If set to None then
     /// another connect attepmpt is tried without any artificial delays.
@@ -88,4 +507,317 @@ impl Connection {
         let mut inner = self.inner.lock().unwrap();
         inner.cool_time = cooloff;
     }
+
+    /// Set the escalating cooloffs after consecutive failed attempts.
+    pub fn set_escalating_cooloff(&self, steps: Vec<Duration>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.cool_steps = steps;
+    }
+
+    /// Set the time to wait for the port to open when a request needs it.
+    pub fn set_connect_timeout(&self, timeout: Option<Duration>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.connect_timeout = timeout;
+    }
+
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.inner.lock().unwrap().connect_timeout
+    }
+
+    /// Set the line mode applied every time the port is opened.
+    pub fn set_line_mode(&self, mode: LineMode) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.options.mode = mode;
+    }
+
+    pub fn set_clocal(&self, clocal: Option<bool>) {
+        self.inner.lock().unwrap().options.clocal = clocal;
+    }
+
+    pub fn set_hupcl(&self, hupcl: Option<bool>) {
+        self.inner.lock().unwrap().options.hupcl = hupcl;
+    }
+
+    pub fn config(&self) -> SerialConfig {
+        self.inner.lock().unwrap().options.config
+    }
+
+    pub fn set_config(&self, config: SerialConfig) {
+        self.inner.lock().unwrap().options.config = config;
+    }
+
+    pub fn set_break_input(&self, policy: Option<InputPolicy>) {
+        self.inner.lock().unwrap().options.breaks = policy;
+    }
+
+    pub fn set_parity_error_input(&self, policy: Option<InputPolicy>) {
+        self.inner.lock().unwrap().options.parity_errors = policy;
+    }
+
+    /// Enable or disable the synchronized I/O open flags.
+    pub fn set_sync_io(&self, sync_io: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.options.sync_io = sync_io;
+    }
+
+    /// Open the port for reading only, without changing its settings.
+    pub fn set_read_only(&self, read_only: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.options.read_only = read_only;
+    }
+
+    /// Set the custom divisor applied every time the port is opened.
+    pub fn set_custom_divisor(&self, divisor: Option<u32>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.options.serial.custom_divisor = divisor;
+    }
+
+    /// Set the UART type applied every time the port is opened.
+    pub fn set_uart_type(&self, uart_type: Option<i32>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.options.serial.uart_type = uart_type;
+    }
+
+    /// Set the latency timer of an FTDI adapter applied every time the port is opened.
+    pub fn set_latency_timer(&self, latency: Option<u8>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.options.latency_timer = latency;
+    }
+
+    /// Set up the port for 9-bit multidrop communication.
+    pub fn set_multidrop(&self, multidrop: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.options.multidrop = multidrop;
+    }
+
+    /// Set the closure which adjusts the termios settings every time the port is opened.
+    pub fn set_termios_hook(&self, hook: Option<TermiosHook>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.termios_hook = hook.map(|hook| Arc::new(Mutex::new(hook)));
+    }
+
+    /// Returns true if opening the port failed too many times in a row.
+    pub fn is_failed(&self) -> bool {
+        let state = self.inner.lock().unwrap();
+        state
+            .max_attempts
+            .is_some_and(|max| state.failed_attempts >= max)
+    }
+
+    /// Set how many times an open failing right after plug-in is retried.
+    pub fn set_open_retries(&self, retries: usize, delay: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.open_retries = retries;
+        inner.open_retry_delay = delay;
+    }
+
+    /// Bytes allowed to wait in the transmit queue of the kernel.
+    pub fn tx_queue_limit(&self) -> Option<usize> {
+        self.inner.lock().unwrap().tx_queue_limit
+    }
+
+    pub fn set_tx_queue_limit(&self, limit: Option<usize>) {
+        // Writing never resumes if nothing may be queued
+        self.inner.lock().unwrap().tx_queue_limit = limit.map(|limit| limit.max(1));
+    }
+
+    /// Whether the port is reopened after the system resumes from suspend,
+    /// rather than only checked.
+    pub fn reopen_on_resume(&self) -> bool {
+        self.inner.lock().unwrap().reopen_on_resume
+    }
+
+    pub fn set_reopen_on_resume(&self, reopen: bool) {
+        self.inner.lock().unwrap().reopen_on_resume = reopen;
+    }
+
+    /// Source of the time of the cooloffs and the retries.
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.inner.lock().unwrap().clock.clone()
+    }
+
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        self.inner.lock().unwrap().clock = clock;
+    }
+
+    /// Whether the arbiter has been shut down, after which the port is
+    /// not opened again.
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown_deadline().is_some()
+    }
+
+    pub fn shutdown_deadline(&self) -> Option<Instant> {
+        self.inner.lock().unwrap().shutdown
+    }
+
+    /// Shut down, serving the pending requests until the deadline. The
+    /// deadline of the first shutdown holds.
+    pub fn shut_down(&self, deadline: Instant) {
+        self.inner.lock().unwrap().shutdown.get_or_insert(deadline);
+    }
+
+    pub fn set_watchdog(&self, window: Option<Duration>) {
+        self.inner.lock().unwrap().watchdog = window.map(Watchdog::new);
+    }
+
+    pub fn set_adaptive_timeout(&self, adaptive: Option<AdaptiveTimeout>) {
+        self.inner.lock().unwrap().round_trips = adaptive.map(RoundTrips::new);
+    }
+
+    /// The timeout of the transactions derived from their round trips, None
+    /// unless the adaptive timeout is set.
+    pub fn adaptive_timeout(&self) -> Option<Duration> {
+        let state = self.inner.lock().unwrap();
+        state.round_trips.as_ref().map(RoundTrips::timeout)
+    }
+
+    pub fn record_round_trip(&self, rtt: Duration) {
+        if let Some(round_trips) = &mut self.inner.lock().unwrap().round_trips {
+            round_trips.record(rtt);
+        }
+    }
+
+    pub fn set_circuit_breaker(&self, policy: Option<CircuitBreaker>) {
+        self.inner.lock().unwrap().circuit = policy.map(Circuit::new);
+    }
+
+    /// Let a transaction through the circuit breaker, or fail it.
+    pub fn circuit_admit(&self) -> io::Result<()> {
+        let mut state = self.inner.lock().unwrap();
+        let now = state.clock.now();
+        let Some(circuit) = &mut state.circuit else {
+            return Ok(());
+        };
+        let event = circuit.admit(now)?;
+        drop(state);
+        if let Some(event) = event {
+            self.events.emit(event);
+        }
+        Ok(())
+    }
+
+    /// Feed the circuit breaker the outcome of a transaction let through.
+    pub fn circuit_record(&self, error: Option<&io::Error>) {
+        let mut state = self.inner.lock().unwrap();
+        let now = state.clock.now();
+        let Some(circuit) = &mut state.circuit else {
+            return;
+        };
+        let event = circuit.record(now, error);
+        drop(state);
+        if let Some(event) = event {
+            self.events.emit(event);
+        }
+    }
+
+    /// Feed the watchdog the outcome of a request which needed the port,
+    /// closing the port if the watchdog fires. Errors other than timeouts
+    /// tell nothing about the port being stuck.
+    pub fn watch_request(&self, error: Option<&io::Error>) {
+        let mut state = self.inner.lock().unwrap();
+        let now = state.clock.now();
+        let Some(watchdog) = &mut state.watchdog else {
+            return;
+        };
+        let report = match error {
+            None => {
+                watchdog.succeeded();
+                return;
+            }
+            Some(err) if err.kind() == ErrorKind::TimedOut => watchdog.timed_out(now, err),
+            Some(_) => None,
+        };
+        drop(state);
+        if let Some(report) = report {
+            self.events.emit(Event::WatchdogFired(report.clone()));
+            self.fail(&format_args!("Watchdog: {report}"));
+        }
+    }
+
+    /// Set the maximum number of consecutive failed attempts to open the port.
+    pub fn set_max_attempts(&self, max_attempts: Option<usize>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.max_attempts = max_attempts;
+    }
+
+    /// Enable or disable opening the closed port on demand.
+    pub fn set_auto_reopen(&self, auto_reopen: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.auto_reopen = auto_reopen;
+    }
+
+    pub fn reconnect_rx(&self) -> ReconnectRx {
+        self.inner.lock().unwrap().reconnect_rx
+    }
+
+    /// Set what happens to the undelivered received data on reconnect.
+    pub fn set_reconnect_rx(&self, policy: ReconnectRx) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.reconnect_rx = policy;
+    }
+
+    /// Enable or disable restoring the termios settings which the device
+    /// had when it was first opened, every time the port gets closed.
+    pub fn set_restore_termios(&self, restore: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.restore_termios = restore;
+    }
+}
+
+impl ConnectionInner {
+    /// The cooloff after the failed attempts made so far.
+    fn cool_time(&self) -> Option<Duration> {
+        if self.cool_steps.is_empty() {
+            return self.cool_time;
+        }
+        let step = self.failed_attempts.saturating_sub(1);
+        Some(self.cool_steps[step.min(self.cool_steps.len() - 1)])
+    }
+
+    /// Drop the file, restoring the original termios settings if requested.
+    fn release_file(&mut self) {
+        if let (Some(fd), Some(original)) = (self.fd.take(), &self.original_termios) {
+            if self.restore_termios && self.file.is_some() {
+                // Closing must not reset the device if asked not to
+                let mut original = *original;
+                termios_hupcl(&mut original, self.options.hupcl);
+                // The device may be already gone so the result is irrelevant
+                let _ = port_set_termios(fd, &original);
+            }
+        }
+        self.file = None;
+    }
+}
+
+/// Returns true if opening failed with an error which is usually transient
+/// right after the device is plugged in: EBUSY while ModemManager probes the
+/// port, EACCES or EPERM until udev applies the permissions.
+fn is_hotplug_race(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EBUSY | libc::EACCES | libc::EPERM)
+    )
+}
+
+/// Marks the worker of a connection as parked for the current thread, see
+/// [`Connection::park_here`].
+pub struct ParkedHere(*const Connection);
+
+impl Drop for ParkedHere {
+    fn drop(&mut self) {
+        PARKED.with(|parked| {
+            let mut parked = parked.borrow_mut();
+            if let Some(idx) = parked.iter().rposition(|conn| *conn == self.0) {
+                parked.remove(idx);
+            }
+        });
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Ok(state) = self.inner.get_mut() {
+            state.release_file();
+        }
+    }
 }
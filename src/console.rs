@@ -0,0 +1,132 @@
+use std::{
+    io::{self, ErrorKind},
+    time::{Duration, Instant},
+};
+
+use regex::bytes::Regex;
+
+use crate::Arbiter;
+
+/// Automation of an interactive serial console, e.g. of U-Boot, RouterOS
+/// or a Linux getty, which prints a prompt when it is ready for the next
+/// command:
+///
+/// ```no_run
+/// # use serial_arbiter::{Arbiter, Console, regex::bytes::Regex};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let arbiter = Arbiter::new();
+/// let console = Console::new(arbiter, Regex::new(r"=> $")?);
+/// console.wait_prompt()?;
+/// let env = console.run_command("printenv bootcmd")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Console {
+    arbiter: Arbiter,
+    prompt: Regex,
+    line_ending: Vec<u8>,
+    timeout: Duration,
+}
+
+impl Console {
+    /// Creates a console recognizing the prompt by the pattern. Commands
+    /// are terminated by a carriage return, like the Enter key of
+    /// a terminal does, and get 10 seconds to complete.
+    pub fn new(arbiter: Arbiter, prompt: Regex) -> Self {
+        Self {
+            arbiter,
+            prompt,
+            line_ending: b"\r".to_vec(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Sets the bytes terminating every command.
+    pub fn with_line_ending(mut self, line_ending: impl AsRef<[u8]>) -> Self {
+        self.line_ending = line_ending.as_ref().to_vec();
+        self
+    }
+
+    /// Sets the time given to every command to print the prompt.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The arbiter of the port of the console.
+    pub fn arbiter(&self) -> &Arbiter {
+        &self.arbiter
+    }
+
+    /// Sends an empty line and waits for the prompt, e.g. to find out that
+    /// the console is ready after a reset.
+    pub fn wait_prompt(&self) -> io::Result<()> {
+        self.run_command("").map(drop)
+    }
+
+    /// Sends the command and returns its output, which is everything the
+    /// console prints before the next prompt, without the echo of the
+    /// command. Data received before the command is discarded. Fails with
+    /// `ErrorKind::TimedOut` if the prompt does not show up in time.
+    pub fn run_command(&self, command: &str) -> io::Result<String> {
+        let deadline = Instant::now() + self.timeout;
+        self.arbiter.clear_rx_buff()?;
+        self.send_line(command, deadline)?;
+        let output = self.read_until_prompt(command, Vec::new(), deadline)?;
+        let output = strip_echo(&output, command.as_bytes());
+        Ok(String::from_utf8_lossy(output).into_owned())
+    }
+
+    /// The pattern of the prompt.
+    pub fn prompt(&self) -> &Regex {
+        &self.prompt
+    }
+
+    /// The time given to every command.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Transmit the command terminated by the line ending.
+    pub(crate) fn send_line(&self, command: &str, deadline: Instant) -> io::Result<()> {
+        let line = [command.as_bytes(), &self.line_ending].concat();
+        self.arbiter.transmit(line.into(), deadline)
+    }
+
+    /// Receive the output of the command up to the prompt, which is consumed
+    /// and left out, continuing from the output received so far.
+    pub(crate) fn read_until_prompt(
+        &self,
+        command: &str,
+        mut received: Vec<u8>,
+        deadline: Instant,
+    ) -> io::Result<Vec<u8>> {
+        loop {
+            if let Some(found) = self.prompt.find(&received) {
+                received.truncate(found.start());
+                return Ok(received);
+            }
+            if !self.arbiter.wait_readable(deadline)? {
+                let msg = format!("Command {command:?} timed out waiting for the prompt");
+                let err = io::Error::new(ErrorKind::TimedOut, msg);
+                return Err(self.arbiter.tagged(err));
+            }
+            if let Some(data) = self.arbiter.receive(None, Some(Instant::now()))? {
+                received.extend_from_slice(&data);
+            }
+        }
+    }
+}
+
+/// Strips the echo of the command, up to the end of its line.
+fn strip_echo<'a>(output: &'a [u8], command: &[u8]) -> &'a [u8] {
+    let Some(rest) = output.strip_prefix(command) else {
+        return output;
+    };
+    let line_end = rest
+        .iter()
+        .position(|byte| !matches!(byte, b'\r' | b'\n'))
+        .unwrap_or(rest.len());
+    &rest[line_end..]
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Remembers recent transactions so the retry of a transaction is not
+/// transmitted again while the response to an earlier attempt is still
+/// fresh. The attempts of a transaction carry the same id, so an identical
+/// frame of another transaction, e.g. the next periodic poll, always goes
+/// to the wire. The time is that of the clock of the connection.
+pub struct DuplicateFilter {
+    window: Duration,
+    /// The last frame transmitted which got no response so far.
+    pending: Option<Attempt>,
+    /// Recently completed transactions, oldest first.
+    answered: VecDeque<Answered>,
+}
+
+struct Attempt {
+    id: u64,
+    frame: Arc<[u8]>,
+    time: Instant,
+}
+
+struct Answered {
+    attempt: Attempt,
+    response: Vec<u8>,
+}
+
+impl Attempt {
+    fn is(&self, id: u64, frame: &[u8]) -> bool {
+        self.id == id && *self.frame == *frame
+    }
+}
+
+impl DuplicateFilter {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: None,
+            answered: VecDeque::new(),
+        }
+    }
+
+    /// Returns the response to an earlier attempt of the transaction with
+    /// the identical frame received within the window.
+    pub fn response(&mut self, now: Instant, id: u64, frame: &[u8]) -> Option<Vec<u8>> {
+        self.expire(now);
+        self.answered
+            .iter()
+            .rev()
+            .find(|answered| answered.attempt.is(id, frame))
+            .map(|answered| answered.response.clone())
+    }
+
+    /// Returns true if an earlier attempt of the transaction with the
+    /// identical frame was transmitted within the window and its response
+    /// did not arrive in time.
+    pub fn is_pending(&mut self, now: Instant, id: u64, frame: &[u8]) -> bool {
+        self.expire(now);
+        self.pending
+            .as_ref()
+            .is_some_and(|pending| pending.is(id, frame))
+    }
+
+    /// Record the transmission of the frame.
+    pub fn sent(&mut self, now: Instant, id: u64, frame: Arc<[u8]>) {
+        self.pending = Some(Attempt {
+            id,
+            frame,
+            time: now,
+        });
+    }
+
+    /// Record the response to the frame.
+    pub fn answered(&mut self, now: Instant, id: u64, frame: Arc<[u8]>, response: Vec<u8>) {
+        self.pending = None;
+        self.answered.push_back(Answered {
+            attempt: Attempt {
+                id,
+                frame,
+                time: now,
+            },
+            response,
+        });
+    }
+
+    /// Forget the transactions which fell out of the window.
+    fn expire(&mut self, now: Instant) {
+        let Some(start) = now.checked_sub(self.window) else {
+            return;
+        };
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.time < start)
+        {
+            self.pending = None;
+        }
+        while self
+            .answered
+            .front()
+            .is_some_and(|answered| answered.attempt.time < start)
+        {
+            self.answered.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::DuplicateFilter;
+
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn retry_gets_the_response_to_the_earlier_attempt() {
+        let start = Instant::now();
+        let mut filter = DuplicateFilter::new(WINDOW);
+        assert_eq!(filter.response(start, 1, b"AT\r"), None);
+        filter.sent(start, 1, b"AT\r"[..].into());
+        filter.answered(start, 1, b"AT\r"[..].into(), b"OK\r".to_vec());
+
+        let later = start + WINDOW / 2;
+        assert_eq!(filter.response(later, 1, b"AT\r"), Some(b"OK\r".to_vec()));
+        // Neither another transaction with the same frame nor another frame
+        assert_eq!(filter.response(later, 2, b"AT\r"), None);
+        assert_eq!(filter.response(later, 1, b"ATI\r"), None);
+    }
+
+    #[test]
+    fn unanswered_attempt_is_pending_until_answered() {
+        let start = Instant::now();
+        let mut filter = DuplicateFilter::new(WINDOW);
+        filter.sent(start, 1, b"AT\r"[..].into());
+        assert!(filter.is_pending(start, 1, b"AT\r"));
+        assert!(!filter.is_pending(start, 2, b"AT\r"));
+
+        let later = start + WINDOW / 2;
+        filter.answered(later, 1, b"AT\r"[..].into(), b"OK\r".to_vec());
+        assert!(!filter.is_pending(later, 1, b"AT\r"));
+    }
+
+    #[test]
+    fn transactions_expire_after_the_window() {
+        let start = Instant::now();
+        let mut filter = DuplicateFilter::new(WINDOW);
+        filter.answered(start, 1, b"AT\r"[..].into(), b"OK\r".to_vec());
+        filter.sent(start + WINDOW / 2, 2, b"AT\r"[..].into());
+
+        let end = start + WINDOW;
+        assert_eq!(filter.response(end, 1, b"AT\r"), Some(b"OK\r".to_vec()));
+        let expired = end + Duration::from_millis(1);
+        assert_eq!(filter.response(expired, 1, b"AT\r"), None);
+        assert!(filter.is_pending(expired, 2, b"AT\r"));
+        assert!(!filter.is_pending(expired + WINDOW / 2, 2, b"AT\r"));
+    }
+}
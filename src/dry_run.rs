@@ -0,0 +1,125 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::PathBuf,
+    sync::Weak,
+    thread,
+    time::{Duration, Instant},
+};
+
+use nix::{pty::openpty, unistd::ttyname};
+
+use crate::{
+    connection::Connection,
+    serial_port::{port_poll, PollKind, PollResult},
+};
+
+/// Interval of checking whether the arbiter still uses the port.
+const POLL: Duration = Duration::from_millis(100);
+
+/// Stand-in for the device, for running the application in a demo or
+/// offline mode with the same code paths, see [`Arbiter::open_dry_run`](crate::Arbiter::open_dry_run):
+///
+/// ```no_run
+/// # use serial_arbiter::{Arbiter, DryRun};
+/// # let arbiter = Arbiter::new();
+/// let device = DryRun::new()
+///     .with_reply("AT\r", "OK\r")
+///     .with_reply("ATI\r", "Demo modem\rOK\r");
+/// arbiter.open_dry_run(device)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// It accepts everything transmitted to it and sends nothing but the replies
+/// to the requests it knows. The arbiter talks to it over a pseudo terminal,
+/// so the port behaves like a real one down to the termios settings.
+#[derive(Debug, Clone, Default)]
+pub struct DryRun {
+    replies: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl DryRun {
+    /// Creates a device which never replies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replies with the response every time the request is received. The
+    /// bytes received before the request are dropped. When several requests
+    /// are received, the one which ends first is replied to first.
+    pub fn with_reply(mut self, request: impl AsRef<[u8]>, response: impl AsRef<[u8]>) -> Self {
+        if !request.as_ref().is_empty() {
+            self.replies
+                .push((request.as_ref().to_vec(), response.as_ref().to_vec()));
+        }
+        self
+    }
+
+    /// Start the device, returning the path of its port. It serves for as
+    /// long as the connection uses the port.
+    pub(crate) fn start(self, conn: Weak<Connection>) -> io::Result<PathBuf> {
+        let pty = openpty(None, None)?;
+        let path = ttyname(&pty.slave)?;
+        let master = File::from(pty.master);
+        thread::spawn({
+            let path = path.clone();
+            move || {
+                // Keep the port from hanging up while the arbiter reopens it
+                let _slave = pty.slave;
+                self.serve(master, conn, path)
+            }
+        });
+        Ok(path)
+    }
+
+    fn serve(&self, mut master: File, conn: Weak<Connection>, path: PathBuf) {
+        let mut received = Vec::new();
+        let mut buf = [0; 4096];
+        while conn
+            .upgrade()
+            .is_some_and(|conn| conn.path().as_ref() == Some(&path))
+        {
+            match port_poll(&master, PollKind::ForRead, Some(Instant::now() + POLL)) {
+                Ok(PollResult::ReadReady) => {
+                    let Ok(n) = master.read(&mut buf) else {
+                        return;
+                    };
+                    received.extend_from_slice(&buf[..n]);
+                    if self.reply(&mut received, &mut master).is_err() {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Reply to the requests in the received data, dropping what cannot be
+    /// a part of the next request.
+    fn reply(&self, received: &mut Vec<u8>, master: &mut File) -> io::Result<()> {
+        loop {
+            let next = self
+                .replies
+                .iter()
+                .filter_map(|(request, response)| {
+                    let start = received
+                        .windows(request.len())
+                        .position(|window| window == request)?;
+                    Some((start + request.len(), response))
+                })
+                .min_by_key(|(end, _)| *end);
+            let Some((end, response)) = next else {
+                break;
+            };
+            received.drain(..end);
+            master.write_all(response)?;
+        }
+        let longest = self.replies.iter().map(|(request, _)| request.len()).max();
+        let keep = longest.unwrap_or(0).saturating_sub(1);
+        if received.len() > keep {
+            received.drain(..received.len() - keep);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+/// What a transmission with nothing to transmit does, see
+/// [`Arbiter::set_empty_transmit`](crate::Arbiter::set_empty_transmit).
+/// A transmission of parts which are all empty counts as empty. The Tx
+/// transform is not applied to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyTransmit {
+    /// Succeed right away without touching the port, not even to reopen it.
+    #[default]
+    Skip,
+    /// Wait until the data transmitted before has left the port, like
+    /// [`Arbiter::flush`](crate::Arbiter::flush).
+    Flush,
+    /// Fail with `ErrorKind::InvalidInput`, to catch the callers which
+    /// transmit nothing by mistake.
+    Reject,
+}
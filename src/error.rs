@@ -0,0 +1,275 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, ErrorKind},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::BaudMismatch;
+
+/// Arbiter specific failures.
+///
+/// All methods of the arbiter return `io::Result`. When a failure has a more
+/// specific meaning than the `io::ErrorKind` can express, the `io::Error`
+/// carries one of these variants, which can be retrieved with [`ArbiterError::of`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArbiterError {
+    /// The echo read back after transmitting differs from the transmitted
+    /// data, usually because another node transmitted at the same time.
+    EchoMismatch {
+        /// Offset of the first differing byte.
+        position: usize,
+        /// The byte which was transmitted.
+        sent: u8,
+        /// The byte which was read back.
+        echoed: u8,
+    },
+    /// A received frame could not be parsed.
+    Parse {
+        /// The frame which failed to parse.
+        frame: Vec<u8>,
+        /// The error reported by the parser.
+        message: String,
+    },
+    /// The port could not be reopened the maximum number of times in a row
+    /// and no more attempts are made until it is reopened explicitly.
+    Failed {
+        /// Number of failed attempts.
+        attempts: usize,
+    },
+    /// An abort pattern of a script matched the received data.
+    Aborted {
+        /// Index of the step which was waiting for its expectation.
+        step: usize,
+        /// The abort pattern which matched.
+        pattern: String,
+        /// The data received since the last met expectation.
+        received: Vec<u8>,
+    },
+    /// The transmission did not complete before the deadline because the
+    /// port was not ready for writing, usually because the peer is not ready
+    /// to receive and holds the transmission with the flow control.
+    FlowControlBlocked {
+        /// Number of bytes written before the port got blocked.
+        sent: usize,
+        /// State of the Clear To Send line, if the port has one.
+        cts: Option<bool>,
+    },
+    /// The arbiter is paused and does not touch the port, see
+    /// [`Arbiter::pause`](crate::Arbiter::pause).
+    Paused,
+    /// The request timed out, with where its time went.
+    DeadlineExceeded(TimeoutDiagnostics),
+    /// The request timed out on a connection where the data received right
+    /// after opening the port suggested a wrong baud rate, see
+    /// [`Event::LikelyBaudMismatch`](crate::Event::LikelyBaudMismatch).
+    LikelyBaudMismatch(BaudMismatch),
+    /// The worker was busy with other requests and did not accept the
+    /// request within the enqueue timeout, see
+    /// [`Arbiter::with_enqueue_timeout`](crate::Arbiter::with_enqueue_timeout).
+    EnqueueTimeout {
+        /// Time the request waited.
+        waited: Duration,
+    },
+    /// A callback run by the worker, e.g. a frame handler or a transform,
+    /// made a request to its own arbiter. The worker would wait for itself
+    /// forever, so the request fails instead. Likewise for the closure of
+    /// [`Arbiter::with_raw_fd`](crate::Arbiter::with_raw_fd), which the
+    /// worker waits for.
+    Reentrant,
+    /// The arbiter has been shut down, see [`Arbiter::shutdown`](crate::Arbiter::shutdown).
+    ShuttingDown,
+    /// Transactions with the device kept failing, so the transaction fails
+    /// without trying it, see
+    /// [`Arbiter::set_circuit_breaker`](crate::Arbiter::set_circuit_breaker).
+    CircuitOpen {
+        /// Time until a transaction may try the device again.
+        retry_in: Duration,
+    },
+    /// The device hung up (POLLHUP), usually because it was unplugged.
+    Disconnected,
+    /// The file descriptor of the port is not open (POLLNVAL).
+    InvalidFd,
+    /// The device reported an error condition (POLLERR), e.g. a USB
+    /// adapter which is failing or being removed.
+    LinkError,
+    /// An already open file descriptor cannot be used as a port, see
+    /// [`Arbiter::from_fd`](crate::Arbiter::from_fd).
+    UnsuitableFd {
+        /// What is wrong with the file descriptor.
+        reason: String,
+    },
+}
+
+impl ArbiterError {
+    /// Returns the arbiter error carried by the given I/O error, if any.
+    pub fn of(err: &io::Error) -> Option<&ArbiterError> {
+        let inner = err.get_ref()?;
+        match inner.downcast_ref::<TaggedError>() {
+            Some(tagged) => ArbiterError::of(&tagged.error),
+            None => inner.downcast_ref(),
+        }
+    }
+
+    /// The I/O error kind used when this error is converted into `io::Error`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ArbiterError::EchoMismatch { .. } => ErrorKind::InvalidData,
+            ArbiterError::Parse { .. } => ErrorKind::InvalidData,
+            ArbiterError::Failed { .. } => ErrorKind::NotConnected,
+            ArbiterError::Aborted { .. } => ErrorKind::Other,
+            ArbiterError::FlowControlBlocked { .. } => ErrorKind::TimedOut,
+            ArbiterError::Paused => ErrorKind::ResourceBusy,
+            ArbiterError::DeadlineExceeded(_) => ErrorKind::TimedOut,
+            ArbiterError::LikelyBaudMismatch(_) => ErrorKind::TimedOut,
+            ArbiterError::EnqueueTimeout { .. } => ErrorKind::TimedOut,
+            ArbiterError::Reentrant => ErrorKind::Other,
+            ArbiterError::ShuttingDown => ErrorKind::BrokenPipe,
+            ArbiterError::CircuitOpen { .. } => ErrorKind::ConnectionRefused,
+            ArbiterError::Disconnected => ErrorKind::BrokenPipe,
+            ArbiterError::InvalidFd => ErrorKind::Other,
+            ArbiterError::LinkError => ErrorKind::Other,
+            ArbiterError::UnsuitableFd { .. } => ErrorKind::InvalidInput,
+        }
+    }
+}
+
+impl fmt::Display for ArbiterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArbiterError::EchoMismatch {
+                position,
+                sent,
+                echoed,
+            } => write!(
+                f,
+                "Echo mismatch at byte {position}: sent 0x{sent:02X}, echoed 0x{echoed:02X}"
+            ),
+            ArbiterError::Parse { message, .. } => write!(f, "Parse error: {message}"),
+            ArbiterError::Failed { attempts } => {
+                write!(
+                    f,
+                    "Giving up on the port after {attempts} failed attempts to open it"
+                )
+            }
+            ArbiterError::Aborted { step, pattern, .. } => {
+                write!(f, "Script aborted at step {step} on {pattern}")
+            }
+            ArbiterError::FlowControlBlocked { sent, cts } => {
+                write!(f, "Transmission blocked by flow control after {sent} bytes")?;
+                match cts {
+                    Some(true) => write!(f, " (CTS asserted)"),
+                    Some(false) => write!(f, " (CTS deasserted)"),
+                    None => Ok(()),
+                }
+            }
+            ArbiterError::Paused => write!(f, "The port is paused"),
+            ArbiterError::DeadlineExceeded(diagnostics) => {
+                write!(f, "Timed out: {diagnostics}")
+            }
+            ArbiterError::LikelyBaudMismatch(mismatch) => {
+                write!(
+                    f,
+                    "Timed out, likely due to a baud rate mismatch: {mismatch}"
+                )
+            }
+            ArbiterError::EnqueueTimeout { waited } => {
+                write!(f, "The worker was busy with other requests for {waited:?}")
+            }
+            ArbiterError::Reentrant => {
+                write!(
+                    f,
+                    "A callback the worker waits for made a request to its arbiter"
+                )
+            }
+            ArbiterError::ShuttingDown => write!(f, "The arbiter has been shut down"),
+            ArbiterError::CircuitOpen { retry_in } => {
+                write!(f, "The circuit breaker is open for another {retry_in:?}")
+            }
+            ArbiterError::Disconnected => write!(f, "POLLHUP: Device has been disconnected"),
+            ArbiterError::InvalidFd => write!(f, "POLLNVAL: Invalid fd member"),
+            ArbiterError::LinkError => write!(f, "POLLERR: An error has occurred"),
+            ArbiterError::UnsuitableFd { reason } => {
+                write!(f, "The file descriptor cannot be used as a port: {reason}")
+            }
+        }
+    }
+}
+
+impl Error for ArbiterError {}
+
+/// Where the time of a request which timed out went, to tell a worker busy
+/// with other requests from a device which never answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeoutDiagnostics {
+    /// Time the request waited for the worker to finish the requests
+    /// before it.
+    pub queued: Duration,
+    /// Time the worker spent on the request, including waiting for the
+    /// port to reopen.
+    pub served: Duration,
+    /// Number of bytes transmitted to the port meanwhile.
+    pub transmitted: usize,
+    /// Number of bytes read from the port meanwhile, whether or not the
+    /// request was waiting for them.
+    pub received: usize,
+    /// Whether the port was open at any time while the request was served.
+    pub port_open: bool,
+}
+
+impl fmt::Display for TimeoutDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "queued for {:?}, served for {:?}, ",
+            self.queued, self.served
+        )?;
+        if !self.port_open {
+            return write!(f, "the port was not open");
+        }
+        write!(
+            f,
+            "{} bytes transmitted, {} bytes received",
+            self.transmitted, self.received
+        )
+    }
+}
+
+/// The error of a request made through an arbiter with a tag, see
+/// [`Arbiter::with_tag`](crate::Arbiter::with_tag). Carried by the
+/// `io::Error`, which keeps the kind of the original error.
+#[derive(Debug)]
+pub struct TaggedError {
+    /// The tag of the request.
+    pub tag: Arc<str>,
+    /// The original error.
+    pub error: io::Error,
+}
+
+impl TaggedError {
+    /// Returns the tag of the request which failed with the given error, if any.
+    pub fn tag_of(err: &io::Error) -> Option<&str> {
+        let tagged: &TaggedError = err.get_ref()?.downcast_ref()?;
+        Some(&tagged.tag)
+    }
+}
+
+impl fmt::Display for TaggedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Request {}: {}", self.tag, self.error)
+    }
+}
+
+impl Error for TaggedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<ArbiterError> for io::Error {
+    fn from(err: ArbiterError) -> Self {
+        io::Error::new(err.kind(), err)
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::channel::{bounded, Receiver, Sender, TrySendError};
+use crate::sync::Mutex;
+
+/// Number of events buffered for a subscriber before new ones are dropped.
+const EVENT_CAPACITY: usize = 256;
+
+/// Notification about the work of the arbiter, see [`Arbiter::events`](crate::Arbiter::events).
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    /// A large transmission made progress.
+    Progress(Progress),
+    /// The connection was closed, either on request or because the device
+    /// failed or disappeared. Unless closed on request, the port is
+    /// reopened with the next request.
+    Disconnected,
+    /// Reopening the port failed the maximum number of times in a row, see
+    /// [`Arbiter::set_max_reconnect_attempts`](crate::Arbiter::set_max_reconnect_attempts).
+    /// No more attempts are made until the port is reopened explicitly.
+    Failed,
+    /// Opening the port failed and the next attempt is made after the
+    /// cooloff, see [`Arbiter::set_cooloff_duration`](crate::Arbiter::set_cooloff_duration).
+    CooloffStarted(Cooloff),
+    /// The cooloff is over, either because the next attempt to open the
+    /// port is being made or because the port was closed on request.
+    CooloffEnded,
+    /// A peer polled by a [`Poller`](crate::Poller) responds slower than
+    /// its service level allows.
+    PeerDegraded(PeerStats),
+    /// A peer polled by a [`Poller`](crate::Poller) missed the allowed
+    /// number of queries in a row.
+    PeerLost(PeerStats),
+    /// A degraded or lost peer responds in time again.
+    PeerRecovered(PeerStats),
+    /// The data received right after opening the port looks like the port
+    /// is set to a different baud rate than the device. Until the port is
+    /// reopened, requests which time out fail with
+    /// [`ArbiterError::LikelyBaudMismatch`](crate::ArbiterError::LikelyBaudMismatch).
+    LikelyBaudMismatch(BaudMismatch),
+    /// The system resumed after being suspended for the given time. The
+    /// port is checked right away, or reopened, see
+    /// [`Arbiter::set_reopen_on_resume`](crate::Arbiter::set_reopen_on_resume).
+    Resumed(Duration),
+    /// Nothing has been received for the idle line timeout, see
+    /// [`Arbiter::set_idle_line_timeout`](crate::Arbiter::set_idle_line_timeout).
+    LineIdle(Duration),
+    /// Data is received again after the line was reported idle. Carries
+    /// for how long the line was quiet.
+    TrafficResumed(Duration),
+    /// Requests kept timing out for the whole window of the watchdog, so
+    /// the port was closed to be reopened, see
+    /// [`Arbiter::set_watchdog`](crate::Arbiter::set_watchdog).
+    WatchdogFired(WatchdogReport),
+    /// Transactions failed in a row, so they fail right away for the given
+    /// backoff, see [`Arbiter::set_circuit_breaker`](crate::Arbiter::set_circuit_breaker).
+    CircuitOpened(Duration),
+    /// The backoff is over and a transaction is trying the device again.
+    CircuitHalfOpen,
+    /// The transaction trying the device succeeded, so the transactions
+    /// are let through again.
+    CircuitClosed,
+}
+
+/// Symptoms of a baud rate mismatch, found in the bytes received right
+/// after the port was opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaudMismatch {
+    /// Baud rate of the port, if it is a standard one.
+    pub baud: Option<u32>,
+    /// Number of bytes judged.
+    pub received: usize,
+    /// Bytes read as 0x00 or 0xFF, which is what bytes sent at a different
+    /// baud rate mostly turn into.
+    pub suspicious: usize,
+    /// Framing errors counted by the driver meanwhile, if it counts them.
+    pub frame_errors: Option<u32>,
+}
+
+impl fmt::Display for BaudMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} bytes received after opening the port were 0x00 or 0xFF",
+            self.suspicious, self.received
+        )?;
+        if let Some(frame_errors) = self.frame_errors {
+            write!(f, ", with {frame_errors} framing errors")?;
+        }
+        if let Some(baud) = self.baud {
+            write!(f, " at {baud} baud")?;
+        }
+        Ok(())
+    }
+}
+
+/// Response statistics of a peer polled by a [`Poller`](crate::Poller).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerStats {
+    /// Name of the peer given to its queries.
+    pub peer: String,
+    /// Queries answered.
+    pub responses: u64,
+    /// Queries not answered, e.g. timed out.
+    pub misses: u64,
+    /// Queries not answered since the last response.
+    pub consecutive_misses: u32,
+    /// Response time of the last answered query.
+    pub last_response_time: Option<Duration>,
+    /// Moving average of the response times, weighting the last one by 1/8.
+    pub smoothed_response_time: Option<Duration>,
+}
+
+/// A pause between the attempts to open the port.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cooloff {
+    /// Why the port is not open, i.e. the error of the last attempt to open it.
+    pub reason: String,
+    /// Time left until the next attempt.
+    pub remaining: Duration,
+}
+
+/// Why the watchdog reopened the port.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchdogReport {
+    /// Time since the first of the requests which timed out.
+    pub stuck_for: Duration,
+    /// Number of requests which timed out meanwhile.
+    pub timeouts: usize,
+    /// The error of the last of them.
+    pub last_error: String,
+}
+
+impl fmt::Display for WatchdogReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requests timed out within {:?} without a success, the last with: {}",
+            self.timeouts, self.stuck_for, self.last_error
+        )
+    }
+}
+
+/// Progress of a transmission.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Bytes sent so far.
+    pub sent: usize,
+    /// Bytes still to be sent.
+    pub remaining: usize,
+    /// Average rate of the transmission so far, in bytes per second.
+    pub rate: f64,
+    /// Estimated time until the transmission completes.
+    pub eta: Option<Duration>,
+}
+
+impl Progress {
+    /// Progress of a transmission of `total` bytes started at `start`.
+    pub fn new(sent: usize, total: usize, start: Instant) -> Self {
+        let remaining = total.saturating_sub(sent);
+        let elapsed = start.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            sent as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = (rate > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / rate));
+        Self {
+            sent,
+            remaining,
+            rate,
+            eta,
+        }
+    }
+}
+
+/// Delivers events to all subscribers.
+pub struct Events {
+    subscribers: Mutex<Vec<Sender<Event>>>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = bounded(EVENT_CAPACITY);
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Returns true if anybody listens for the events.
+    pub fn is_observed(&self) -> bool {
+        !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    /// Send the event to every subscriber. A subscriber which does not keep
+    /// up misses the event rather than stalling the arbiter.
+    pub fn emit(&self, event: Event) {
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            !matches!(
+                subscriber.try_send(event.clone()),
+                Err(TrySendError::Disconnected(_))
+            )
+        });
+    }
+}
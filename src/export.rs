@@ -0,0 +1,48 @@
+use metrics::{counter, describe_counter, describe_histogram, Counter, Histogram, Unit};
+
+/// Handles of the metrics of a port, labeled by the path of the port.
+pub struct PortMetrics {
+    pub tx_bytes: Counter,
+    pub rx_bytes: Counter,
+    pub errors: Counter,
+    pub reconnects: Counter,
+    pub transact_latency: Histogram,
+}
+
+impl PortMetrics {
+    pub fn new(port: String) -> Self {
+        describe_counter!(
+            "serial_arbiter_tx_bytes_total",
+            Unit::Bytes,
+            "Bytes transmitted to the port"
+        );
+        describe_counter!(
+            "serial_arbiter_rx_bytes_total",
+            Unit::Bytes,
+            "Bytes received from the port"
+        );
+        describe_counter!(
+            "serial_arbiter_errors_total",
+            "I/O errors which closed the connection"
+        );
+        describe_counter!(
+            "serial_arbiter_reconnects_total",
+            "Times the port was reopened after the first open"
+        );
+        describe_histogram!(
+            "serial_arbiter_transact_latency_seconds",
+            Unit::Seconds,
+            "Duration of successful transactions"
+        );
+        Self {
+            tx_bytes: counter!("serial_arbiter_tx_bytes_total", "port" => port.clone()),
+            rx_bytes: counter!("serial_arbiter_rx_bytes_total", "port" => port.clone()),
+            errors: counter!("serial_arbiter_errors_total", "port" => port.clone()),
+            reconnects: counter!("serial_arbiter_reconnects_total", "port" => port.clone()),
+            transact_latency: metrics::histogram!(
+                "serial_arbiter_transact_latency_seconds",
+                "port" => port
+            ),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Sharing of an open port between processes over a Unix domain socket.
+//!
+//! A privileged broker opens `/dev/tty*` and hands the file descriptor to
+//! unprivileged workers, which wrap it in an [`Arbiter`] of their own:
+//!
+//! ```no_run
+//! # use std::os::unix::net::UnixStream;
+//! # use serial_arbiter::Arbiter;
+//! # fn main() -> std::io::Result<()> {
+//! let (broker_end, worker_end) = UnixStream::pair()?;
+//! // In the broker
+//! let broker = Arbiter::new();
+//! broker.open("/dev/ttyUSB0")?;
+//! broker.send_port(&broker_end)?;
+//! broker.close();
+//! // In the worker
+//! let worker = Arbiter::recv_port(&worker_end)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The descriptors in both processes refer to the same open port. Every
+//! arbiter arbitrates only the access of its own clones, so the processes
+//! have to agree on who uses the port: the kernel delivers every received
+//! byte to only one reader and writes of the processes get interleaved.
+//! Usually the broker closes its own handle, or pauses it with
+//! [`Arbiter::pause`], once the port is handed over. The port received by
+//! the worker is never reopened, as there is no path to reopen it from.
+
+use std::{
+    fs::File,
+    io::{self, ErrorKind, IoSlice, IoSliceMut},
+    os::{
+        fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::net::UnixStream,
+    },
+};
+
+use nix::sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags};
+
+use crate::Arbiter;
+
+/// Sends the file descriptor over the socket.
+pub fn send_fd(socket: &UnixStream, fd: BorrowedFd<'_>) -> io::Result<()> {
+    let fds = [fd.as_raw_fd()];
+    let cmsgs = [ControlMessage::ScmRights(&fds)];
+    // At least one byte of data has to go along with the descriptor
+    let iov = [IoSlice::new(&[0])];
+    sendmsg::<()>(socket.as_raw_fd(), &iov, &cmsgs, MsgFlags::empty(), None)?;
+    Ok(())
+}
+
+/// Receives a file descriptor sent over the socket with [`send_fd`].
+pub fn recv_fd(socket: &UnixStream) -> io::Result<OwnedFd> {
+    let mut byte = [0];
+    let mut iov = [IoSliceMut::new(&mut byte)];
+    let mut space = nix::cmsg_space!(RawFd);
+    let msg = recvmsg::<()>(
+        socket.as_raw_fd(),
+        &mut iov,
+        Some(&mut space),
+        MsgFlags::MSG_CMSG_CLOEXEC,
+    )?;
+    let mut received = Vec::new();
+    for cmsg in msg.cmsgs()? {
+        if let ControlMessageOwned::ScmRights(fds) = cmsg {
+            // SAFETY: The descriptors were just installed by the kernel and are ours
+            received.extend(
+                fds.into_iter()
+                    .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }),
+            );
+        }
+    }
+    received
+        .into_iter()
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "No file descriptor received"))
+}
+
+impl Arbiter {
+    /// Creates an arbiter of a port opened elsewhere, e.g. received from
+    /// another process. The port is never reopened.
+    ///
+    /// The descriptor is made non-blocking if it is not, which affects
+    /// everybody sharing its open file description, as the worker would
+    /// hang in reads otherwise. The other settings of the port are left as
+    /// they are. Fails with [`ArbiterError::UnsuitableFd`](crate::ArbiterError::UnsuitableFd)
+    /// if the descriptor is not a terminal or the terminal echoes the
+    /// received data, see [`Arbiter::from_fd_raw`].
+    pub fn from_fd(fd: impl Into<OwnedFd>) -> io::Result<Arbiter> {
+        let arbiter = Arbiter::new();
+        arbiter.conn.adopt(File::from(fd.into()), false)?;
+        Ok(arbiter)
+    }
+
+    /// Creates an arbiter of a port opened elsewhere like
+    /// [`Arbiter::from_fd`], switching the port to the raw mode, as
+    /// [`Arbiter::open`] does. Suits e.g. a terminal opened by a library
+    /// which left it in the default canonical mode.
+    pub fn from_fd_raw(fd: impl Into<OwnedFd>) -> io::Result<Arbiter> {
+        let arbiter = Arbiter::new();
+        arbiter.conn.adopt(File::from(fd.into()), true)?;
+        Ok(arbiter)
+    }
+
+    /// Sends the file descriptor of the port over the socket, opening the
+    /// port if it is closed. See the [module docs](crate::fd_passing).
+    pub fn send_port(&self, socket: &UnixStream) -> io::Result<()> {
+        self.with_raw_fd(|fd| send_fd(socket, fd))?
+    }
+
+    /// Creates an arbiter of the port sent over the socket with
+    /// [`Arbiter::send_port`].
+    pub fn recv_port(socket: &UnixStream) -> io::Result<Arbiter> {
+        Arbiter::from_fd(recv_fd(socket)?)
+    }
+}
@@ -0,0 +1,186 @@
+use std::{collections::BTreeMap, error::Error, fmt, io, path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{Arbiter, DataBits, FlowControl, LineMode, Parity, SerialConfig, StopBits};
+
+/// Settings of one port of a [`fleet`], as loaded from a config file. Every
+/// setting left out keeps the default of the arbiter, e.g. in TOML:
+///
+/// ```toml
+/// [modem]
+/// path = "/dev/serial/by-id/usb-Quectel_EG25-if02"
+/// baud = 115200
+/// line_mode = "canonical"
+///
+/// [plc]
+/// path = "/dev/ttyS1"
+/// baud = 9600
+/// parity = "even"
+/// clocal = true
+/// connect_timeout_ms = 2000
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortConfig {
+    pub path: PathBuf,
+    /// See [`SerialConfig::with_baud`].
+    #[serde(default)]
+    pub baud: Option<u32>,
+    /// See [`SerialConfig`], e.g. `"8"`.
+    #[serde(default)]
+    pub data_bits: Option<DataBits>,
+    /// See [`SerialConfig`], e.g. `"even"`.
+    #[serde(default)]
+    pub parity: Option<Parity>,
+    /// See [`SerialConfig`], e.g. `"1"`.
+    #[serde(default)]
+    pub stop_bits: Option<StopBits>,
+    /// See [`SerialConfig`], e.g. `"hardware"`.
+    #[serde(default)]
+    pub flow_control: Option<FlowControl>,
+    /// See [`Arbiter::set_line_mode`].
+    #[serde(default)]
+    pub line_mode: Option<LineMode>,
+    /// See [`Arbiter::set_clocal`].
+    #[serde(default)]
+    pub clocal: Option<bool>,
+    /// See [`Arbiter::set_hupcl`].
+    #[serde(default)]
+    pub hupcl: Option<bool>,
+    /// See [`Arbiter::set_restore_termios`].
+    #[serde(default)]
+    pub restore_termios: Option<bool>,
+    /// See [`Arbiter::set_latency_timer`].
+    #[serde(default)]
+    pub latency_timer: Option<u8>,
+    /// See [`Arbiter::set_auto_reopen`].
+    #[serde(default)]
+    pub auto_reopen: Option<bool>,
+    /// See [`Arbiter::set_max_reconnect_attempts`].
+    #[serde(default)]
+    pub max_reconnect_attempts: Option<usize>,
+    /// See [`Arbiter::set_connect_timeout`], in milliseconds.
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    /// See [`Arbiter::set_rx_pump`].
+    #[serde(default)]
+    pub rx_pump: Option<bool>,
+}
+
+impl PortConfig {
+    /// Builds the arbiter and opens its port.
+    pub fn open(&self) -> io::Result<Arbiter> {
+        let config = SerialConfig {
+            baud: self.baud,
+            data_bits: self.data_bits,
+            parity: self.parity,
+            stop_bits: self.stop_bits,
+            flow_control: self.flow_control,
+        };
+        let mut builder = Arbiter::builder().with_path(&self.path).with_config(config);
+        if let Some(mode) = self.line_mode {
+            builder = builder.with_line_mode(mode);
+        }
+        if let Some(clocal) = self.clocal {
+            builder = builder.with_clocal(clocal);
+        }
+        if let Some(hupcl) = self.hupcl {
+            builder = builder.with_hupcl(hupcl);
+        }
+        if let Some(restore) = self.restore_termios {
+            builder = builder.with_restore_termios(restore);
+        }
+        if let Some(latency) = self.latency_timer {
+            builder = builder.with_latency_timer(latency);
+        }
+        if let Some(auto_reopen) = self.auto_reopen {
+            builder = builder.with_auto_reopen(auto_reopen);
+        }
+        if let Some(max_attempts) = self.max_reconnect_attempts {
+            builder = builder.with_max_reconnect_attempts(max_attempts);
+        }
+        if let Some(timeout) = self.connect_timeout_ms {
+            builder = builder.with_connect_timeout(Duration::from_millis(timeout));
+        }
+        if let Some(enabled) = self.rx_pump {
+            builder = builder.with_rx_pump(enabled);
+        }
+        builder.build()
+    }
+}
+
+/// Builds an arbiter for every port of the config and opens them all,
+/// returning them by name:
+///
+/// ```no_run
+/// # use std::collections::BTreeMap;
+/// # use serial_arbiter::{fleet, PortConfig};
+/// # let config = "";
+/// let ports: BTreeMap<String, PortConfig> = serde_json::from_str(config)?;
+/// let arbiters = fleet(ports)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// Every port is tried, so when some fail the error lists all of them, and
+/// it keeps the arbiters of the ports which did open for a daemon which
+/// carries on without the others.
+pub fn fleet(
+    ports: impl IntoIterator<Item = (String, PortConfig)>,
+) -> Result<BTreeMap<String, Arbiter>, FleetError> {
+    let mut arbiters = BTreeMap::new();
+    let mut failed = BTreeMap::new();
+    for (name, config) in ports {
+        match config.open() {
+            Ok(arbiter) => {
+                arbiters.insert(name, arbiter);
+            }
+            Err(err) => {
+                log::warn!(
+                    "Cannot open port {name} at {}: {err}",
+                    config.path.display()
+                );
+                failed.insert(name, err);
+            }
+        }
+    }
+    if failed.is_empty() {
+        Ok(arbiters)
+    } else {
+        Err(FleetError { arbiters, failed })
+    }
+}
+
+/// The ports of a [`fleet`] which failed to open.
+pub struct FleetError {
+    /// The arbiters of the ports which opened, by name.
+    pub arbiters: BTreeMap<String, Arbiter>,
+    /// The error of every port which failed, by name.
+    pub failed: BTreeMap<String, io::Error>,
+}
+
+impl fmt::Debug for FleetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FleetError")
+            .field("arbiters", &self.arbiters.keys().collect::<Vec<_>>())
+            .field("failed", &self.failed)
+            .finish()
+    }
+}
+
+impl fmt::Display for FleetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} ports failed to open",
+            self.failed.len(),
+            self.failed.len() + self.arbiters.len()
+        )?;
+        for (name, err) in &self.failed {
+            write!(f, "; {name}: {err}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for FleetError {}
@@ -0,0 +1,128 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+
+use crate::Arbiter;
+
+/// How long the port is read before the data is forwarded to the clients.
+const RECEIVE_SLICE: Duration = Duration::from_millis(5);
+
+/// Time given to every chunk from a client to be transmitted.
+const TRANSMIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time a client gets to take the data from the port before it is dropped,
+/// so a client which stopped reading does not hold up the others.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Pause after failing to accept a client, e.g. out of file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Exposes a port to TCP clients as a raw byte stream, like ser2net.
+///
+/// Data received from the port is sent to all connected clients. Data from
+/// each client is transmitted through the arbiter, so chunks from different
+/// clients are never interleaved, same as requests of local callers. A client
+/// which does not keep up with the data from the port is dropped.
+pub struct Gateway {
+    arbiter: Arbiter,
+    listener: TcpListener,
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl Gateway {
+    /// Listens on the given address for clients of the port.
+    pub fn bind(arbiter: Arbiter, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            arbiter,
+            listener: TcpListener::bind(addr)?,
+            clients: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// The address the gateway listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves the clients. A client which fails to connect, e.g. because it
+    /// is gone before it is accepted, is logged and skipped.
+    pub fn run(self) -> io::Result<()> {
+        let arbiter = self.arbiter.clone();
+        let clients = self.clients.clone();
+        thread::spawn(move || forward_port(arbiter, clients));
+
+        for stream in self.listener.incoming() {
+            match stream.and_then(|stream| self.accept(stream)) {
+                Ok(()) => {}
+                Err(err) => {
+                    warn!("Gateway failed to accept a client: {err}");
+                    thread::sleep(ACCEPT_BACKOFF);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn accept(&self, stream: TcpStream) -> io::Result<()> {
+        let peer = stream.peer_addr()?;
+        let _ = stream.set_nodelay(true);
+        let client = stream.try_clone()?;
+        client.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT))?;
+        info!("Gateway client {peer} connected");
+        self.clients.lock().unwrap().push(client);
+        let arbiter = self.arbiter.clone();
+        thread::spawn(move || forward_client(arbiter, stream, peer));
+        Ok(())
+    }
+}
+
+/// Send the data received from the port to all clients.
+fn forward_port(arbiter: Arbiter, clients: Arc<Mutex<Vec<TcpStream>>>) {
+    loop {
+        let data = match arbiter.receive(None, Some(Instant::now() + RECEIVE_SLICE)) {
+            Ok(Some(data)) => data,
+            Ok(None) => continue,
+            Err(_) => {
+                // Reconnecting
+                thread::sleep(RECEIVE_SLICE);
+                continue;
+            }
+        };
+        // Not holding up the accepting of the clients while writing
+        let mut writing = std::mem::take(&mut *clients.lock().unwrap());
+        writing.retain_mut(|client| match client.write_all(&data) {
+            Ok(()) => true,
+            Err(err) => {
+                if let Ok(peer) = client.peer_addr() {
+                    warn!("Gateway dropped client {peer}: {err}");
+                }
+                // Let the client forwarder end
+                let _ = client.shutdown(Shutdown::Both);
+                false
+            }
+        });
+        let mut clients = clients.lock().unwrap();
+        writing.append(&mut clients);
+        *clients = writing;
+    }
+}
+
+/// Transmit the data from the client to the port.
+fn forward_client(arbiter: Arbiter, mut stream: TcpStream, peer: SocketAddr) {
+    let mut buf = [0u8; 4096];
+    while let Ok(len @ 1..) = stream.read(&mut buf) {
+        let deadline = Instant::now() + TRANSMIT_TIMEOUT;
+        if let Err(err) = arbiter.transmit(Arc::from(&buf[..len]), deadline) {
+            warn!("Gateway dropped data from {peer}: {err}");
+        }
+    }
+    info!("Gateway client {peer} disconnected");
+    // Let the port forwarder drop the client
+    let _ = stream.shutdown(Shutdown::Both);
+}
@@ -0,0 +1,42 @@
+use std::{io, thread};
+
+use crate::channel::{unbounded, Sender};
+
+use crate::{rx_buffer::RxBuffer, Codec};
+
+/// Callback receiving every decoded frame, or the error of decoding it.
+pub type FrameCallback = Box<dyn FnMut(io::Result<Vec<u8>>) + Send>;
+
+/// Decodes complete frames from the RX buffer and hands them over to
+/// a callback running on its own dispatch thread.
+pub struct FrameHandler {
+    codec: Box<dyn Codec>,
+    dispatch: Sender<io::Result<Vec<u8>>>,
+}
+
+impl FrameHandler {
+    /// Starts the dispatch thread, which ends once the handler is dropped.
+    pub fn new(codec: Box<dyn Codec>, mut callback: FrameCallback) -> Self {
+        let (dispatch, frames) = unbounded();
+        thread::spawn(move || {
+            for frame in frames {
+                callback(frame);
+            }
+        });
+        Self { codec, dispatch }
+    }
+
+    /// Consume all complete frames from the RX buffer and dispatch them.
+    /// Empty frames are skipped.
+    pub fn dispatch(&mut self, buff: &mut RxBuffer) {
+        let delimiter = self.codec.delimiter();
+        while let Some(pos) = buff.position(delimiter) {
+            let frame = buff.take(pos + 1).data;
+            let frame = &frame[..pos];
+            if frame.is_empty() {
+                continue;
+            }
+            let _ = self.dispatch.send(self.codec.decode(frame));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use crate::Direction;
+
+/// A chunk of the traffic of the port, see [`Arbiter::recent_traffic`](crate::Arbiter::recent_traffic).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficRecord {
+    /// Time the chunk was transmitted or read from the port.
+    pub time: SystemTime,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+/// The most recent traffic of the port, up to the capacity in bytes. The
+/// oldest data is dropped to make room for the new.
+#[derive(Debug, Default)]
+pub struct TrafficHistory {
+    capacity: usize,
+    size: usize,
+    records: VecDeque<TrafficRecord>,
+}
+
+impl TrafficHistory {
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Change the capacity, zero disabling the history. Drops the oldest
+    /// data over the new capacity.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+
+    pub fn record(&mut self, direction: Direction, data: &[u8]) {
+        if self.capacity == 0 || data.is_empty() {
+            return;
+        }
+        let data = &data[data.len().saturating_sub(self.capacity)..];
+        self.records.push_back(TrafficRecord {
+            time: SystemTime::now(),
+            direction,
+            data: data.to_vec(),
+        });
+        self.size += data.len();
+        self.trim();
+    }
+
+    /// The recorded traffic, oldest first.
+    pub fn records(&self) -> Vec<TrafficRecord> {
+        self.records.iter().cloned().collect()
+    }
+
+    fn trim(&mut self) {
+        while self.size > self.capacity {
+            let Some(oldest) = self.records.front_mut() else {
+                break;
+            };
+            let excess = self.size - self.capacity;
+            if oldest.data.len() > excess {
+                // Keep the newer part of the oldest chunk
+                oldest.data.drain(..excess);
+                self.size -= excess;
+            } else {
+                self.size -= oldest.data.len();
+                self.records.pop_front();
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+/// Watches for the line going quiet, see
+/// [`Arbiter::set_idle_line_timeout`](crate::Arbiter::set_idle_line_timeout).
+pub struct IdleLine {
+    timeout: Duration,
+    last_rx: Instant,
+    idle: bool,
+}
+
+impl IdleLine {
+    pub fn new(timeout: Duration, now: Instant) -> Self {
+        Self {
+            timeout,
+            last_rx: now,
+            idle: false,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Account data received now. Returns for how long the line was quiet
+    /// if it was reported idle.
+    pub fn received(&mut self, now: Instant) -> Option<Duration> {
+        let quiet = now.saturating_duration_since(self.last_rx);
+        self.last_rx = now;
+        std::mem::take(&mut self.idle).then_some(quiet)
+    }
+
+    /// Returns true once the line has been quiet for the timeout, until
+    /// data is received again.
+    pub fn check(&mut self, now: Instant) -> bool {
+        if self.idle || now.saturating_duration_since(self.last_rx) < self.timeout {
+            return false;
+        }
+        self.idle = true;
+        true
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    fs::File,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    sync::Arc,
+};
+
+use crate::{sync::Mutex, Arbiter};
+
+/// Exclusive use of the file descriptor of the serial port, handed out by
+/// [`Arbiter::lease_fd`], e.g. to a C library which insists on owning the
+/// fd for a while.
+///
+/// The worker thread does not touch the port while the lease is held and
+/// requests which need the port fail with
+/// [`ArbiterError::Paused`](crate::ArbiterError::Paused). Dropping the lease
+/// resumes normal operation. Data received before and during the lease is
+/// discarded then, as it is out of sequence with what the holder consumed.
+pub struct FdLease {
+    arbiter: Arbiter,
+    /// Keeps the fd open even if the port is closed meanwhile.
+    _file: Arc<Mutex<File>>,
+    fd: RawFd,
+}
+
+impl FdLease {
+    pub(crate) fn new(arbiter: Arbiter, file: Arc<Mutex<File>>) -> Self {
+        let fd = file.lock().unwrap().as_raw_fd();
+        Self {
+            arbiter,
+            _file: file,
+            fd,
+        }
+    }
+}
+
+impl AsRawFd for FdLease {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for FdLease {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: The file is kept open by `self._file` for the lifetime of the lease.
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl Drop for FdLease {
+    fn drop(&mut self) {
+        if let Err(err) = self.arbiter.end_lease() {
+            log::warn!("Failed to end the fd lease: {err}");
+        }
+    }
+}
@@ -1,14 +1,169 @@
+mod adaptive_timeout;
+mod baud_check;
+mod bridge;
+mod builder;
+pub mod channel;
+mod circuit_breaker;
+mod clock;
+mod codec;
 mod connection;
+#[cfg(feature = "script")]
+mod console;
+mod dedup;
+mod dry_run;
+mod empty_transmit;
+mod error;
+mod events;
+#[cfg(feature = "metrics")]
+mod export;
+pub mod fd_passing;
+#[cfg(feature = "fleet")]
+mod fleet;
+mod gateway;
+mod handler;
+mod history;
+mod idle_line;
+mod lease;
+#[cfg(all(test, loom))]
+mod loom_tests;
+mod meter;
+#[cfg(feature = "miniterm")]
+mod miniterm;
+mod modem;
+mod multidrop;
+#[cfg(feature = "noise")]
+mod noise;
+mod ping;
+mod poller;
+mod probe;
+mod retry;
+mod router;
+mod rs485;
+mod rx_buffer;
+mod rx_filter;
+mod rx_pump;
+mod scheduler;
+#[cfg(feature = "script")]
+mod script;
+mod serial_config;
 mod serial_port;
+mod session_log;
+mod setserial;
+mod shutdown;
+mod suspend;
+mod sync;
+mod tap;
+mod thread_tuning;
+mod trace;
+#[cfg(feature = "script")]
+mod uboot;
+mod usage;
+mod watchdog;
+mod ymodem;
 
+/// Internals exposed to the fuzz targets.
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzzing {
+    pub use crate::{multidrop::MultidropDecoder, rx_buffer::RxBuffer};
+}
+
+use baud_check::{BaudCheck, Verdict};
+use channel::{bounded, Receiver, RecvTimeoutError, SendTimeoutError, Sender};
 use connection::Connection;
-use crossbeam::channel::{bounded, Receiver, RecvTimeoutError, SendError, Sender};
-use serial_port::{port_recv, port_send};
+use dedup::DuplicateFilter;
+#[cfg(feature = "metrics")]
+use export::PortMetrics;
+use handler::FrameHandler;
+use history::TrafficHistory;
+use idle_line::IdleLine;
+use meter::LinkMeter;
+use modem::{port_carrier, port_send_break, port_set_modem_line, CarrierAction, CarrierState};
+use multidrop::MultidropDecoder;
+use nix::sys::prctl::set_timerslack;
+use probe::port_probe;
+use router::{Router, UNMATCHED_CAPACITY};
+use rx_buffer::{RxBuffer, RxTransform};
+use rx_pump::RxPump;
+use scheduler::Scheduler;
+use serial_port::{
+    is_revoked, port_clocal, port_collect, port_drain, port_flush, port_hupcl, port_recv,
+    port_recv_len, port_send, port_set_9th_bit, port_set_multidrop, port_set_termios,
+};
+use setserial::port_serial_info;
 use std::collections::VecDeque;
-use std::path::Path;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::thread;
 use std::time::{Duration, Instant};
-use std::{io, mem, thread};
+use suspend::SuspendDetector;
+use sync::Mutex;
+use termios::Termios;
+use trace::RequestTrace;
+use usage::{UsageCounter, UsageRegistry};
+
+pub use adaptive_timeout::AdaptiveTimeout;
+pub use bridge::{bridge, BridgeDirection, BridgeFilter, BridgeOptions};
+pub use builder::ArbiterBuilder;
+pub use circuit_breaker::CircuitBreaker;
+pub use clock::{Clock, ManualClock, SystemClock};
+#[cfg(feature = "compression")]
+pub use codec::Deflate;
+pub use codec::{CobsCodec, Codec, LineCodec};
+#[cfg(feature = "script")]
+pub use console::Console;
+pub use dry_run::DryRun;
+pub use empty_transmit::EmptyTransmit;
+pub use error::{ArbiterError, TaggedError, TimeoutDiagnostics};
+pub use events::{BaudMismatch, Cooloff, Event, PeerStats, Progress, WatchdogReport};
+#[cfg(feature = "fleet")]
+pub use fleet::{fleet, FleetError, PortConfig};
+pub use gateway::Gateway;
+pub use history::TrafficRecord;
+pub use lease::FdLease;
+pub use meter::Utilization;
+#[cfg(feature = "miniterm")]
+pub use miniterm::Miniterm;
+pub use modem::{CarrierWatch, ModemLine};
+#[cfg(feature = "noise")]
+pub use noise::Noise;
+pub use ping::PingStats;
+pub use poller::{Poller, ResponseHandler};
+pub use probe::{PortProbe, UsbInfo};
+#[cfg(feature = "script")]
+pub use regex;
+pub use retry::{is_transient, RetryPolicy};
+pub use rs485::DirectionGpio;
+pub use rx_buffer::{ReconnectRx, Timestamped};
+pub use rx_filter::RxFilter;
+#[cfg(feature = "script")]
+pub use script::{Script, Step};
+pub use serial_config::{DataBits, FlowControl, Parity, SerialConfig, StopBits};
+pub use serial_port::{baud_speed, InputPolicy, LineMode};
+pub use session_log::{Direction, LogFormat, SessionLog};
+pub use setserial::SerialInfo;
+pub use shutdown::ShutdownPolicy;
+#[cfg(feature = "noise")]
+pub use snow;
+pub use tap::Tap;
+pub use termios;
+pub use thread_tuning::ThreadTuning;
+#[cfg(feature = "script")]
+pub use uboot::UBoot;
+pub use usage::ClientUsage;
+
+/// Interval of checking if the device of the open port is still present.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time given to a scheduled frame to be transmitted.
+const SCHEDULED_TX_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Timer slack of the worker thread, which bounds how late the kernel may wake it up.
+const TIMER_SLACK_NS: u64 = 1_000;
 
 pub const POLLING_INTERVAL: Duration = Duration::from_millis(1);
 
@@ -26,16 +181,100 @@ pub const POLLING_INTERVAL: Duration = Duration::from_millis(1);
 ///
 /// **This is an "async-less" library**, and it is intended to remain that way.  
 /// If you need asynchronous behavior, you can easily make it async-compatible in your own code.
-#[derive(Clone)]
+///
+/// Deadlines are not rounded to milliseconds. The worker waits for the port with
+/// nanosecond timeouts and a timer slack of 1 µs, so on a system which is not overloaded
+/// deadlines are kept to within tens of microseconds. This is enough for sub-millisecond
+/// gaps, such as the Modbus T3.5 inter-frame gap at high baud rates.
 pub struct Arbiter {
     conn: Arc<Connection>,
-    chan: Sender<Request>,
+    chan: Sender<Envelope>,
+    meter: Arc<Mutex<LinkMeter>>,
+    history: Arc<Mutex<TrafficHistory>>,
+    clients: Arc<Mutex<UsageRegistry>>,
+    /// Usage of the port by this handle, see [`Arbiter::usage`].
+    usage: Arc<UsageCounter>,
+    /// Tag attached to every request, see [`Arbiter::with_tag`].
+    tag: Option<Arc<str>>,
+    /// See [`Arbiter::with_enqueue_timeout`].
+    enqueue_timeout: Option<Duration>,
+}
+
+impl Clone for Arbiter {
+    /// Returns a handle to the same port, whose usage is counted apart.
+    fn clone(&self) -> Self {
+        self.handle(self.tag.clone())
+    }
+}
+
+/// A request together with the tag of the arbiter which made it.
+struct Envelope {
+    tag: Option<Arc<str>>,
+    request: Request,
+    trace: Arc<RequestTrace>,
 }
 
 enum Request {
     Clear(Clear),
     Transmit(Transmit),
     Receive(Receive),
+    WaitReadable(WaitReadable),
+    Transact(Transact),
+    SetRouter(SetRouter),
+    Route(Route),
+    Park(Park),
+    SetDirectionGpio(SetDirectionGpio),
+    SetMultidrop(SetMultidrop),
+    TransmitAddressed(TransmitAddressed),
+    SetDuplicateWindow(SetDuplicateWindow),
+    SetSessionLog(SetSessionLog),
+    SetFrameHandler(SetFrameHandler),
+    Schedule(Schedule),
+    Unschedule(Unschedule),
+    SetScheduleGap(SetScheduleGap),
+    SetCarrierWatch(SetCarrierWatch),
+    TransmitParts(TransmitParts),
+    SetPaused(SetPaused),
+    Lease(Lease),
+    EndLease(EndLease),
+    SetRxFilter(SetRxFilter),
+    SetRxTransform(SetRxTransform),
+    SetTxTransform(SetTxTransform),
+    ReceiveMatch(ReceiveMatch),
+    SetReadLimits(SetReadLimits),
+    SetRxPump(SetRxPump),
+    SetThreadTuning(SetThreadTuning),
+    SetPath(SetPath),
+    SetTrafficHistory(SetTrafficHistory),
+    SetEmptyTransmit(SetEmptyTransmit),
+    SetIdleLineTimeout(SetIdleLineTimeout),
+    Shutdown(Shutdown),
+}
+
+impl Request {
+    /// Returns true if the request does I/O on the port.
+    fn needs_port(&self) -> bool {
+        if let Request::Transmit(tx) = self {
+            // Unless it is empty, see [`EmptyTransmit`]
+            return !tx.tx_bytes.is_empty() || tx.flush;
+        }
+        matches!(
+            self,
+            Request::TransmitParts(_)
+                | Request::Receive(_)
+                | Request::WaitReadable(_)
+                | Request::Transact(_)
+                | Request::Route(_)
+                | Request::Park(_)
+                | Request::TransmitAddressed(_)
+                | Request::ReceiveMatch(_)
+        )
+    }
+
+    /// Returns true if a failure of the request tells nothing about the port.
+    fn is_isolated(&self) -> bool {
+        matches!(self, Request::Transact(tx) if tx.isolated)
+    }
 }
 
 struct Clear {
@@ -45,19 +284,246 @@ struct Clear {
 struct Transmit {
     pub tx_bytes: Arc<[u8]>,
     pub deadline: Instant,
+    pub echo: bool,
+    /// Wait until the data has left the port.
+    pub flush: bool,
     pub response: Sender<io::Result<()>>,
 }
 
 struct Receive {
     pub until: Option<u8>,
     pub deadline: Option<Instant>,
-    pub response: Sender<io::Result<Option<Vec<u8>>>>,
+    pub complete: bool,
+    pub response: Sender<io::Result<Option<Timestamped>>>,
+}
+
+struct WaitReadable {
+    pub deadline: Instant,
+    pub response: Sender<io::Result<bool>>,
+}
+
+struct ReceiveMatch {
+    pub pattern: Arc<[u8]>,
+    /// Number of bytes already delivered as partial data.
+    pub seen: usize,
+    pub deadline: Instant,
+    pub response: Sender<io::Result<MatchProgress>>,
+}
+
+enum MatchProgress {
+    /// The data up to the end of the match, consumed.
+    Matched(Vec<u8>),
+    /// The data received after the data seen so far, not consumed.
+    Partial(Vec<u8>),
+}
+
+struct Transact {
+    /// Shared by the retries of the transaction, see
+    /// [`Arbiter::set_duplicate_window`].
+    pub id: u64,
+    pub tx_bytes: Arc<[u8]>,
+    pub until: u8,
+    pub deadline: Instant,
+    pub flush: bool,
+    /// A failure is blamed on the peer rather than on the port, see
+    /// [`Poller`].
+    pub isolated: bool,
+    pub response: Sender<io::Result<Vec<u8>>>,
+}
+
+struct SetRouter {
+    pub router: Option<Router>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct Route {
+    pub tx_bytes: Option<Arc<[u8]>>,
+    pub key: Vec<u8>,
+    pub deadline: Instant,
+    pub response: Sender<io::Result<Vec<u8>>>,
+}
+
+struct TransmitParts {
+    pub parts: Vec<Arc<[u8]>>,
+    pub deadline: Instant,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct Park {
+    pub response: Sender<io::Result<RawFd>>,
+    pub release: Receiver<()>,
+}
+
+struct SetDirectionGpio {
+    pub gpio: Option<DirectionGpio>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetMultidrop {
+    pub enabled: bool,
+    pub filter: Option<u8>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct TransmitAddressed {
+    pub address: u8,
+    pub tx_bytes: Arc<[u8]>,
+    pub deadline: Instant,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetDuplicateWindow {
+    pub window: Option<Duration>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct Schedule {
+    pub tx_bytes: Arc<[u8]>,
+    pub interval: Duration,
+    pub response: Sender<io::Result<u64>>,
+}
+
+struct Unschedule {
+    pub id: u64,
+    pub response: Sender<io::Result<bool>>,
+}
+
+struct SetScheduleGap {
+    pub gap: Duration,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetFrameHandler {
+    pub handler: Option<FrameHandler>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetCarrierWatch {
+    pub watch: Option<CarrierWatch>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetSessionLog {
+    pub log: Option<SessionLog>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetPaused {
+    pub paused: bool,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct Lease {
+    pub deadline: Instant,
+    pub response: Sender<io::Result<Arc<Mutex<File>>>>,
+}
+
+struct EndLease {
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetRxFilter {
+    pub filter: Option<RxFilter>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetReadLimits {
+    pub chunk_size: usize,
+    pub budget: Option<usize>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetRxPump {
+    pub enabled: bool,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetThreadTuning {
+    pub tuning: ThreadTuning,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetEmptyTransmit {
+    pub policy: EmptyTransmit,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct Shutdown {
+    pub response: Sender<()>,
+}
+
+struct SetIdleLineTimeout {
+    pub timeout: Option<Duration>,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetTrafficHistory {
+    pub capacity: usize,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetPath {
+    pub path: PathBuf,
+    pub rx: ReconnectRx,
+    pub response: Sender<io::Result<()>>,
+}
+
+struct SetRxTransform {
+    pub transform: Option<RxTransform>,
+    pub response: Sender<io::Result<()>>,
+}
+
+/// Transform of every transmitted frame, writing the bytes to be put on
+/// the wire into the vector.
+type TxTransform = Box<dyn FnMut(&[u8], &mut Vec<u8>) + Send>;
+
+struct SetTxTransform {
+    pub transform: Option<TxTransform>,
+    pub response: Sender<io::Result<()>>,
 }
 
 struct WorkerThread {
-    buff: VecDeque<u8>,
+    buff: RxBuffer,
     conn: Arc<Connection>,
-    chan: Receiver<Request>,
+    meter: Arc<Mutex<LinkMeter>>,
+    history: Arc<Mutex<TrafficHistory>>,
+    chan: Receiver<Envelope>,
+    /// Request which arrived while collecting the incoming data, served next.
+    preempted: Option<Envelope>,
+    router: Option<Router>,
+    direction_gpio: Option<DirectionGpio>,
+    multidrop: bool,
+    /// Whether the port is read on a dedicated thread, see [`Arbiter::set_rx_pump`].
+    rx_pump_enabled: bool,
+    rx_pump: Option<RxPump>,
+    /// Scheduling of the worker, also applied to the Rx pump.
+    tuning: ThreadTuning,
+    empty_transmit: EmptyTransmit,
+    idle_line: Option<IdleLine>,
+    /// Responses to the callers of [`Arbiter::shutdown`], sent once stopped.
+    shutdown: Vec<Sender<()>>,
+    suspend: SuspendDetector,
+    duplicates: Option<DuplicateFilter>,
+    last_device_check: Instant,
+    /// Data received before reconnecting, kept apart from the newer data.
+    stale: VecDeque<Timestamped>,
+    #[cfg(feature = "metrics")]
+    metrics: PortMetrics,
+    session_log: Option<SessionLog>,
+    frame_handler: Option<FrameHandler>,
+    scheduler: Scheduler,
+    /// Time of the end of the last transmission.
+    last_tx: Option<Instant>,
+    carrier: Option<CarrierState>,
+    /// Tag of the request being served.
+    tag: Option<Arc<str>>,
+    /// Trace of the request being served.
+    trace: Option<Arc<RequestTrace>>,
+    /// The port is left alone until resumed.
+    paused: bool,
+    tx_transform: Option<TxTransform>,
+    /// Judges the data received right after the port was opened.
+    baud_check: Option<BaudCheck>,
 }
 
 impl Default for Arbiter {
@@ -70,16 +536,53 @@ impl Arbiter {
     /// Creates a new arbiter which will handle a serial port
     /// connection defined by the given serial port builder.
     pub fn new() -> Self {
-        let conn = Arc::new(Connection::new());
+        ArbiterBuilder::new().spawn()
+    }
+
+    /// Returns a builder configuring the arbiter before its worker starts,
+    /// see [`ArbiterBuilder`].
+    pub fn builder() -> ArbiterBuilder {
+        ArbiterBuilder::new()
+    }
+
+    /// Returns a handle to the same port whose requests carry the given
+    /// tag, e.g. the id of a transaction handled by a gateway. The tag shows
+    /// in the session log next to the transmitted data, in the log when the
+    /// request makes the port close, and in the errors of the requests,
+    /// see [`TaggedError`].
+    pub fn with_tag(&self, tag: impl Into<Arc<str>>) -> Arbiter {
+        self.handle(Some(tag.into()))
+    }
 
-        // Setup read and write channels
-        let (req_tx, req_rx) = bounded::<Request>(0);
+    fn handle(&self, tag: Option<Arc<str>>) -> Arbiter {
+        Arbiter {
+            conn: self.conn.clone(),
+            chan: self.chan.clone(),
+            meter: self.meter.clone(),
+            history: self.history.clone(),
+            clients: self.clients.clone(),
+            usage: self.clients.lock().unwrap().register(tag.clone()),
+            tag,
+            enqueue_timeout: self.enqueue_timeout,
+        }
+    }
 
-        // Spawn background thread
-        let worker = WorkerThread::new(conn.clone(), req_rx);
-        worker.spawn();
+    /// Returns a handle to the same port whose requests wait for the worker
+    /// to accept them no longer than the timeout. The worker serves one
+    /// request at a time, so without it a transmission may wait for a long
+    /// receive of another thread for as long as that takes, whatever its own
+    /// deadline. A request not accepted in time fails with
+    /// [`ArbiterError::EnqueueTimeout`], without being made.
+    pub fn with_enqueue_timeout(&self, timeout: Duration) -> Arbiter {
+        Arbiter {
+            enqueue_timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
 
-        Self { conn, chan: req_tx }
+    /// The tag attached to the requests of this handle.
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
     }
 
     /// Closes the serial port
@@ -87,45 +590,278 @@ impl Arbiter {
         self.conn.close();
     }
 
+    /// Stops the worker and closes the port, for good. The request being
+    /// served completes. The requests made by other threads which are still
+    /// pending are failed with [`ArbiterError::ShuttingDown`], or served
+    /// first, as the policy says.
+    /// The requests waiting for routed frames are failed either way. Every
+    /// later request fails, and the port cannot be opened again. Returns
+    /// once the worker has stopped.
+    ///
+    /// Called from a callback run by the worker, it returns right away and
+    /// the worker stops once the callback returns. Dropping the last handle
+    /// of the arbiter stops the worker too, with nobody left to wait for a
+    /// response.
+    pub fn shutdown(&self, policy: ShutdownPolicy) {
+        let grace = match policy {
+            ShutdownPolicy::FailPending => Duration::ZERO,
+            ShutdownPolicy::Drain(grace) => grace,
+        };
+        // Seen by the worker before it serves the next request
+        self.conn.shut_down(Instant::now() + grace);
+        if self.conn.is_worker_thread() {
+            // A callback cannot wait for the worker which runs it
+            return;
+        }
+        let (response, stopped) = bounded(1);
+        let envelope = Envelope {
+            tag: self.tag.clone(),
+            request: Request::Shutdown(Shutdown { response }),
+            trace: Arc::new(RequestTrace::new()),
+        };
+        // Either served while draining or dropped with the worker
+        if self.chan.send(envelope).is_ok() {
+            let _ = stopped.recv();
+        }
+    }
+
     /// Returns true if the connection is open
     pub fn is_open(&self) -> bool {
         self.conn.is_open()
     }
 
+    /// Returns true if the connection is open and the device is alive.
+    /// Unlike [`Arbiter::is_open`], which is only updated when I/O fails,
+    /// this polls the port for hangups and errors and verifies that the
+    /// device node is still present. A dead connection gets closed.
+    pub fn is_alive(&self) -> bool {
+        self.conn.check_device()
+    }
+
+    /// Returns the generation of the connection, which is incremented every
+    /// time the port is opened, including every reconnect. Zero if the port
+    /// has never been opened. A different generation between two calls means
+    /// the connection was reestablished in between, so the device may have
+    /// been reset and any protocol state kept by the caller may be stale.
+    pub fn generation(&self) -> u64 {
+        self.conn.generation()
+    }
+
+    /// Returns the symptoms of a wrong baud rate if they were found in the
+    /// data received right after the port was last opened, see
+    /// [`Event::LikelyBaudMismatch`].
+    pub fn baud_mismatch(&self) -> Option<BaudMismatch> {
+        self.conn.baud_mismatch()
+    }
+
+    /// Sets what happens to the received but not yet delivered data when
+    /// the connection fails and the port is reopened. Keeps it by default.
+    pub fn set_reconnect_rx(&self, policy: ReconnectRx) {
+        self.conn.set_reconnect_rx(policy);
+    }
+
     /// Opens the serial port.
     pub fn open(&self, path: impl AsRef<Path>) -> io::Result<()> {
         self.conn.set_path(path);
-        self.conn.open().map(|_| ())
+        self.conn.connect().map(|_| ())
+    }
+
+    /// Checks the port at the path without claiming it, e.g. to validate the
+    /// candidates in a port picker before opening one. Opens the port, reads
+    /// its settings and what the device is, and closes it again. No worker
+    /// is started and the settings are not changed, except that dropping
+    /// DTR and RTS on close (`HUPCL`) is turned off for the close, as it
+    /// would reset some devices. Fails like [`Arbiter::open`] for a port
+    /// which cannot be opened, e.g. one which is not a terminal or is not
+    /// accessible.
+    pub fn probe(path: impl AsRef<Path>) -> io::Result<PortProbe> {
+        port_probe(path.as_ref())
+    }
+
+    /// Opens the serial port with the line settings, which are applied again
+    /// every time it is reopened, see [`Arbiter::set_config`]. An invalid
+    /// config fails before the port is touched, see [`SerialConfig::validate`].
+    pub fn open_with_config(&self, path: impl AsRef<Path>, config: SerialConfig) -> io::Result<()> {
+        config.validate()?;
+        self.conn.set_config(config);
+        self.open(path)
+    }
+
+    /// Opens the serial port, retrying until it opens or until the deadline,
+    /// e.g. while a USB adapter is being plugged in. Waits for the cooloff
+    /// between the attempts. At the deadline fails with the error of the
+    /// last attempt.
+    pub fn connect(&self, path: impl AsRef<Path>, deadline: Instant) -> io::Result<()> {
+        self.conn.set_path(path);
+        self.conn.connect_until(true, deadline).map(|_| ())
+    }
+
+    /// Opens a simulated device instead of a serial port, for a demo or an
+    /// offline mode, see [`DryRun`]. Like [`Arbiter::open`] it replaces the
+    /// port which is open, and [`Arbiter::open`] or [`Arbiter::set_path`]
+    /// switch back to a real one.
+    pub fn open_dry_run(&self, device: DryRun) -> io::Result<()> {
+        let path = device.start(Arc::downgrade(&self.conn))?;
+        self.open(path)
+    }
+
+    /// Switches to the port at another path, e.g. one picked by the user,
+    /// keeping the settings, hooks and handlers of the arbiter. The current
+    /// port is closed once the requests ahead of this one are done, and
+    /// what it has received is kept, dropped or delivered on its own as
+    /// the `rx` policy says. The new port is opened like a port which was
+    /// closed after an error, i.e. by the next request unless reopening on
+    /// demand is disabled, see [`Arbiter::set_auto_reopen`].
+    pub fn set_path(&self, path: impl AsRef<Path>, rx: ReconnectRx) -> io::Result<()> {
+        let path = path.as_ref().into();
+        self.request(|response| Request::SetPath(SetPath { path, rx, response }))
+    }
+
+    /// Reopens the serial port under the path given to [`Arbiter::open`].
+    /// Does nothing if the port is open.
+    pub fn reopen(&self) -> io::Result<()> {
+        self.conn.connect().map(|_| ())
+    }
+
+    /// Sets the custom divisor of the UART clock every time the port is
+    /// opened, like `setserial spd_cust divisor N`. For old 16550-class ports
+    /// where this is the only way to get non-standard baud rates: the port
+    /// is set to 38400 baud, which the driver replaces with
+    /// `baud_base / divisor` (see [`Arbiter::serial_info`]).
+    /// Takes effect the next time the port is opened.
+    pub fn set_custom_divisor(&self, divisor: Option<u32>) {
+        self.conn.set_custom_divisor(divisor);
+    }
+
+    /// Sets the UART type every time the port is opened, like
+    /// `setserial uart 16550A`. Requires the `CAP_SYS_ADMIN` capability.
+    /// Takes effect the next time the port is opened.
+    pub fn set_uart_type(&self, uart_type: Option<i32>) {
+        self.conn.set_uart_type(uart_type);
+    }
+
+    /// Reads the low level settings of the serial driver of the open port.
+    /// Fails for devices without them, such as most USB-serial adapters.
+    pub fn serial_info(&self) -> io::Result<SerialInfo> {
+        self.with_raw_fd(|fd| port_serial_info(fd.as_raw_fd()))?
+    }
+
+    /// Sets the latency timer of an FTDI USB-serial adapter, in milliseconds,
+    /// every time the port is opened. The adapter holds received data for up
+    /// to this time (16 ms by default) before passing it to the host, so a low
+    /// value (e.g. 1) speeds up request-response exchanges. The value is
+    /// written to the `latency_timer` sysfs attribute of the device. It is
+    /// ignored for other devices and when the attribute is not writable
+    /// (usually it requires root or a udev rule). Takes effect the next time
+    /// the port is opened.
+    pub fn set_latency_timer(&self, latency: Option<u8>) {
+        self.conn.set_latency_timer(latency);
+    }
+
+    /// Sets the maximum number of consecutive failed attempts to reopen the
+    /// port. Once reached, the arbiter enters the failed state: it emits
+    /// [`Event::Failed`] and requests fail with [`ArbiterError::Failed`]
+    /// instead of retrying against a device which is permanently gone.
+    /// [`Arbiter::open`] and [`Arbiter::reopen`] leave the failed state.
+    /// Unlimited (None) by default.
+    pub fn set_max_reconnect_attempts(&self, max_attempts: Option<usize>) {
+        self.conn.set_max_attempts(max_attempts);
+    }
+
+    /// Returns true if the arbiter gave up reopening the port, see
+    /// [`Arbiter::set_max_reconnect_attempts`].
+    pub fn is_failed(&self) -> bool {
+        self.conn.is_failed()
+    }
+
+    /// Enables (default) or disables reopening the port on demand. By default
+    /// every request reopens the port if it was closed, e.g. after an error.
+    /// In the strict mode (false) requests fail fast with
+    /// `ErrorKind::NotConnected` instead, so the application can reopen the
+    /// port with [`Arbiter::reopen`] and run its init sequence before any
+    /// other traffic.
+    pub fn set_auto_reopen(&self, auto_reopen: bool) {
+        self.conn.set_auto_reopen(auto_reopen);
     }
 
     /// Clear the Rx buffer of the serial port.
     pub fn clear_rx_buff(&self) -> io::Result<()> {
-        let (response, result_ch) = bounded(1);
-        let request = Request::Clear(Clear { response });
-        if let Err(SendError { .. }) = self.chan.send(request) {
-            return Err(io::Error::other("Internal error"));
-        }
-        match result_ch.recv() {
-            Err(_) => Err(io::Error::other("Internal error")),
-            Ok(result) => result,
-        }
+        self.request(|response| Request::Clear(Clear { response }))
     }
 
     /// Transmits data to the serial port.
     pub fn transmit(&self, tx_bytes: Arc<[u8]>, deadline: Instant) -> io::Result<()> {
-        let (response, result_ch) = bounded(1);
-        let request = Request::Transmit(Transmit {
-            tx_bytes,
-            deadline,
-            response,
-        });
-        if let Err(SendError { .. }) = self.chan.send(request) {
-            return Err(io::Error::other("Internal error"));
-        }
-        match result_ch.recv() {
-            Err(_) => Err(io::Error::other("Internal error")),
-            Ok(result) => result,
-        }
+        self.request(|response| {
+            Request::Transmit(Transmit {
+                tx_bytes,
+                deadline,
+                echo: false,
+                flush: false,
+                response,
+            })
+        })
+    }
+
+    /// Transmits data to the serial port and waits until it has left the
+    /// port, rather than only the buffers of the kernel. Useful before
+    /// changing the line settings or releasing a half-duplex bus.
+    pub fn transmit_flush(&self, tx_bytes: Arc<[u8]>, deadline: Instant) -> io::Result<()> {
+        self.request(|response| {
+            Request::Transmit(Transmit {
+                tx_bytes,
+                deadline,
+                echo: false,
+                flush: true,
+                response,
+            })
+        })
+    }
+
+    /// Waits until the data transmitted before has left the port. A
+    /// transmission which cannot drain before the deadline fails like one
+    /// which cannot be written, e.g. with [`ArbiterError::FlowControlBlocked`].
+    pub fn flush(&self, deadline: Instant) -> io::Result<()> {
+        self.transmit_flush(Arc::from([]), deadline)
+    }
+
+    /// Sets what a transmission with nothing to transmit does. By default
+    /// it succeeds without touching the port, see [`EmptyTransmit`].
+    pub fn set_empty_transmit(&self, policy: EmptyTransmit) -> io::Result<()> {
+        self.request(|response| Request::SetEmptyTransmit(SetEmptyTransmit { policy, response }))
+    }
+
+    /// Transmits the parts back to back as one frame, e.g. a header,
+    /// a payload and a CRC, without concatenating them first. No other
+    /// request is served between the parts.
+    pub fn transmit_parts(&self, parts: &[&[u8]], deadline: Instant) -> io::Result<()> {
+        let parts = parts.iter().map(|&part| part.into()).collect();
+        self.request(|response| {
+            Request::TransmitParts(TransmitParts {
+                parts,
+                deadline,
+                response,
+            })
+        })
+    }
+
+    /// Transmits data to the serial port and verifies the local echo.
+    ///
+    /// Intended for half-duplex buses (1-wire, RS-485 without echo suppression)
+    /// and devices with local echo, where every transmitted byte is also
+    /// received. The echo is consumed and compared with the transmitted data;
+    /// a difference (e.g. a collision with another node) fails the transmit
+    /// with [`ArbiterError::EchoMismatch`]. An incomplete echo at the deadline
+    /// fails with `ErrorKind::TimedOut`.
+    pub fn transmit_echoed(&self, tx_bytes: Arc<[u8]>, deadline: Instant) -> io::Result<()> {
+        self.request(|response| {
+            Request::Transmit(Transmit {
+                tx_bytes,
+                deadline,
+                echo: true,
+                flush: false,
+                response,
+            })
+        })
     }
 
     /// Transmits a string to the serial port.
@@ -141,18 +877,163 @@ impl Arbiter {
         until: Option<u8>,
         deadline: Option<Instant>,
     ) -> io::Result<Option<Vec<u8>>> {
-        let (response, result_ch) = bounded(1);
-        let request = Request::Receive(Receive {
-            until,
-            deadline,
-            response,
-        });
-        if let Err(SendError { .. }) = self.chan.send(request) {
-            return Err(io::Error::other("Internal error"));
+        let result = self.receive_timestamped(until, deadline)?;
+        Ok(result.map(|x| x.data))
+    }
+
+    /// Receives data from the serial port together with the time
+    /// each chunk of the data was read from the port.
+    pub fn receive_timestamped(
+        &self,
+        until: Option<u8>,
+        deadline: Option<Instant>,
+    ) -> io::Result<Option<Timestamped>> {
+        self.request(|response| {
+            Request::Receive(Receive {
+                until,
+                deadline,
+                complete: false,
+                response,
+            })
+        })
+    }
+
+    /// Receives one complete frame terminated by the delimiter (included).
+    /// Returns None if no complete frame arrived before the deadline, in
+    /// which case any partial frame stays buffered for the next call.
+    pub fn receive_frame(&self, delimiter: u8, deadline: Instant) -> io::Result<Option<Vec<u8>>> {
+        let result = self.request(|response| {
+            Request::Receive(Receive {
+                until: Some(delimiter),
+                deadline: Some(deadline),
+                complete: true,
+                response,
+            })
+        })?;
+        Ok(result.map(|x| x.data))
+    }
+
+    /// Receives one frame terminated by the delimiter and parses it (without
+    /// the delimiter) with the given parser. Blank frames are skipped.
+    /// Fails with `ErrorKind::TimedOut` if no complete frame arrives before
+    /// the deadline and with [`ArbiterError::Parse`] if the parser fails.
+    pub fn receive_parsed<T, E: Display>(
+        &self,
+        delimiter: u8,
+        deadline: Instant,
+        mut parse: impl FnMut(&[u8]) -> Result<T, E>,
+    ) -> io::Result<T> {
+        loop {
+            let Some(mut frame) = self.receive_frame(delimiter, deadline)? else {
+                return Err(self.tagged(ErrorKind::TimedOut.into()));
+            };
+            frame.pop();
+            if frame.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            return parse(&frame).map_err(|err| {
+                let message = err.to_string();
+                self.tagged(ArbiterError::Parse { frame, message }.into())
+            });
         }
-        match result_ch.recv() {
-            Err(_) => Err(io::Error::other("Internal error")),
-            Ok(result) => result,
+    }
+
+    /// Encodes the message with the codec and transmits the frame.
+    pub fn transmit_encoded(
+        &self,
+        codec: &mut dyn Codec,
+        msg: &[u8],
+        deadline: Instant,
+    ) -> io::Result<()> {
+        let frame = codec.encode(msg)?;
+        self.transmit(frame.into(), deadline)
+    }
+
+    /// Receives one frame and decodes it with the codec. Empty frames are
+    /// skipped. Fails with `ErrorKind::TimedOut` if no complete frame
+    /// arrives before the deadline.
+    pub fn receive_decoded(&self, codec: &mut dyn Codec, deadline: Instant) -> io::Result<Vec<u8>> {
+        loop {
+            let Some(mut frame) = self.receive_frame(codec.delimiter(), deadline)? else {
+                return Err(self.tagged(ErrorKind::TimedOut.into()));
+            };
+            frame.pop();
+            if !frame.is_empty() {
+                return codec.decode(&frame).map_err(|err| self.tagged(err));
+            }
+        }
+    }
+
+    /// Serializes the message with postcard and transmits it as a COBS frame,
+    /// compatible with `postcard::to_slice_cobs` on the other end of the wire.
+    #[cfg(feature = "postcard")]
+    pub fn send_msg<T: serde::Serialize>(&self, msg: &T, deadline: Instant) -> io::Result<()> {
+        let msg = postcard::to_allocvec(msg)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err))?;
+        self.transmit_encoded(&mut CobsCodec, &msg, deadline)
+    }
+
+    /// Receives a COBS frame and deserializes the message with postcard.
+    /// Fails with [`ArbiterError::Parse`] if the message does not deserialize.
+    #[cfg(feature = "postcard")]
+    pub fn recv_msg<T: serde::de::DeserializeOwned>(&self, deadline: Instant) -> io::Result<T> {
+        let frame = self.receive_decoded(&mut CobsCodec, deadline)?;
+        postcard::from_bytes(&frame).map_err(|err| {
+            let message = err.to_string();
+            self.tagged(ArbiterError::Parse { frame, message }.into())
+        })
+    }
+
+    /// Receives one frame terminated by the delimiter and deserializes it from JSON.
+    /// See [`Arbiter::receive_parsed`] for details.
+    #[cfg(feature = "json")]
+    pub fn receive_json<T: serde::de::DeserializeOwned>(
+        &self,
+        delimiter: u8,
+        deadline: Instant,
+    ) -> io::Result<T> {
+        self.receive_parsed(delimiter, deadline, |frame| serde_json::from_slice(frame))
+    }
+
+    /// Blocks until at least one byte is available to be received or until
+    /// the deadline. Nothing is consumed. Returns true if data is available.
+    pub fn wait_readable(&self, deadline: Instant) -> io::Result<bool> {
+        self.request(|response| Request::WaitReadable(WaitReadable { deadline, response }))
+    }
+
+    /// Receives everything up to and including the first occurrence of the
+    /// pattern, e.g. the prompt of a device at the end of a long running
+    /// operation. The data which arrives in the meantime is handed to the
+    /// `on_partial` callback as soon as it is received, e.g. to show the
+    /// progress printed by the device. The callback gets the bytes in the
+    /// chunks they were received in, and runs on the calling thread while
+    /// the worker waits for the rest. Nothing is consumed until the pattern
+    /// matches. Fails with `ErrorKind::TimedOut` if the pattern does not
+    /// arrive before the deadline.
+    pub fn receive_until_match(
+        &self,
+        pattern: impl AsRef<[u8]>,
+        deadline: Instant,
+        mut on_partial: impl FnMut(&[u8]),
+    ) -> io::Result<Vec<u8>> {
+        let pattern: Arc<[u8]> = pattern.as_ref().into();
+        let mut seen = 0;
+        loop {
+            let progress = self.request(|response| {
+                Request::ReceiveMatch(ReceiveMatch {
+                    pattern: pattern.clone(),
+                    seen,
+                    deadline,
+                    response,
+                })
+            })?;
+            match progress {
+                MatchProgress::Matched(data) => return Ok(data),
+                MatchProgress::Partial(data) => {
+                    seen += data.len();
+                    on_partial(&data);
+                }
+            }
         }
     }
 
@@ -166,56 +1047,1071 @@ impl Arbiter {
         Ok(result.map(|x| String::from_utf8_lossy(&x).to_string()))
     }
 
-    /// Change the duration of cooloff after disconnecting due to an error
-    /// and before a new connection attempt is made. If set to None then
-    /// another connect attepmpt is tried without any artificial delays.
-    pub fn set_cooloff_duration(&self, cooloff: Option<Duration>) {
-        self.conn.set_cooloff_duration(cooloff);
+    /// Transmits data to the serial port and receives the response until the
+    /// given delimiter as a single transaction, so no other request can be
+    /// interleaved. Fails with `ErrorKind::TimedOut` if the delimiter does
+    /// not arrive before the deadline, or before the adaptive timeout if it
+    /// is sooner, see [`Arbiter::set_adaptive_timeout`]. Fails right away
+    /// while the circuit breaker is open, see [`Arbiter::set_circuit_breaker`].
+    pub fn transact(
+        &self,
+        tx_bytes: Arc<[u8]>,
+        until: u8,
+        deadline: Instant,
+    ) -> io::Result<Vec<u8>> {
+        let id = self.conn.next_transaction_id();
+        self.transact_inner(id, tx_bytes, until, deadline, false, false)
     }
-}
 
-impl WorkerThread {
-    fn new(connection: Arc<Connection>, requests: Receiver<Request>) -> Self {
-        Self {
-            buff: VecDeque::new(),
-            conn: connection,
-            chan: requests,
+    /// Performs a transaction and retries it according to the policy.
+    /// Each attempt gets its own `timeout` and every retry flushes the Rx
+    /// buffer before retransmitting. Returns the error of the last attempt.
+    /// The `timeout` is real time, as the port is polled until it runs out,
+    /// whereas the backoff of the policy follows the clock of the arbiter.
+    pub fn transact_with_retry(
+        &self,
+        tx_bytes: Arc<[u8]>,
+        until: u8,
+        timeout: Duration,
+        policy: &RetryPolicy,
+    ) -> io::Result<Vec<u8>> {
+        let id = self.conn.next_transaction_id();
+        let mut attempt = 1;
+        loop {
+            let deadline = Instant::now() + timeout;
+            let flush = attempt > 1;
+            match self.transact_inner(id, tx_bytes.clone(), until, deadline, flush, false) {
+                Ok(data) => return Ok(data),
+                Err(err) if attempt >= policy.attempts || !(policy.retriable)(&err) => {
+                    return Err(err)
+                }
+                Err(_) => {
+                    attempt += 1;
+                    self.conn.clock().sleep(policy.backoff);
+                }
+            }
         }
     }
 
-    fn spawn(mut self) {
-        thread::spawn(move || loop {
-            self.process();
+    /// Makes the transactions time out after a timeout derived from the
+    /// round-trip times of the recent ones, unless their deadline is sooner,
+    /// see [`AdaptiveTimeout`]. So the same code needs no hand-tuned
+    /// deadlines for a slow radio link and a fast USB one: given a distant
+    /// deadline, the transactions wait about as long as the device takes.
+    /// The round trips are measured from the call to the response, like by
+    /// [`Arbiter::ping`], which is a way to measure them before the first
+    /// transaction. A transaction which ran out of the adaptive timeout counts
+    /// as a round trip of that long, so the timeout grows for a device which
+    /// got slower. None, the default, disables it.
+    pub fn set_adaptive_timeout(&self, adaptive: Option<AdaptiveTimeout>) {
+        self.conn.set_adaptive_timeout(adaptive);
+    }
+
+    /// Makes the transactions fail right away with
+    /// [`ArbiterError::CircuitOpen`] once the given number of them failed in
+    /// a row, for the backoff of the breaker, so a bus master stops hitting
+    /// a device which is gone and the other clients of the port are not
+    /// held up by its timeouts. After the backoff one transaction tries the
+    /// device again: if it succeeds the transactions are let through again,
+    /// otherwise the breaker opens for another backoff. The transitions are
+    /// reported as [`Event::CircuitOpened`], [`Event::CircuitHalfOpen`] and
+    /// [`Event::CircuitClosed`]. The breaker is shared by all the handles
+    /// of the port. None, the default, disables it.
+    pub fn set_circuit_breaker(&self, policy: Option<CircuitBreaker>) {
+        self.conn.set_circuit_breaker(policy);
+    }
+
+    /// The timeout the transactions currently get, see
+    /// [`Arbiter::set_adaptive_timeout`]. None if it is not set.
+    pub fn adaptive_timeout(&self) -> Option<Duration> {
+        self.conn.adaptive_timeout()
+    }
+
+    /// Measures the round-trip time of the device by transmitting the frame
+    /// the given number of times and waiting for the response ending with
+    /// `expect` each time, up to the timeout. Every exchange drops what was
+    /// received before it, so a late response is not taken for the next one.
+    /// Timeouts and unexpected responses are counted, other errors stop the
+    /// measurement. The time is measured on the calling thread, so it also
+    /// includes the requests of the other handles the worker serves first.
+    pub fn ping(
+        &self,
+        frame: impl AsRef<[u8]>,
+        expect: impl AsRef<[u8]>,
+        samples: usize,
+        timeout: Duration,
+    ) -> io::Result<PingStats> {
+        let expect = expect.as_ref();
+        let Some(&until) = expect.last() else {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Empty expected response",
+            ));
+        };
+        let frame: Arc<[u8]> = frame.as_ref().into();
+        let mut stats = PingStats::default();
+        for _ in 0..samples {
+            let start = Instant::now();
+            stats.sent += 1;
+            let id = self.conn.next_transaction_id();
+            match self.transact_inner(id, frame.clone(), until, start + timeout, true, false) {
+                Ok(response) if response.ends_with(expect) => stats.rtts.push(start.elapsed()),
+                Ok(_) => stats.mismatched += 1,
+                Err(err) if err.kind() == ErrorKind::TimedOut => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Performs a transaction. An isolated one which fails is not accounted
+    /// by the adaptive timeout, the circuit breaker and the watchdog, which
+    /// judge the whole port. The attempts of a transaction share the id.
+    fn transact_inner(
+        &self,
+        id: u64,
+        tx_bytes: Arc<[u8]>,
+        until: u8,
+        deadline: Instant,
+        flush: bool,
+        isolated: bool,
+    ) -> io::Result<Vec<u8>> {
+        self.conn.circuit_admit().map_err(|err| self.tagged(err))?;
+        let start = Instant::now();
+        let adaptive = self.conn.adaptive_timeout().map(|timeout| start + timeout);
+        let result = self.request(|response| {
+            Request::Transact(Transact {
+                id,
+                tx_bytes,
+                until,
+                deadline: adaptive.map_or(deadline, |adaptive| adaptive.min(deadline)),
+                flush,
+                isolated,
+                response,
+            })
         });
+        match &result {
+            Ok(_) => self.conn.record_round_trip(start.elapsed()),
+            Err(_) if isolated => return result,
+            // Only running out of the adaptive timeout tells it is too short
+            Err(err)
+                if err.kind() == ErrorKind::TimedOut
+                    && adaptive.is_some_and(|adaptive| adaptive < deadline) =>
+            {
+                self.conn.record_round_trip(start.elapsed())
+            }
+            Err(_) => {}
+        }
+        self.conn.circuit_record(result.as_ref().err());
+        result
     }
 
-    fn process(&mut self) {
+    /// Starts writing all traffic of the port to the given session log, or
+    /// stops when None. Can be toggled at any time, e.g. on request of
+    /// a support engineer, without restarting the application.
+    pub fn set_session_log(&self, log: Option<SessionLog>) -> io::Result<()> {
+        self.request(|response| Request::SetSessionLog(SetSessionLog { log, response }))
+    }
+
+    /// Makes the retries of a transaction idempotent within the given time
+    /// window. A retry by [`Arbiter::transact_with_retry`] of a frame answered
+    /// within the window is not transmitted again and the earlier response
+    /// is returned instead. This also covers a retry racing a late response:
+    /// if the response to the previous, timed out, attempt has arrived
+    /// meanwhile, it is returned without retransmitting. Protects against
+    /// double actuation on flaky links. Separate transactions always go to
+    /// the wire, even with an identical frame, e.g. a periodic poll. The
+    /// window is measured by the clock of the arbiter, see
+    /// [`ArbiterBuilder::with_clock`].
+    /// Disabled (None) by default.
+    pub fn set_duplicate_window(&self, window: Option<Duration>) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetDuplicateWindow(SetDuplicateWindow { window, response })
+        })
+    }
+
+    /// Returns the share of the theoretical throughput of the link used in
+    /// each direction within the recent time window (1 second by default).
+    /// The throughput follows from the baud rate and the character format
+    /// of the port. Returns None if the port is closed or its baud rate is
+    /// not one of the standard ones.
+    pub fn link_utilization(&self) -> Option<Utilization> {
+        let char_rate = self.conn.char_rate()?;
+        Some(self.meter.lock().unwrap().utilization(char_rate))
+    }
+
+    /// Change the time window over which the link utilization is measured.
+    pub fn set_utilization_window(&self, window: Duration) {
+        self.meter.lock().unwrap().set_window(window);
+    }
+
+    /// Keeps the most recent traffic of the port in memory, up to the given
+    /// number of bytes, or none when zero (the default). See
+    /// [`Arbiter::recent_traffic`].
+    pub fn set_traffic_history(&self, capacity: usize) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetTrafficHistory(SetTrafficHistory { capacity, response })
+        })
+    }
+
+    /// Returns the most recent traffic of the port, oldest first, e.g. to
+    /// attach the wire history to the report of a failed transaction. Empty
+    /// unless enabled with [`Arbiter::set_traffic_history`].
+    pub fn recent_traffic(&self) -> Vec<TrafficRecord> {
+        self.history.lock().unwrap().records()
+    }
+
+    /// Returns what this handle has used the port for. Every clone of the
+    /// arbiter is counted apart, so giving each subsystem its own clone shows
+    /// which of them keeps the port busy.
+    pub fn usage(&self) -> ClientUsage {
+        self.usage.usage()
+    }
+
+    /// Returns the usage of the port by every handle of the arbiter which is
+    /// alive, oldest first, see [`Arbiter::usage`].
+    pub fn client_usage(&self) -> Vec<ClientUsage> {
+        self.clients.lock().unwrap().usage()
+    }
+
+    /// Emits [`Event::LineIdle`] once nothing has been received for the
+    /// timeout while the port is open, and [`Event::TrafficResumed`] when
+    /// data arrives again, to tell a quiet device from a stalled stream
+    /// without polling. None, the default, disables it.
+    pub fn set_idle_line_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetIdleLineTimeout(SetIdleLineTimeout { timeout, response })
+        })
+    }
+
+    /// Subscribes to the events of the arbiter, e.g. the progress of
+    /// transmissions larger than 1 KiB. Every subscriber gets all events
+    /// emitted after subscribing. Events are dropped for a subscriber which
+    /// falls behind by more than 256 events.
+    pub fn events(&self) -> Receiver<Event> {
+        self.conn.events.subscribe()
+    }
+
+    /// Change the duration of cooloff after disconnecting due to an error
+    /// and before a new connection attempt is made. If set to None then
+    /// another connect attepmpt is tried without any artificial delays.
+    pub fn set_cooloff_duration(&self, cooloff: Option<Duration>) {
+        self.conn.set_cooloff_duration(cooloff);
+    }
+
+    /// Sets how long a request waits for a closed port to be reopened,
+    /// independently of the deadline of the request, which then bounds only
+    /// the I/O. By default (None) a request fails right away when the port
+    /// cannot be reopened, e.g. with `ErrorKind::QuotaExceeded` during the
+    /// cooloff.
+    pub fn set_connect_timeout(&self, timeout: Option<Duration>) {
+        self.conn.set_connect_timeout(timeout);
+    }
+
+    /// Makes the cooloff escalate with every consecutive failed attempt to
+    /// open the port, e.g. `[100 ms, 500 ms, 2 s]`, with the last step
+    /// repeated until the port opens, which resets the escalation. Reconnects
+    /// fast after a brief glitch without hammering a flapping USB hub.
+    /// Overrides [`Arbiter::set_cooloff_duration`] unless empty.
+    pub fn set_escalating_cooloff(&self, steps: impl Into<Vec<Duration>>) {
+        self.conn.set_escalating_cooloff(steps.into());
+    }
+
+    /// Sets how many times opening the port is retried, with the delay in
+    /// between, when it fails with EBUSY (e.g. ModemManager probing a newly
+    /// plugged in modem) or with EACCES or EPERM (udev not having applied
+    /// the permissions yet), before the failure counts as an attempt. 3 times
+    /// 100 ms by default. Only the open of the device is retried, not the
+    /// setup of the port once it opened. The other clones are not held up
+    /// while waiting for the next try.
+    pub fn set_open_retries(&self, retries: usize, delay: Duration) {
+        self.conn.set_open_retries(retries, delay);
+    }
+
+    /// Caps how many bytes may wait in the transmit queue of the kernel.
+    /// Transmissions pause writing while the queue is full, so they complete
+    /// only when at most `limit` bytes are left to go out, rather than when
+    /// the whole burst is buffered and still trickling out to a slow peer.
+    /// Also makes the transmit deadline cover the actual transmission.
+    /// Unlimited by default. Ignored by drivers which do not report their
+    /// queue (TIOCOUTQ).
+    pub fn set_tx_queue_limit(&self, limit: Option<usize>) {
+        self.conn.set_tx_queue_limit(limit);
+    }
+
+    /// Reopens the port whenever the system resumes from suspend, which is
+    /// when USB serial adapters commonly re-enumerate. Otherwise the port is
+    /// only checked as by [`Arbiter::is_alive`], which misses a device that
+    /// came back under the same node. Off by default. The resume is detected
+    /// by the worker within a second and emits [`Event::Resumed`].
+    pub fn set_reopen_on_resume(&self, reopen: bool) {
+        self.conn.set_reopen_on_resume(reopen);
+    }
+
+    /// Closes the port when the requests using it have been timing out for
+    /// the window without a single success, emitting [`Event::WatchdogFired`].
+    /// Some USB serial bridges wedge until reopened, which happens with the
+    /// next request unless reopening is left to the application. Errors
+    /// other than timeouts neither count nor reset the window. None, the
+    /// default, disables the watchdog.
+    pub fn set_watchdog(&self, window: Option<Duration>) {
+        self.conn.set_watchdog(window);
+    }
+
+    /// Returns the ongoing cooloff, with the reason and the time left until
+    /// the next attempt to open the port, e.g. to show "retrying in 700 ms".
+    pub fn cooloff(&self) -> Option<Cooloff> {
+        self.conn.cooloff()
+    }
+
+    /// Selects raw (default) or canonical line processing by the kernel.
+    /// Takes effect the next time the port is opened.
+    pub fn set_line_mode(&self, mode: LineMode) {
+        self.conn.set_line_mode(mode);
+    }
+
+    /// Sets whether the port ignores the modem control lines (`CLOCAL` set)
+    /// or honors the Data Carrier Detect line (`CLOCAL` cleared), which real
+    /// modems and some industrial converters need. Honoring it, the kernel
+    /// hangs up the port when the carrier drops, which the worker sees as a
+    /// disconnect. The port is opened without waiting for the carrier
+    /// either way. Unless set, the setting the device has stays. Takes
+    /// effect the next time the port is opened.
+    pub fn set_clocal(&self, clocal: Option<bool>) {
+        self.conn.set_clocal(clocal);
+    }
+
+    /// Returns true if the open port ignores the modem control lines, see
+    /// [`Arbiter::set_clocal`].
+    pub fn clocal(&self) -> io::Result<bool> {
+        self.with_raw_fd(|fd| port_clocal(fd.as_raw_fd()))?
+    }
+
+    /// Sets whether closing the port drops the DTR and RTS lines (`HUPCL`).
+    /// Dropping them resets an Arduino and power-cycles some sensors, so
+    /// without it every reconnect may reboot the device. The settings
+    /// restored on close, see [`Arbiter::set_restore_termios`], keep it too.
+    /// Unless set, the setting the device has stays. Takes effect the next
+    /// time the port is opened.
+    pub fn set_hupcl(&self, hupcl: Option<bool>) {
+        self.conn.set_hupcl(hupcl);
+    }
+
+    /// Returns true if closing the open port drops the DTR and RTS lines,
+    /// see [`Arbiter::set_hupcl`].
+    pub fn hupcl(&self) -> io::Result<bool> {
+        self.with_raw_fd(|fd| port_hupcl(fd.as_raw_fd()))?
+    }
+
+    /// Sets what the kernel does with a break condition on the line, see
+    /// [`InputPolicy`]. Unless set, a break is read as whatever the raw or
+    /// canonical mode leaves it to. Takes effect the next time the port is
+    /// opened.
+    pub fn set_break_input(&self, policy: Option<InputPolicy>) {
+        self.conn.set_break_input(policy);
+    }
+
+    /// Sets what the kernel does with the bytes received with a parity or
+    /// framing error, see [`InputPolicy`]. Unless set, the parity checking
+    /// of the device settings stays. The multidrop mode overrides it. Takes
+    /// effect the next time the port is opened.
+    pub fn set_parity_error_input(&self, policy: Option<InputPolicy>) {
+        self.conn.set_parity_error_input(policy);
+    }
+
+    /// Opens the port with the synchronized I/O flags (`O_SYNC`, `O_DSYNC`,
+    /// `O_RSYNC`). Disabled by default: ttys have no backing storage to
+    /// synchronize with, and some USB-serial drivers turn every write into a
+    /// slow synchronous round trip when these flags are set. Enable only if
+    /// a particular driver is known to need them.
+    /// Takes effect the next time the port is opened.
+    pub fn set_sync_io(&self, sync_io: bool) {
+        self.conn.set_sync_io(sync_io);
+    }
+
+    /// Registers a closure which adjusts the termios settings every time the
+    /// port is opened, including automatic reconnects. It runs after the line
+    /// mode is set up and before the settings are applied to the port, which
+    /// allows enabling unusual flags (IGNPAR, specific VMIN/VTIME, etc.).
+    /// Takes effect the next time the port is opened. It may use the getters
+    /// and setters of the arbiter, but when run by the worker, reopening the
+    /// port, its requests fail with [`ArbiterError::Reentrant`].
+    pub fn set_termios_hook(&self, hook: impl FnMut(&mut Termios) + Send + 'static) {
+        self.conn.set_termios_hook(Some(Box::new(hook)));
+    }
+
+    /// Removes the closure registered with `set_termios_hook`.
+    pub fn remove_termios_hook(&self) {
+        self.conn.set_termios_hook(None);
+    }
+
+    /// Sets the baud rate, the character format and the flow control of the
+    /// port, see [`SerialConfig`]. They are applied every time the port is
+    /// opened, including the automatic reconnects, and right away if it is
+    /// open. Without them the port keeps whatever the device was set to.
+    /// An invalid config is rejected and the previous one kept, see
+    /// [`SerialConfig::validate`].
+    pub fn set_config(&self, config: SerialConfig) -> io::Result<()> {
+        config.validate()?;
+        self.conn.set_config(config);
+        if !self.conn.is_open() {
+            return Ok(());
+        }
+        self.with_raw_fd(|fd| {
+            let mut termios = Termios::from_fd(fd.as_raw_fd())?;
+            config.apply(&mut termios)?;
+            port_set_termios(fd.as_raw_fd(), &termios)
+        })?
+    }
+
+    /// Returns the termios settings of the open port, e.g. to restore them
+    /// with [`Arbiter::set_termios`] after a temporary change. Like
+    /// [`Arbiter::with_raw_fd`], it waits for the requests ahead of it.
+    pub fn get_termios(&self) -> io::Result<Termios> {
+        self.with_raw_fd(|fd| Termios::from_fd(fd.as_raw_fd()))?
+    }
+
+    /// Applies the termios settings to the open port right away, between
+    /// the requests, e.g. to toggle `IXON` for the duration of a transfer:
+    ///
+    /// ```no_run
+    /// # use serial_arbiter::{termios, Arbiter};
+    /// # let arbiter = Arbiter::new();
+    /// let saved = arbiter.get_termios()?;
+    /// let mut settings = saved;
+    /// settings.c_iflag |= termios::IXON;
+    /// arbiter.set_termios(&settings)?;
+    /// // ... the transfer ...
+    /// arbiter.set_termios(&saved)?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// Data still leaving the port is sent with the new settings, see
+    /// [`Arbiter::flush`] to wait for it first. The settings last until the
+    /// port is reopened, which applies the configured ones again; to change
+    /// them for good, see [`Arbiter::set_termios_hook`].
+    pub fn set_termios(&self, termios: &Termios) -> io::Result<()> {
+        self.with_raw_fd(|fd| port_set_termios(fd.as_raw_fd(), termios))?
+    }
+
+    /// When enabled, the termios settings which the device had when it was
+    /// first opened are restored every time the port is closed, including
+    /// when the last clone of the arbiter is dropped. Useful when automating
+    /// a console tty which must remain usable for others afterwards.
+    /// Disabled by default.
+    pub fn set_restore_termios(&self, restore: bool) {
+        self.conn.set_restore_termios(restore);
+    }
+
+    /// Sets the GPIO line driving the direction of an RS-485 transceiver.
+    /// The line is asserted before every transmission and released once
+    /// all data has been drained from the UART, which may take longer than
+    /// the transmit deadline at low baud rates.
+    pub fn set_direction_gpio(&self, gpio: Option<DirectionGpio>) -> io::Result<()> {
+        self.request(|response| Request::SetDirectionGpio(SetDirectionGpio { gpio, response }))
+    }
+
+    /// Enables 9-bit multidrop communication, used by several industrial
+    /// buses. The 9th bit is carried by the parity bit, so the port must
+    /// support mark/space (stick) parity. Received address bytes (9th bit
+    /// set) are delivered as the first byte of the frame they start. With
+    /// an `address_filter` only frames addressed to the given address are
+    /// received and everything else on the bus is dropped.
+    pub fn enable_multidrop(&self, address_filter: Option<u8>) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetMultidrop(SetMultidrop {
+                enabled: true,
+                filter: address_filter,
+                response,
+            })
+        })
+    }
+
+    /// Disables 9-bit multidrop communication.
+    pub fn disable_multidrop(&self) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetMultidrop(SetMultidrop {
+                enabled: false,
+                filter: None,
+                response,
+            })
+        })
+    }
+
+    /// Transmits the address byte with the 9th bit set followed by the
+    /// data with the 9th bit cleared. Requires the multidrop mode.
+    pub fn transmit_addressed(
+        &self,
+        address: u8,
+        tx_bytes: Arc<[u8]>,
+        deadline: Instant,
+    ) -> io::Result<()> {
+        self.request(|response| {
+            Request::TransmitAddressed(TransmitAddressed {
+                address,
+                tx_bytes,
+                deadline,
+                response,
+            })
+        })
+    }
+
+    /// Installs a router which takes every complete frame (terminated by the
+    /// `delimiter`) out of the Rx buffer and delivers it to the request
+    /// waiting for its correlation key, as returned by the `extractor`.
+    /// Frames without a key, or with a key nobody waits for, are sent to
+    /// the returned receiver. It buffers up to 256 frames, after which new
+    /// ones are dropped until it is drained, so it may be ignored if only
+    /// the routed frames matter. Replaces any previously installed router.
+    pub fn set_router(
+        &self,
+        delimiter: u8,
+        extractor: impl Fn(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+    ) -> io::Result<Receiver<Vec<u8>>> {
+        let (sink, unmatched) = bounded(UNMATCHED_CAPACITY);
+        let router = Router::new(delimiter, Box::new(extractor), sink);
+        self.request(|response| {
+            Request::SetRouter(SetRouter {
+                router: Some(router),
+                response,
+            })
+        })?;
+        Ok(unmatched)
+    }
+
+    /// Schedules the frame to be transmitted every `interval`, starting right
+    /// away, e.g. for keepalives, watchdog pets or DMX refreshes. The worker
+    /// sends the frame between the requests, so it never interleaves with
+    /// them, which may delay it by the duration of a long request. Returns
+    /// the id for [`Arbiter::unschedule`].
+    pub fn schedule(&self, tx_bytes: Arc<[u8]>, interval: Duration) -> io::Result<u64> {
+        if interval.is_zero() {
+            let err = io::Error::new(ErrorKind::InvalidInput, "Zero scheduling interval");
+            return Err(err);
+        }
+        self.request(|response| {
+            Request::Schedule(Schedule {
+                tx_bytes,
+                interval,
+                response,
+            })
+        })
+    }
+
+    /// Stops transmitting the scheduled frame. Returns false if there was no
+    /// frame scheduled with the given id.
+    pub fn unschedule(&self, id: u64) -> io::Result<bool> {
+        self.request(|response| Request::Unschedule(Unschedule { id, response }))
+    }
+
+    /// Sets the minimum time since the end of the last transmission before
+    /// a scheduled frame is sent, so that the receiver can tell the frames
+    /// apart (e.g. the Modbus RTU T3.5 gap). Zero by default.
+    pub fn set_schedule_gap(&self, gap: Duration) -> io::Result<()> {
+        self.request(|response| Request::SetScheduleGap(SetScheduleGap { gap, response }))
+    }
+
+    /// Registers a callback which gets every frame received, decoded by the
+    /// codec (or the error of decoding it), for push-style delivery instead
+    /// of blocking receive calls. The callback runs on its own dispatch
+    /// thread, so it may take its time and may call the arbiter. Frames are
+    /// taken out of the Rx buffer as soon as they are complete, so they are
+    /// not returned by receive calls. Replaces any previously registered
+    /// callback.
+    pub fn on_frame(
+        &self,
+        codec: impl Codec + 'static,
+        callback: impl FnMut(io::Result<Vec<u8>>) + Send + 'static,
+    ) -> io::Result<()> {
+        let handler = FrameHandler::new(Box::new(codec), Box::new(callback));
+        self.request(|response| {
+            Request::SetFrameHandler(SetFrameHandler {
+                handler: Some(handler),
+                response,
+            })
+        })
+    }
+
+    /// Unregisters the frame callback. Frames already decoded are still
+    /// delivered to it.
+    pub fn remove_frame_handler(&self) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetFrameHandler(SetFrameHandler {
+                handler: None,
+                response,
+            })
+        })
+    }
+
+    /// Installs a filter dropping known frames, e.g. keep-alives, as soon as
+    /// they are received, or removes it when None. The frames already in the
+    /// Rx buffer are filtered too. Replaces any previously set filter.
+    pub fn set_rx_filter(&self, filter: Option<RxFilter>) -> io::Result<()> {
+        self.request(|response| Request::SetRxFilter(SetRxFilter { filter, response }))
+    }
+
+    /// Sets the size of a single read from the port, 1 MiB by default, and the
+    /// budget of bytes the worker reads at a time, unlimited by default. With a
+    /// budget a device which never stops sending cannot monopolize the worker:
+    /// once the budget is spent the pending requests, e.g. transmits, are
+    /// served and the rest of the data is read on the next turn.
+    pub fn set_read_limits(&self, chunk_size: usize, budget: Option<usize>) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetReadLimits(SetReadLimits {
+                chunk_size,
+                budget,
+                response,
+            })
+        })
+    }
+
+    /// Reads the port on a dedicated thread, which hands the data over to the
+    /// worker through a pipe, e.g. for streaming at 1 Mbaud. The port is then
+    /// read continuously, even while a long transmission or a slow request is
+    /// in progress, and the incoming data never delays the transmissions. Off
+    /// by default. The thread is stopped while the port is paused or leased.
+    pub fn set_rx_pump(&self, enabled: bool) -> io::Result<()> {
+        self.request(|response| Request::SetRxPump(SetRxPump { enabled, response }))
+    }
+
+    /// Pins the worker thread, and the Rx pump if enabled, to CPUs and sets
+    /// their niceness, to keep the scheduler from adding jitter to the timing
+    /// of the protocol, see [`ThreadTuning`]. The settings applied before an
+    /// error, e.g. the affinity when raising the priority is not permitted,
+    /// stay in effect.
+    pub fn set_thread_tuning(&self, tuning: ThreadTuning) -> io::Result<()> {
+        self.request(|response| Request::SetThreadTuning(SetThreadTuning { tuning, response }))
+    }
+
+    /// Installs a transform of the received byte stream, which the worker
+    /// applies to every chunk read from the port before buffering it, e.g.
+    /// to strip the padding a device emits:
+    ///
+    /// ```no_run
+    /// # let arbiter = serial_arbiter::Arbiter::new();
+    /// arbiter.set_rx_transform(|input, output| {
+    ///     output.extend(input.iter().filter(|&&byte| byte != 0));
+    /// })?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// The chunks are cut arbitrarily, so a transform which works on
+    /// sequences of bytes has to keep the unfinished ones to itself until
+    /// the next chunk. The session log records the bytes before the
+    /// transform. Replaces any previously installed transform. The transform
+    /// runs on the worker, so the requests it makes to this arbiter fail
+    /// with [`ArbiterError::Reentrant`].
+    pub fn set_rx_transform(
+        &self,
+        transform: impl FnMut(&[u8], &mut Vec<u8>) + Send + 'static,
+    ) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetRxTransform(SetRxTransform {
+                transform: Some(Box::new(transform)),
+                response,
+            })
+        })
+    }
+
+    /// Removes the transform of the received byte stream.
+    pub fn remove_rx_transform(&self) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetRxTransform(SetRxTransform {
+                transform: None,
+                response,
+            })
+        })
+    }
+
+    /// Installs a transform of the transmitted frames, e.g. escaping or
+    /// appending a checksum, which the worker applies to every frame
+    /// transmitted by any clone of the arbiter, so the encoding on the wire
+    /// is consistent:
+    ///
+    /// ```no_run
+    /// # let arbiter = serial_arbiter::Arbiter::new();
+    /// arbiter.set_tx_transform(|frame, output| {
+    ///     let checksum = frame.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    ///     output.extend_from_slice(frame);
+    ///     output.push(checksum);
+    /// })?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// The transform gets the whole frame of every transmission, with the
+    /// parts of [`Arbiter::transmit_parts`] joined and without the address
+    /// of [`Arbiter::transmit_addressed`]. Echoes are verified against the
+    /// transformed frame and the session log records it as transmitted.
+    /// Replaces any previously installed transform. Like the Rx transform,
+    /// it cannot make requests to this arbiter.
+    pub fn set_tx_transform(
+        &self,
+        transform: impl FnMut(&[u8], &mut Vec<u8>) + Send + 'static,
+    ) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetTxTransform(SetTxTransform {
+                transform: Some(Box::new(transform)),
+                response,
+            })
+        })
+    }
+
+    /// Removes the transform of the transmitted frames.
+    pub fn remove_tx_transform(&self) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetTxTransform(SetTxTransform {
+                transform: None,
+                response,
+            })
+        })
+    }
+
+    /// Removes the router. Requests still waiting for their frames fail
+    /// with `ErrorKind::Interrupted`.
+    pub fn remove_router(&self) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetRouter(SetRouter {
+                router: None,
+                response,
+            })
+        })
+    }
+
+    /// Transmits data to the serial port and waits for the frame with the
+    /// given correlation key. The key is registered by the worker within the
+    /// same transaction so even an immediate response cannot be missed.
+    pub fn transmit_routed(
+        &self,
+        tx_bytes: Arc<[u8]>,
+        key: impl Into<Vec<u8>>,
+        deadline: Instant,
+    ) -> io::Result<Vec<u8>> {
+        self.request(|response| {
+            Request::Route(Route {
+                tx_bytes: Some(tx_bytes),
+                key: key.into(),
+                deadline,
+                response,
+            })
+        })
+    }
+
+    /// Waits for the frame with the given correlation key.
+    pub fn receive_routed(
+        &self,
+        key: impl Into<Vec<u8>>,
+        deadline: Instant,
+    ) -> io::Result<Vec<u8>> {
+        self.request(|response| {
+            Request::Route(Route {
+                tx_bytes: None,
+                key: key.into(),
+                deadline,
+                response,
+            })
+        })
+    }
+
+    /// Asserts (true) or deasserts (false) a modem control line.
+    pub fn set_modem_line(&self, line: ModemLine, level: bool) -> io::Result<()> {
+        self.with_raw_fd(|fd| port_set_modem_line(fd.as_raw_fd(), line, level))?
+    }
+
+    /// Returns true if the Data Carrier Detect line is asserted.
+    pub fn carrier(&self) -> io::Result<bool> {
+        self.with_raw_fd(|fd| port_carrier(fd.as_raw_fd()))?
+    }
+
+    /// Starts treating the loss of the carrier as a disconnect, optionally
+    /// hanging up and redialing, or stops when None. The carrier is checked
+    /// once a second while the port is idle.
+    pub fn set_carrier_watch(&self, watch: Option<CarrierWatch>) -> io::Result<()> {
+        self.request(|response| Request::SetCarrierWatch(SetCarrierWatch { watch, response }))
+    }
+
+    /// Transmits a break, a continuous stream of zero bits for 0.25 to
+    /// 0.5 seconds, used e.g. to get the attention of a boot loader.
+    pub fn send_break(&self) -> io::Result<()> {
+        self.with_raw_fd(|fd| port_send_break(fd.as_raw_fd()))?
+    }
+
+    /// Runs the closure with the file descriptor of the serial port, e.g. to
+    /// issue an ioctl which is not wrapped by this crate. The worker thread
+    /// holds the port locked and does not touch it until the closure returns,
+    /// so the closure cannot race with any reads or writes. The worker waits
+    /// for the closure, so the closure must not call back into the arbiter:
+    /// the requests it makes to it, of any of its clones, fail with
+    /// [`ArbiterError::Reentrant`].
+    pub fn with_raw_fd<R>(&self, f: impl FnOnce(BorrowedFd<'_>) -> R) -> io::Result<R> {
+        let (_release, release) = bounded(0);
+        let fd = self.request(|response| Request::Park(Park { response, release }))?;
+        let _parked = self.conn.park_here();
+        // SAFETY: The worker keeps the file open and locked until `_release` is dropped.
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        Ok(f(fd))
+    }
+
+    /// Stops touching the port without closing it, e.g. while an external
+    /// flasher takes over the device. No data is received in the background,
+    /// no scheduled frames are transmitted and the port is not reopened.
+    /// Requests which need the port fail with [`ArbiterError::Paused`]
+    /// until [`Arbiter::resume`] is called.
+    pub fn pause(&self) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetPaused(SetPaused {
+                paused: true,
+                response,
+            })
+        })
+    }
+
+    /// Resumes using the port after [`Arbiter::pause`].
+    pub fn resume(&self) -> io::Result<()> {
+        self.request(|response| {
+            Request::SetPaused(SetPaused {
+                paused: false,
+                response,
+            })
+        })
+    }
+
+    /// Hands out the file descriptor of the port for exclusive use until the
+    /// returned lease is dropped, see [`FdLease`]. Waits until the deadline
+    /// for the port to open. Fails with [`ArbiterError::Paused`] if the
+    /// arbiter is paused or the fd is already leased.
+    pub fn lease_fd(&self, deadline: Instant) -> io::Result<FdLease> {
+        let file = self.request(|response| Request::Lease(Lease { deadline, response }))?;
+        Ok(FdLease::new(self.clone(), file))
+    }
+
+    /// Resumes normal operation after the fd lease and drops the data
+    /// received so far.
+    fn end_lease(&self) -> io::Result<()> {
+        self.request(|response| Request::EndLease(EndLease { response }))
+    }
+
+    /// Sends a request to the worker thread and waits for its response.
+    fn request<T>(&self, request: impl FnOnce(Sender<io::Result<T>>) -> Request) -> io::Result<T> {
+        if self.conn.is_worker_thread() || self.conn.is_parked_here() {
+            return Err(self.tagged(ArbiterError::Reentrant.into()));
+        }
+        if self.conn.is_shut_down() {
+            return Err(self.tagged(ArbiterError::ShuttingDown.into()));
+        }
+        let (response, result_ch) = bounded(1);
+        let trace = Arc::new(RequestTrace::new());
+        let request = request(response);
+        let needs_port = request.needs_port();
+        let isolated = request.is_isolated();
+        let envelope = Envelope {
+            tag: self.tag.clone(),
+            request,
+            trace: trace.clone(),
+        };
+        if let Err(err) = self.enqueue(envelope) {
+            let timed_out = err.kind() == ErrorKind::TimedOut;
+            self.usage.record(&trace.diagnostics(), timed_out);
+            return Err(self.tagged(err));
+        }
+        let result = match result_ch.recv() {
+            Err(_) => Err(self.worker_gone()),
+            Ok(result) => result,
+        };
+        if needs_port && !(isolated && result.is_err()) {
+            self.conn.watch_request(result.as_ref().err());
+        }
+        let diagnostics = trace.diagnostics();
+        let timed_out = matches!(&result, Err(err) if err.kind() == ErrorKind::TimedOut);
+        self.usage.record(&diagnostics, timed_out);
+        result.map_err(|err| self.tagged(self.diagnosed(err, diagnostics)))
+    }
+
+    /// Hands the request over to the worker, waiting for it to finish the
+    /// requests before no longer than the enqueue timeout.
+    fn enqueue(&self, envelope: Envelope) -> io::Result<()> {
+        let Some(timeout) = self.enqueue_timeout else {
+            return self.chan.send(envelope).map_err(|_| self.worker_gone());
+        };
+        match self.chan.send_timeout(envelope, timeout) {
+            Ok(()) => Ok(()),
+            Err(SendTimeoutError::Timeout(_)) => {
+                Err(ArbiterError::EnqueueTimeout { waited: timeout }.into())
+            }
+            Err(SendTimeoutError::Disconnected(_)) => Err(self.worker_gone()),
+        }
+    }
+
+    /// The error of a request the worker dropped without a response.
+    fn worker_gone(&self) -> io::Error {
+        if self.conn.is_shut_down() {
+            return ArbiterError::ShuttingDown.into();
+        }
+        io::Error::other("Internal error")
+    }
+
+    /// Replaces a bare timeout by [`ArbiterError::DeadlineExceeded`], unless
+    /// a likely baud rate mismatch explains it better.
+    fn diagnosed(&self, error: io::Error, diagnostics: TimeoutDiagnostics) -> io::Error {
+        if error.kind() != ErrorKind::TimedOut
+            || error.get_ref().is_some()
+            || self.conn.baud_mismatch().is_some()
+        {
+            return error;
+        }
+        ArbiterError::DeadlineExceeded(diagnostics).into()
+    }
+
+    /// Wraps the error in a [`TaggedError`] if the arbiter has a tag and the
+    /// error is not tagged yet. A bare timeout on a connection with a likely
+    /// baud rate mismatch is replaced by [`ArbiterError::LikelyBaudMismatch`].
+    fn tagged(&self, error: io::Error) -> io::Error {
+        let error = match self.conn.baud_mismatch() {
+            Some(mismatch) if error.kind() == ErrorKind::TimedOut && error.get_ref().is_none() => {
+                ArbiterError::LikelyBaudMismatch(mismatch).into()
+            }
+            _ => error,
+        };
+        let Some(tag) = &self.tag else {
+            return error;
+        };
+        if TaggedError::tag_of(&error).is_some() {
+            return error;
+        }
+        log::debug!(tag:% = tag, error:% = error; "Request {tag} failed: {error}");
+        let kind = error.kind();
+        let tag = tag.clone();
+        io::Error::new(kind, TaggedError { tag, error })
+    }
+}
+
+impl WorkerThread {
+    fn new(
+        connection: Arc<Connection>,
+        meter: Arc<Mutex<LinkMeter>>,
+        history: Arc<Mutex<TrafficHistory>>,
+        requests: Receiver<Envelope>,
+    ) -> Self {
+        Self {
+            buff: RxBuffer::new(),
+            conn: connection,
+            meter,
+            history,
+            chan: requests,
+            preempted: None,
+            router: None,
+            direction_gpio: None,
+            multidrop: false,
+            rx_pump_enabled: false,
+            rx_pump: None,
+            tuning: ThreadTuning::new(),
+            empty_transmit: EmptyTransmit::default(),
+            idle_line: None,
+            shutdown: Vec::new(),
+            suspend: SuspendDetector::new(),
+            duplicates: None,
+            last_device_check: Instant::now(),
+            stale: VecDeque::new(),
+            #[cfg(feature = "metrics")]
+            metrics: PortMetrics::new(String::new()),
+            session_log: None,
+            frame_handler: None,
+            scheduler: Scheduler::new(),
+            last_tx: None,
+            carrier: None,
+            tag: None,
+            trace: None,
+            paused: false,
+            tx_transform: None,
+            baud_check: None,
+        }
+    }
+
+    fn spawn(mut self) {
+        thread::spawn(move || {
+            self.conn.set_worker_thread();
+            // Wake up on time rather than grouped with other timers. Best effort.
+            let _ = set_timerslack(TIMER_SLACK_NS);
+            self.process()
+        });
+    }
+
+    fn process(&mut self) {
         loop {
-            let request_recv = self.chan.recv_timeout(POLLING_INTERVAL);
+            if let Some(deadline) = self.conn.shutdown_deadline() {
+                if Instant::now() >= deadline || !self.has_pending() {
+                    return self.stop();
+                }
+            }
+            let request_recv = match self.preempted.take() {
+                Some(envelope) => Ok(envelope),
+                None => self.chan.recv_timeout(POLLING_INTERVAL),
+            };
             match request_recv {
                 Err(RecvTimeoutError::Disconnected) => {
                     // Stop signal
                     return;
                 }
+                Err(RecvTimeoutError::Timeout) if self.paused => {
+                    self.tag = None;
+                }
                 Err(RecvTimeoutError::Timeout) => {
-                    // Collect incomming data to avoid RX buffer starvation
-                    let _ = self.receive_from_port(None, None);
+                    self.tag = None;
+                    // Collect incomming data to avoid RX buffer starvation.
+                    // This also detects the errors and hangups reported by poll.
+                    let _ = self.collect_from_port();
+                    let now = self.conn.clock().now();
+                    if now.saturating_duration_since(self.last_device_check)
+                        >= DEVICE_CHECK_INTERVAL
+                    {
+                        self.last_device_check = now;
+                        if let Some(suspended) = self.suspend.check() {
+                            self.resumed(suspended);
+                        } else if self.conn.check_device() {
+                            self.check_carrier();
+                        }
+                    }
                 }
-                Ok(request) => match request {
+                Ok(Envelope {
+                    tag,
+                    request,
+                    trace,
+                }) => match self.await_port(tag, trace, request) {
                     Request::Clear(tx) => {
-                        let result = if self.conn.is_open() {
-                            self.receive_from_port(None, None)
+                        let result = self.clear_rx();
+                        let _ = tx.response.try_send(result);
+                    }
+                    Request::Transmit(tx) => {
+                        let result = if tx.tx_bytes.is_empty() {
+                            self.transmit_nothing(tx.flush, tx.deadline)
+                        } else if tx.echo {
+                            self.transmit_echoed(tx.tx_bytes, tx.deadline)
                         } else {
-                            Ok(())
+                            self.transmit_to_port(tx.tx_bytes, tx.deadline)
+                        };
+                        let result = match result {
+                            Ok(()) if tx.flush => self.flush_port(tx.deadline),
+                            result => result,
                         };
-                        self.buff.clear();
                         let _ = tx.response.try_send(result);
                     }
-                    Request::Transmit(tx) => {
-                        let result = self.transmit_to_port(tx.tx_bytes, tx.deadline);
+                    Request::TransmitParts(tx) => {
+                        let parts: Vec<&[u8]> = tx.parts.iter().map(|part| &**part).collect();
+                        let result = if parts.iter().all(|part| part.is_empty()) {
+                            self.transmit_nothing(false, tx.deadline)
+                        } else {
+                            self.transmit_parts_to_port(&parts, tx.deadline)
+                        };
                         let _ = tx.response.try_send(result);
                     }
                     Request::Receive(rx) => {
+                        // Data from before a reconnect goes first and on its own
+                        if !rx.complete {
+                            if let Some(data) = self.stale.pop_front() {
+                                let _ = rx.response.try_send(Ok(Some(data)));
+                                continue;
+                            }
+                        }
+
                         // Check if we can skip reading from port
                         if let Some(delimiter) = rx.until {
                             // If we have all needed data
@@ -237,13 +2133,675 @@ impl WorkerThread {
                         // Return collected data
                         let colltype = match rx.until {
                             None => CollectKind::Everything,
+                            Some(delimiter) if rx.complete => {
+                                CollectKind::UntilOrNothing(delimiter)
+                            }
                             Some(delimiter) => CollectKind::UntilOrEverything(delimiter),
                         };
                         let data = self.collect_from_buff(colltype);
                         let _ = rx.response.try_send(Ok(data));
                     }
+                    Request::WaitReadable(req) => {
+                        let result = self.wait_readable(req.deadline);
+                        let _ = req.response.try_send(result);
+                    }
+                    Request::ReceiveMatch(req) => {
+                        let result = self.receive_match(&req.pattern, req.seen, req.deadline);
+                        let _ = req.response.try_send(result);
+                    }
+                    Request::Transact(req) => {
+                        let result =
+                            self.transact(req.id, req.tx_bytes, req.until, req.deadline, req.flush);
+                        let _ = req.response.try_send(result);
+                    }
+                    Request::SetRouter(req) => {
+                        self.router = req.router;
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetDirectionGpio(req) => {
+                        self.direction_gpio = req.gpio;
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetMultidrop(req) => {
+                        let result = self.set_multidrop(req.enabled, req.filter);
+                        let _ = req.response.try_send(result);
+                    }
+                    Request::TransmitAddressed(req) => {
+                        let result =
+                            self.transmit_addressed(req.address, req.tx_bytes, req.deadline);
+                        let _ = req.response.try_send(result);
+                    }
+                    Request::Schedule(req) => {
+                        let now = self.conn.clock().now();
+                        let id = self.scheduler.add(req.tx_bytes, req.interval, now);
+                        let _ = req.response.try_send(Ok(id));
+                    }
+                    Request::Unschedule(req) => {
+                        let removed = self.scheduler.remove(req.id);
+                        let _ = req.response.try_send(Ok(removed));
+                    }
+                    Request::SetScheduleGap(req) => {
+                        self.scheduler.set_gap(req.gap);
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetFrameHandler(req) => {
+                        self.frame_handler = req.handler;
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetRxTransform(req) => {
+                        self.buff.set_transform(req.transform);
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetTxTransform(req) => {
+                        self.tx_transform = req.transform;
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetRxFilter(req) => {
+                        self.buff.set_filter(req.filter);
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetReadLimits(req) => {
+                        self.buff.set_read_limits(req.chunk_size, req.budget);
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetThreadTuning(req) => {
+                        let result = req.tuning.apply();
+                        if result.is_ok() {
+                            self.tuning = req.tuning;
+                            // The pump restarts tuned with the next read of the port
+                            self.stop_rx_pump();
+                        }
+                        let _ = req.response.try_send(result);
+                    }
+                    Request::SetTrafficHistory(req) => {
+                        self.history.lock().unwrap().set_capacity(req.capacity);
+                        self.update_capture();
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetPath(req) => {
+                        let result = self.retarget(&req.path, req.rx);
+                        let _ = req.response.try_send(result);
+                    }
+                    Request::Shutdown(req) => self.shutdown.push(req.response),
+                    Request::SetIdleLineTimeout(req) => {
+                        let now = self.conn.clock().now();
+                        self.idle_line = req.timeout.map(|timeout| IdleLine::new(timeout, now));
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetEmptyTransmit(req) => {
+                        self.empty_transmit = req.policy;
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetRxPump(req) => {
+                        // The pump starts with the next read of the port
+                        self.rx_pump_enabled = req.enabled;
+                        if !req.enabled {
+                            self.stop_rx_pump();
+                        }
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetCarrierWatch(req) => {
+                        self.carrier = req.watch.map(CarrierState::new);
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetPaused(req) => {
+                        if self.paused != req.paused {
+                            log::info!(paused = req.paused; "Port paused: {}", req.paused);
+                        }
+                        self.paused = req.paused;
+                        if req.paused {
+                            self.stop_rx_pump();
+                        }
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetSessionLog(req) => {
+                        self.session_log = req.log;
+                        self.update_capture();
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::SetDuplicateWindow(req) => {
+                        self.duplicates = req.window.map(DuplicateFilter::new);
+                        let _ = req.response.try_send(Ok(()));
+                    }
+                    Request::Park(req) => {
+                        self.park(req);
+                    }
+                    Request::Lease(req) => {
+                        self.lease(req);
+                    }
+                    Request::EndLease(req) => {
+                        log::info!("Fd lease ended");
+                        self.paused = false;
+                        let result = self.clear_rx();
+                        let _ = req.response.try_send(result);
+                    }
+                    Request::Route(req) => {
+                        let transmitted = match req.tx_bytes {
+                            _ if self.router.is_none() => Err(io::Error::new(
+                                ErrorKind::InvalidInput,
+                                "No router installed",
+                            )),
+                            Some(tx_bytes) => self.transmit_to_port(tx_bytes, req.deadline),
+                            None => Ok(()),
+                        };
+                        // Either way the frames waited for by the others are routed below
+                        if let Err(err) = transmitted {
+                            let _ = req.response.try_send(Err(err));
+                        } else if let Some(router) = &mut self.router {
+                            router.wait(req.key, req.deadline, req.response);
+                        }
+                    }
                 },
             };
+            self.set_trace(None);
+            self.route_frames();
+            self.send_scheduled();
+            self.account_rx();
+        }
+    }
+
+    /// Transmit the scheduled frames which are due.
+    fn send_scheduled(&mut self) {
+        if self.paused {
+            return;
+        }
+        let clock = self.conn.clock();
+        while let Some(frame) = self.scheduler.next_due(clock.now(), self.last_tx) {
+            // The schedule follows the clock, the port is written in real time
+            let deadline = Instant::now() + SCHEDULED_TX_TIMEOUT;
+            if let Err(err) = self.transmit_to_port(frame, deadline) {
+                log::debug!("Scheduled transmission failed: {err}");
+                break;
+            }
+        }
+    }
+
+    /// Record the bytes received from the port in the link meter.
+    fn account_rx(&mut self) {
+        let count = self.buff.take_unaccounted();
+        self.watch_idle_line(count);
+        self.meter.lock().unwrap().record_rx(count);
+        #[cfg(feature = "metrics")]
+        self.metrics.rx_bytes.increment(count as u64);
+        let data = self.buff.take_captured();
+        if self.session_log.is_some() {
+            self.log_traffic(Direction::Rx, &data);
+        }
+        self.history.lock().unwrap().record(Direction::Rx, &data);
+        self.check_baud(&data);
+    }
+
+    /// Report the line going quiet and the traffic resuming.
+    fn watch_idle_line(&mut self, received: usize) {
+        let Some(idle_line) = &mut self.idle_line else {
+            return;
+        };
+        let now = self.conn.clock().now();
+        if received > 0 {
+            if let Some(quiet) = idle_line.received(now) {
+                self.conn.events.emit(Event::TrafficResumed(quiet));
+            }
+        } else if self.conn.is_open() && idle_line.check(now) {
+            self.conn.events.emit(Event::LineIdle(idle_line.timeout()));
+        }
+    }
+
+    /// Keep a copy of the received data while anybody needs it.
+    fn update_capture(&mut self) {
+        let capture = self.session_log.is_some()
+            || self.baud_check.is_some()
+            || self.history.lock().unwrap().is_enabled();
+        self.buff.set_capture(capture);
+    }
+
+    /// Look for the symptoms of a wrong baud rate in the received data.
+    fn check_baud(&mut self, data: &[u8]) {
+        let Some(check) = &mut self.baud_check else {
+            return;
+        };
+        match check.observe(data) {
+            Verdict::Pending => return,
+            Verdict::Fine => {}
+            Verdict::Mismatch(mismatch) => {
+                log::warn!("Likely baud rate mismatch: {mismatch}");
+                self.conn.set_baud_mismatch(mismatch.clone());
+                self.conn.events.emit(Event::LikelyBaudMismatch(mismatch));
+            }
+        }
+        self.baud_check = None;
+        self.update_capture();
+    }
+
+    /// Follow the request being served in its trace.
+    fn set_trace(&mut self, trace: Option<Arc<RequestTrace>>) {
+        self.buff.set_trace(trace.clone());
+        self.trace = trace;
+    }
+
+    /// Record the bytes transmitted to the port.
+    fn record_tx(&mut self, data: &[u8]) {
+        if let Some(trace) = &self.trace {
+            trace.transmitted(data.len());
+        }
+        self.last_tx = Some(self.conn.clock().now());
+        self.meter.lock().unwrap().record_tx(data.len());
+        #[cfg(feature = "metrics")]
+        self.metrics.tx_bytes.increment(data.len() as u64);
+        self.history.lock().unwrap().record(Direction::Tx, data);
+        self.log_traffic(Direction::Tx, data);
+    }
+
+    /// Write the traffic to the session log, if enabled.
+    fn log_traffic(&mut self, direction: Direction, data: &[u8]) {
+        if let Some(session_log) = &mut self.session_log {
+            let tag = match direction {
+                Direction::Tx => self.tag.as_deref(),
+                Direction::Rx => None,
+            };
+            if let Err(err) = session_log.write_tagged(direction, tag, data) {
+                log::warn!("Disabling the session log after an error: {err}");
+                self.session_log = None;
+                self.update_capture();
+            }
+        }
+    }
+
+    /// Close the connection after an I/O error, so the port gets reopened.
+    fn port_failed(&mut self, err: &io::Error) {
+        #[cfg(feature = "metrics")]
+        self.metrics.errors.increment(1);
+        let reason = match &self.tag {
+            Some(tag) => format!("{err} (request {tag})"),
+            None => err.to_string(),
+        };
+        if is_revoked(err) {
+            self.conn.revoke(&reason);
+        } else {
+            self.conn.fail(&reason);
+        }
+    }
+
+    /// Returns true if a request is waiting to be served, taking it from
+    /// the channel if it is blocked on sending it.
+    fn has_pending(&mut self) -> bool {
+        if self.preempted.is_none() {
+            self.preempted = self.chan.try_recv().ok();
+        }
+        self.preempted.is_some()
+    }
+
+    /// Finish the shutdown. Whatever was not served is failed as the worker
+    /// goes away and drops the requests.
+    fn stop(&mut self) {
+        self.stop_rx_pump();
+        self.conn.close();
+        log::info!("Arbiter shut down");
+        for response in self.shutdown.drain(..) {
+            let _ = response.try_send(());
+        }
+    }
+
+    /// Give the closed port the connect timeout to reopen before serving
+    /// a request which needs it.
+    fn await_port(
+        &mut self,
+        tag: Option<Arc<str>>,
+        trace: Arc<RequestTrace>,
+        request: Request,
+    ) -> Request {
+        self.tag = tag;
+        trace.start();
+        self.set_trace(Some(trace));
+        if let Some(timeout) = self.conn.connect_timeout() {
+            if request.needs_port() && !self.paused && !self.conn.is_open() {
+                // The request gets the error when it opens the port itself
+                let deadline = self.conn.clock().now() + timeout;
+                let _ = self.conn.connect_until(false, deadline);
+            }
+        }
+        request
+    }
+
+    /// Close the port and switch to the one at the path, handling what was
+    /// received from the old port according to the policy.
+    fn retarget(&mut self, path: &Path, rx: ReconnectRx) -> io::Result<()> {
+        if self.paused {
+            return Err(ArbiterError::Paused.into());
+        }
+        // Collect what the old port has received so far
+        if self.conn.is_open() {
+            let _ = self.receive_from_port(None, None);
+        }
+        self.stop_rx_pump();
+        match rx {
+            ReconnectRx::Keep => {}
+            ReconnectRx::Discard => {
+                self.buff.clear();
+                self.stale.clear();
+            }
+            ReconnectRx::Separate => {
+                if !self.buff.is_empty() {
+                    self.stale.push_back(self.buff.take_all());
+                }
+            }
+        }
+        self.conn.close();
+        self.conn.set_path(path);
+        // The policy for reconnects does not apply to the new port
+        self.buff.set_generation(0);
+        Ok(())
+    }
+
+    /// Check or reopen the port after the system was suspended, as the
+    /// device may have been reset or re-enumerated meanwhile.
+    fn resumed(&mut self, suspended: Duration) {
+        log::info!("System resumed after {suspended:?} of suspend");
+        self.conn.events.emit(Event::Resumed(suspended));
+        if self.conn.reopen_on_resume() {
+            self.conn.fail(&"The system was suspended");
+        } else if self.conn.check_device() {
+            self.check_carrier();
+        }
+    }
+
+    /// Hang up or dial according to the carrier of the open port.
+    fn check_carrier(&mut self) {
+        if self.carrier.is_none() {
+            return;
+        }
+        let Ok(file_mutex) = self.open_port() else {
+            return;
+        };
+        let up = port_carrier(file_mutex.lock().unwrap().as_raw_fd());
+        let Some(carrier) = &mut self.carrier else {
+            return;
+        };
+        let action = match up {
+            Ok(up) => carrier.update(self.conn.generation(), up),
+            Err(err) => {
+                // E.g. a pseudo terminal has no modem lines
+                log::warn!("Cannot read the carrier, not watching it: {err}");
+                self.carrier = None;
+                return;
+            }
+        };
+        let deadline = Instant::now() + SCHEDULED_TX_TIMEOUT;
+        match action {
+            CarrierAction::None => {}
+            CarrierAction::Dial(dial) => {
+                log::info!("Dialing");
+                let _ = self.transmit_to_port(dial, deadline);
+            }
+            CarrierAction::Hangup(hangup, reason) => {
+                if let Some(hangup) = hangup {
+                    let _ = self.transmit_to_port(hangup, deadline);
+                }
+                self.port_failed(&io::Error::new(ErrorKind::NotConnected, reason));
+            }
+        }
+    }
+
+    /// Deliver complete frames to the requests waiting for them.
+    fn route_frames(&mut self) {
+        if let Some(router) = &mut self.router {
+            router.dispatch(&mut self.buff);
+            router.expire(Instant::now());
+        }
+        if let Some(handler) = &mut self.frame_handler {
+            handler.dispatch(&mut self.buff);
+        }
+    }
+
+    fn set_multidrop(&mut self, enabled: bool, filter: Option<u8>) -> io::Result<()> {
+        self.multidrop = enabled;
+        self.conn.set_multidrop(enabled);
+        self.buff
+            .set_multidrop(enabled.then(|| MultidropDecoder::new(filter)));
+        if self.conn.is_open() {
+            let file_mutex = self.open_port()?;
+            let file = file_mutex.lock().unwrap();
+            port_set_multidrop(&file, enabled)?;
+        }
+        Ok(())
+    }
+
+    fn transmit_addressed(
+        &mut self,
+        address: u8,
+        data: Arc<[u8]>,
+        deadline: Instant,
+    ) -> io::Result<()> {
+        if !self.multidrop {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Multidrop mode is not enabled",
+            ));
+        }
+        let data = match self.tx_transformed(&[&data]) {
+            Some(frame) => frame.into(),
+            None => data,
+        };
+        let limit = self.conn.tx_queue_limit();
+        let file_mutex = self.open_port()?;
+        let mut file = file_mutex.lock().unwrap();
+        // The incoming data is collected while transmitting, unless the Rx pump does it
+        let mut buff = self.rx_pump.is_none().then_some(&mut self.buff);
+        // Each change of the 9th bit waits until the queued bytes are transmitted
+        let result = port_set_9th_bit(&file, true)
+            .and_then(|_| port_send(&mut file, &[address], buff.as_deref_mut(), limit, deadline))
+            .and_then(|_| port_set_9th_bit(&file, false))
+            .and_then(|_| port_send(&mut file, &data, buff, limit, deadline));
+        if let Err(err) = &result {
+            self.port_failed(err);
+        } else {
+            self.record_tx(&[address]);
+            self.record_tx(&data);
+        }
+        result
+    }
+
+    /// Hand out the file descriptor and stay away from the port until released.
+    fn park(&mut self, req: Park) {
+        let file_mutex = match self.open_port() {
+            Ok(file_mutex) => file_mutex,
+            Err(err) => {
+                let _ = req.response.try_send(Err(err));
+                return;
+            }
+        };
+        self.stop_rx_pump();
+        let file = file_mutex.lock().unwrap();
+        if req.response.try_send(Ok(file.as_raw_fd())).is_ok() {
+            // Wait until the holder of the fd drops the release channel
+            let _ = req.release.recv();
+        }
+    }
+
+    /// Hand out the open port and leave it alone until the lease ends.
+    fn lease(&mut self, req: Lease) {
+        if !self.paused && !self.conn.is_open() {
+            // The error of the last attempt is reported by open_port below
+            let _ = self.conn.connect_until(false, req.deadline);
+        }
+        let result = self.open_port();
+        let leased = result.is_ok();
+        self.stop_rx_pump();
+        if req.response.try_send(result).is_ok() && leased {
+            log::info!("Fd leased");
+            self.paused = true;
+        }
+    }
+
+    fn wait_readable(&mut self, deadline: Instant) -> io::Result<bool> {
+        if self.buff.is_empty() {
+            let file_mutex = self.rx_port()?;
+            let mut file = file_mutex.lock().unwrap();
+            let result = port_recv_len(&mut file, &mut self.buff, 1, deadline);
+            if let Err(err) = &result {
+                self.port_failed(err);
+            }
+            result?;
+        }
+        Ok(!self.buff.is_empty())
+    }
+
+    /// Wait until the pattern is received or until data beyond what was
+    /// seen already arrives.
+    fn receive_match(
+        &mut self,
+        pattern: &[u8],
+        seen: usize,
+        deadline: Instant,
+    ) -> io::Result<MatchProgress> {
+        loop {
+            if let Some(end) = self.buff.find(pattern) {
+                return Ok(MatchProgress::Matched(self.buff.take(end).data));
+            }
+            if self.buff.len() > seen {
+                return Ok(MatchProgress::Partial(self.buff.peek(seen)));
+            }
+            if Instant::now() >= deadline {
+                return Err(ErrorKind::TimedOut.into());
+            }
+            let file_mutex = self.rx_port()?;
+            let mut file = file_mutex.lock().unwrap();
+            let result = port_recv_len(&mut file, &mut self.buff, seen + 1, deadline);
+            if let Err(err) = &result {
+                self.port_failed(err);
+            }
+            result?;
+        }
+    }
+
+    /// Drop all data received so far, including what is waiting at the port.
+    fn clear_rx(&mut self) -> io::Result<()> {
+        let result = if self.conn.is_open() && !self.paused {
+            self.receive_from_port(None, None)
+        } else {
+            Ok(())
+        };
+        self.buff.clear();
+        self.stale.clear();
+        result
+    }
+
+    /// Returns the response to an earlier attempt of the transaction if the
+    /// duplicate suppression is enabled and the frame need not be
+    /// transmitted again.
+    fn duplicate_response(&mut self, id: u64, tx_bytes: &Arc<[u8]>, until: u8) -> Option<Vec<u8>> {
+        let duplicates = self.duplicates.as_mut()?;
+        let now = self.conn.clock().now();
+        if let Some(data) = duplicates.response(now, id, tx_bytes) {
+            return Some(data);
+        }
+        if !duplicates.is_pending(now, id, tx_bytes) {
+            return None;
+        }
+        // The response to the earlier transmission may have arrived late
+        if self.conn.is_open() {
+            let _ = self.receive_from_port(None, None);
+        }
+        let data = self
+            .collect_from_buff(CollectKind::UntilOrNothing(until))?
+            .data;
+        if let Some(duplicates) = &mut self.duplicates {
+            duplicates.answered(self.conn.clock().now(), id, tx_bytes.clone(), data.clone());
+        }
+        Some(data)
+    }
+
+    fn transact(
+        &mut self,
+        id: u64,
+        tx_bytes: Arc<[u8]>,
+        until: u8,
+        deadline: Instant,
+        flush: bool,
+    ) -> io::Result<Vec<u8>> {
+        if let Some(data) = self.duplicate_response(id, &tx_bytes, until) {
+            return Ok(data);
+        }
+        if flush {
+            self.clear_rx()?;
+        }
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        self.transmit_to_port(tx_bytes.clone(), deadline)?;
+        if let Some(duplicates) = &mut self.duplicates {
+            duplicates.sent(self.conn.clock().now(), id, tx_bytes.clone());
+        }
+        self.receive_from_port(Some(until), Some(deadline))?;
+        match self.collect_from_buff(CollectKind::UntilOrNothing(until)) {
+            Some(data) => {
+                if let Some(duplicates) = &mut self.duplicates {
+                    duplicates.answered(self.conn.clock().now(), id, tx_bytes, data.data.clone());
+                }
+                #[cfg(feature = "metrics")]
+                self.metrics.transact_latency.record(start.elapsed());
+                Ok(data.data)
+            }
+            None => Err(ErrorKind::TimedOut.into()),
+        }
+    }
+
+    /// Open the port if necessary, applying the reconnect policy to the
+    /// received data if the port has been reopened.
+    fn open_port(&mut self) -> io::Result<Arc<Mutex<File>>> {
+        if self.paused {
+            return Err(ArbiterError::Paused.into());
+        }
+        let file = self.conn.open()?;
+        if let Some(trace) = &self.trace {
+            trace.port_opened();
+        }
+        let generation = self.conn.generation();
+        if generation != self.buff.generation() {
+            // What the pump read from the previous port belongs to its generation
+            self.stop_rx_pump();
+            #[cfg(feature = "metrics")]
+            {
+                self.metrics = PortMetrics::new(self.conn.path_label());
+                if self.buff.generation() != 0 {
+                    self.metrics.reconnects.increment(1);
+                }
+            }
+            if self.buff.generation() != 0 && !self.buff.is_empty() {
+                match self.conn.reconnect_rx() {
+                    ReconnectRx::Keep => {}
+                    ReconnectRx::Discard => self.buff.clear(),
+                    ReconnectRx::Separate => self.stale.push_back(self.buff.take_all()),
+                }
+            }
+            self.buff.set_generation(generation);
+            // The parity errors marked in the multidrop mode look like garbage
+            if !self.multidrop {
+                self.baud_check = Some(BaudCheck::new(&file));
+                self.update_capture();
+            }
+        }
+        Ok(file)
+    }
+
+    /// The port to read the incoming data from, which is the pipe of the Rx
+    /// pump if it is enabled.
+    fn rx_port(&mut self) -> io::Result<Arc<Mutex<File>>> {
+        let file = self.open_port()?;
+        if !self.rx_pump_enabled {
+            return Ok(file);
+        }
+        let pump = match self.rx_pump.take() {
+            Some(pump) => pump,
+            None => RxPump::start(&file, &self.tuning)?,
+        };
+        let pipe = pump.pipe();
+        self.rx_pump = Some(pump);
+        Ok(pipe)
+    }
+
+    /// Stop reading the port on the dedicated thread, collecting what it has read.
+    fn stop_rx_pump(&mut self) {
+        if let Some(pump) = self.rx_pump.take() {
+            pump.stop(&mut self.buff);
         }
     }
 
@@ -252,41 +2810,169 @@ impl WorkerThread {
         until: Option<u8>,
         deadline: Option<Instant>,
     ) -> io::Result<()> {
-        let file_mutex = self.conn.open()?;
+        let file_mutex = self.rx_port()?;
         let mut file = file_mutex.lock().unwrap();
         let result = port_recv(&mut file, &mut self.buff, until, deadline);
-        if result.is_err() {
-            self.conn.close();
+        if let Err(err) = &result {
+            self.port_failed(err);
+        }
+        result
+    }
+
+    /// Collect the incoming data in the background, giving way to the next
+    /// request as soon as it arrives.
+    fn collect_from_port(&mut self) -> io::Result<()> {
+        let file_mutex = self.rx_port()?;
+        let mut file = file_mutex.lock().unwrap();
+        let (chan, preempted) = (&self.chan, &mut self.preempted);
+        let result = port_collect(&mut file, &mut self.buff, || {
+            if preempted.is_none() {
+                *preempted = chan.try_recv().ok();
+            }
+            preempted.is_some()
+        });
+        if let Err(err) = &result {
+            self.port_failed(err);
         }
         result
     }
 
     fn transmit_to_port(&mut self, data: Arc<[u8]>, deadline: Instant) -> io::Result<()> {
-        let file_mutex = self.conn.open()?;
+        self.transmit_parts_to_port(&[&data], deadline)
+    }
+
+    /// Transmit the parts back to back as one frame, transformed by the
+    /// Tx transform if there is one.
+    fn transmit_parts_to_port(&mut self, parts: &[&[u8]], deadline: Instant) -> io::Result<()> {
+        match self.tx_transformed(parts) {
+            Some(frame) => self.write_parts_to_port(&[&frame], deadline),
+            None => self.write_parts_to_port(parts, deadline),
+        }
+    }
+
+    /// Returns the frame made of the parts transformed by the Tx transform,
+    /// or None if there is no transform.
+    fn tx_transformed(&mut self, parts: &[&[u8]]) -> Option<Vec<u8>> {
+        let transform = self.tx_transform.as_mut()?;
+        let mut frame = Vec::new();
+        transform(&parts.concat(), &mut frame);
+        Some(frame)
+    }
+
+    /// Serve a transmission with nothing to transmit, see [`EmptyTransmit`].
+    /// A flush asked for explicitly is left to the caller.
+    fn transmit_nothing(&mut self, flush: bool, deadline: Instant) -> io::Result<()> {
+        match self.empty_transmit {
+            _ if flush => Ok(()),
+            EmptyTransmit::Skip => Ok(()),
+            EmptyTransmit::Flush => self.flush_port(deadline),
+            EmptyTransmit::Reject => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Nothing to transmit",
+            )),
+        }
+    }
+
+    /// Wait until the transmitted data has left the port.
+    fn flush_port(&mut self, deadline: Instant) -> io::Result<()> {
+        let file_mutex = self.open_port()?;
+        let mut file = file_mutex.lock().unwrap();
+        let buff = self.rx_pump.is_none().then_some(&mut self.buff);
+        let result = port_flush(&mut file, buff, deadline);
+        if let Err(err) = &result {
+            self.port_failed(err);
+        }
+        result
+    }
+
+    /// Write the parts back to back as one frame.
+    fn write_parts_to_port(&mut self, parts: &[&[u8]], deadline: Instant) -> io::Result<()> {
+        let file_mutex = self.open_port()?;
         let mut file = file_mutex.lock().unwrap();
-        let result = port_send(&mut file, &data, &mut self.buff, deadline);
-        if result.is_err() {
-            self.conn.close();
+        // The incoming data is collected while transmitting, unless the Rx pump does it
+        let buff = self.rx_pump.is_none().then_some(&mut self.buff);
+        let Some(gpio) = &mut self.direction_gpio else {
+            let result = send_parts(&mut file, parts, buff, &self.conn, deadline);
+            if let Err(err) = &result {
+                self.port_failed(err);
+            } else {
+                parts.iter().for_each(|part| self.record_tx(part));
+            }
+            return result;
+        };
+
+        // Enable the RS-485 transmitter only for the time of the transmission
+        let result = gpio.set(true).and_then(|_| {
+            let sent = send_parts(&mut file, parts, buff, &self.conn, deadline)
+                .and_then(|_| port_drain(&file));
+            // Release the line even if the transmission failed
+            let released = gpio.set(false);
+            sent.and(released)
+        });
+        if let Err(err) = &result {
+            self.port_failed(err);
+        } else {
+            parts.iter().for_each(|part| self.record_tx(part));
         }
         result
     }
 
+    fn transmit_echoed(&mut self, data: Arc<[u8]>, deadline: Instant) -> io::Result<()> {
+        // Collect pending data first so it is not mistaken for the echo
+        self.receive_from_port(None, None)?;
+        let start = self.buff.len();
+        let data = match self.tx_transformed(&[&data]) {
+            Some(frame) => frame.into(),
+            None => data,
+        };
+        self.write_parts_to_port(&[&data], deadline)?;
+
+        // Wait for the whole echo
+        let file_mutex = self.rx_port()?;
+        let mut file = file_mutex.lock().unwrap();
+        let result = port_recv_len(&mut file, &mut self.buff, start + data.len(), deadline);
+        if let Err(err) = &result {
+            self.port_failed(err);
+        }
+        result?;
+
+        // Consume and verify the echo
+        let end = self.buff.len().min(start + data.len());
+        let echo = self.buff.remove(start..end);
+        let mismatch = data
+            .iter()
+            .zip(&echo)
+            .position(|(sent, echoed)| sent != echoed);
+        if let Some(position) = mismatch {
+            let err = ArbiterError::EchoMismatch {
+                position,
+                sent: data[position],
+                echoed: echo[position],
+            };
+            return Err(err.into());
+        }
+        if echo.len() < data.len() {
+            return Err(ErrorKind::TimedOut.into());
+        }
+        Ok(())
+    }
+
     /// Collect data from the RX FIFO buffer.
-    fn collect_from_buff(&mut self, collect: CollectKind) -> Option<Vec<u8>> {
+    fn collect_from_buff(&mut self, collect: CollectKind) -> Option<Timestamped> {
         if self.buff.is_empty() {
             return None;
         }
         match collect {
             CollectKind::Everything => self.collect_from_buff_everything(),
             CollectKind::UntilOrEverything(delimiter) => {
-                if let Some(pos) = self.buff.iter().position(|x| x == &delimiter) {
+                if let Some(pos) = self.buff.position(delimiter) {
                     self.collect_from_buff_count(pos + 1)
                 } else {
                     self.collect_from_buff_everything()
                 }
             }
             CollectKind::UntilOrNothing(delimiter) => {
-                if let Some(pos) = self.buff.iter().position(|x| x == &delimiter) {
+                if let Some(pos) = self.buff.position(delimiter) {
                     self.collect_from_buff_count(pos + 1)
                 } else {
                     None
@@ -296,7 +2982,7 @@ impl WorkerThread {
     }
 
     /// Collect the given count of elements from the RX FIFO buffer
-    fn collect_from_buff_count(&mut self, count: usize) -> Option<Vec<u8>> {
+    fn collect_from_buff_count(&mut self, count: usize) -> Option<Timestamped> {
         if self.buff.is_empty() {
             // Return nothing
             return None;
@@ -305,19 +2991,15 @@ impl WorkerThread {
             return self.collect_from_buff_everything();
         }
         // Return part of the buffer
-        let mut data = self.buff.split_off(count);
-        mem::swap(&mut self.buff, &mut data);
-        Some(data.into())
+        Some(self.buff.take(count))
     }
 
     /// Collect all data from the RX FIFO buffer
-    fn collect_from_buff_everything(&mut self) -> Option<Vec<u8>> {
+    fn collect_from_buff_everything(&mut self) -> Option<Timestamped> {
         if self.buff.is_empty() {
             return None;
         }
-        let mut data = VecDeque::new();
-        mem::swap(&mut self.buff, &mut data);
-        Some(data.into())
+        Some(self.buff.take_all())
     }
 }
 
@@ -331,3 +3013,218 @@ enum CollectKind {
     /// If the byte is not found then do not consume any data from the buffer.
     UntilOrNothing(u8),
 }
+
+/// Transmissions larger than this are sent in chunks of this size,
+/// with a progress event emitted after each chunk.
+const PROGRESS_CHUNK: usize = 1024;
+
+/// Send the parts one after another as one frame, emitting progress events
+/// over the whole frame if it is large and somebody listens for them.
+fn send_parts(
+    file: &mut File,
+    parts: &[&[u8]],
+    mut buff: Option<&mut RxBuffer>,
+    conn: &Connection,
+    deadline: Instant,
+) -> io::Result<()> {
+    let limit = conn.tx_queue_limit();
+    let total: usize = parts.iter().map(|part| part.len()).sum();
+    let report = total > PROGRESS_CHUNK && conn.events.is_observed();
+    let start = Instant::now();
+    let mut sent = 0;
+    for part in parts {
+        if !report {
+            port_send(file, part, buff.as_deref_mut(), limit, deadline)?;
+            continue;
+        }
+        for chunk in part.chunks(PROGRESS_CHUNK) {
+            port_send(file, chunk, buff.as_deref_mut(), limit, deadline)?;
+            sent += chunk.len();
+            let progress = Progress::new(sent, total, start);
+            conn.events.emit(Event::Progress(progress));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read, iter};
+
+    use nix::{pty::openpty, unistd::ttyname};
+
+    use super::*;
+
+    /// An arbiter open on a pty, with the other end of the pty.
+    pub fn pty_arbiter() -> (Arbiter, File) {
+        let arbiter = Arbiter::new();
+        let device = open_pty(&arbiter);
+        (arbiter, device)
+    }
+
+    /// Opens the arbiter on a pty, returning the other end of the pty.
+    pub fn open_pty(arbiter: &Arbiter) -> File {
+        let pty = openpty(None, None).unwrap();
+        arbiter.open(ttyname(&pty.slave).unwrap()).unwrap();
+        File::from(pty.master)
+    }
+
+    /// Keeps the worker busy in a closure of [`Arbiter::with_raw_fd`] on
+    /// another thread until the returned sender is dropped.
+    pub fn hold_worker(arbiter: &Arbiter) -> Sender<()> {
+        let (held, held_ch) = bounded(0);
+        let (release, released) = bounded::<()>(0);
+        let arbiter = arbiter.clone();
+        thread::spawn(move || {
+            arbiter.with_raw_fd(|_| {
+                held.send(()).unwrap();
+                let _ = released.recv();
+            })
+        });
+        held_ch.recv().unwrap();
+        release
+    }
+
+    /// The arbiter specific failure of the result, which must be an error.
+    pub fn arbiter_error<T: std::fmt::Debug>(result: io::Result<T>) -> Option<ArbiterError> {
+        ArbiterError::of(&result.unwrap_err()).cloned()
+    }
+
+    #[test]
+    fn requests_from_the_raw_fd_closure_are_reentrant() {
+        let (arbiter, mut device) = pty_arbiter();
+        let clone = arbiter.clone();
+        let deadline = || Instant::now() + Duration::from_secs(1);
+        let (nested, transmitted) = arbiter
+            .with_raw_fd(|_| {
+                let nested = clone.with_raw_fd(|_| ());
+                (nested, clone.transmit(b"A"[..].into(), deadline()))
+            })
+            .unwrap();
+        assert_eq!(arbiter_error(nested), Some(ArbiterError::Reentrant));
+        assert_eq!(arbiter_error(transmitted), Some(ArbiterError::Reentrant));
+
+        // The worker is released with the closure
+        arbiter.transmit(b"B"[..].into(), deadline()).unwrap();
+        let mut received = [0; 1];
+        device.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"B");
+    }
+
+    #[test]
+    fn termios_hook_may_call_back_into_the_arbiter() {
+        let pty = openpty(None, None).unwrap();
+        let arbiter = Arbiter::new();
+        let handle = arbiter.clone();
+        let (seen, seen_ch) = bounded(1);
+        arbiter.set_termios_hook(move |_| {
+            handle.set_clocal(Some(true));
+            let _ = seen.try_send((handle.is_open(), handle.generation()));
+        });
+        arbiter.open(ttyname(&pty.slave).unwrap()).unwrap();
+        assert_eq!(seen_ch.try_recv().unwrap(), (false, 0));
+        assert!(arbiter.is_open());
+        // The hook holds a handle
+        arbiter.remove_termios_hook();
+    }
+
+    #[test]
+    fn watchdog_closes_the_port_stuck_for_the_window() {
+        let clock = Arc::new(ManualClock::new());
+        let arbiter = Arbiter::builder()
+            .with_clock(clock.clone())
+            .build()
+            .unwrap();
+        let _device = open_pty(&arbiter);
+        arbiter.set_watchdog(Some(Duration::from_secs(10)));
+        let events = arbiter.events();
+        let deadline = || Instant::now() + Duration::from_millis(10);
+        // Nobody answers on the other end of the pty
+        let transact = || arbiter.transact(b"AT\r"[..].into(), b'\n', deadline());
+
+        assert_eq!(transact().unwrap_err().kind(), ErrorKind::TimedOut);
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(transact().unwrap_err().kind(), ErrorKind::TimedOut);
+        assert!(arbiter.is_open());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(transact().unwrap_err().kind(), ErrorKind::TimedOut);
+        let mut events = iter::from_fn(|| events.try_recv().ok());
+        let report = events.find_map(|event| match event {
+            Event::WatchdogFired(report) => Some(report),
+            _ => None,
+        });
+        let report = report.unwrap();
+        assert_eq!(report.stuck_for, Duration::from_secs(10));
+        assert_eq!(report.timeouts, 3);
+        assert!(!arbiter.is_open());
+    }
+
+    #[test]
+    fn pending_requests_fail_on_shutdown() {
+        let (arbiter, _device) = pty_arbiter();
+        let deadline = || Instant::now() + Duration::from_secs(1);
+        let release = hold_worker(&arbiter);
+        let pending = {
+            let arbiter = arbiter.clone();
+            thread::spawn(move || arbiter.transmit(b"A"[..].into(), deadline()))
+        };
+        // Either waiting for the worker by now or made after the shutdown began
+        thread::sleep(Duration::from_millis(50));
+        let stopping = {
+            let arbiter = arbiter.clone();
+            thread::spawn(move || arbiter.shutdown(ShutdownPolicy::FailPending))
+        };
+        drop(release);
+        stopping.join().unwrap();
+
+        let pending = pending.join().unwrap();
+        assert_eq!(arbiter_error(pending), Some(ArbiterError::ShuttingDown));
+        let later = arbiter.transmit(b"B"[..].into(), deadline());
+        assert_eq!(arbiter_error(later), Some(ArbiterError::ShuttingDown));
+    }
+
+    #[test]
+    fn request_times_out_waiting_for_a_busy_worker() {
+        let (arbiter, mut device) = pty_arbiter();
+        let deadline = || Instant::now() + Duration::from_secs(1);
+        let impatient = arbiter.with_enqueue_timeout(Duration::from_millis(50));
+        let release = hold_worker(&arbiter);
+
+        let start = Instant::now();
+        let result = impatient.transmit(b"A"[..].into(), deadline());
+        let Some(ArbiterError::EnqueueTimeout { waited }) = arbiter_error(result) else {
+            panic!("Not timed out waiting for the worker");
+        };
+        assert!(waited >= Duration::from_millis(50) && waited <= start.elapsed());
+
+        // Never handed over, so only the next one is transmitted
+        drop(release);
+        arbiter.transmit(b"B"[..].into(), deadline()).unwrap();
+        let mut received = [0; 1];
+        device.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"B");
+    }
+
+    #[test]
+    fn requests_from_the_worker_callbacks_are_reentrant() {
+        let (arbiter, mut device) = pty_arbiter();
+        let deadline = || Instant::now() + Duration::from_secs(1);
+        let handle = arbiter.clone();
+        let (nested, nested_ch) = bounded(1);
+        arbiter
+            .set_tx_transform(move |frame, output| {
+                output.extend_from_slice(frame);
+                let _ = nested.try_send(handle.transmit(b"B"[..].into(), deadline()));
+            })
+            .unwrap();
+
+        arbiter.transmit(b"A"[..].into(), deadline()).unwrap();
+        let nested = nested_ch.try_recv().unwrap();
+        assert_eq!(arbiter_error(nested), Some(ArbiterError::Reentrant));
+        // The transform holds a handle
+        arbiter.remove_tx_transform().unwrap();
+        let mut received = [0; 1];
+        device.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"A");
+    }
+}
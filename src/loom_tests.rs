@@ -0,0 +1,128 @@
+//! Models of the handover of the requests to the worker and of their
+//! responses back, run under loom:
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg loom" cargo test --lib --release loom
+//! ```
+//!
+//! The worker does file I/O which loom cannot model, so it is stood in for
+//! by a thread answering the requests the way the worker does, with a
+//! `try_send` on the response channel. The callers run the real code.
+
+use std::{
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use loom::thread;
+
+use crate::{
+    channel::{bounded, Receiver},
+    connection::Connection,
+    history::TrafficHistory,
+    meter::LinkMeter,
+    sync::Mutex,
+    usage::UsageRegistry,
+    Arbiter, ArbiterError, Envelope, Request,
+};
+
+/// An arbiter whose requests are left to the caller to serve.
+fn arbiter(enqueue_timeout: Option<Duration>) -> (Arbiter, Receiver<Envelope>) {
+    let (chan, requests) = bounded::<Envelope>(0);
+    let clients = Arc::new(Mutex::new(UsageRegistry::default()));
+    let usage = clients.lock().unwrap().register(None);
+    let arbiter = Arbiter {
+        conn: Arc::new(Connection::new()),
+        chan,
+        meter: Arc::new(Mutex::new(LinkMeter::new())),
+        history: Arc::new(Mutex::new(TrafficHistory::default())),
+        clients,
+        usage,
+        tag: None,
+        enqueue_timeout,
+    };
+    (arbiter, requests)
+}
+
+/// Answers the given number of transactions with their frame, returning
+/// their ids.
+fn serve(requests: Receiver<Envelope>, count: usize) -> Vec<u64> {
+    let mut ids = Vec::new();
+    for _ in 0..count {
+        let Ok(envelope) = requests.recv() else {
+            break;
+        };
+        envelope.trace.start();
+        let Request::Transact(req) = envelope.request else {
+            panic!("Unexpected request");
+        };
+        ids.push(req.id);
+        let _ = req.response.try_send(Ok(req.tx_bytes.to_vec()));
+    }
+    ids
+}
+
+/// Explores the interleavings with up to two preemptions, which catches
+/// most races at a fraction of the time of an exhaustive check.
+fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = loom::model::Builder::new();
+    builder.preemption_bound.get_or_insert(2);
+    builder.check(f);
+}
+
+fn transact(arbiter: &Arbiter, frame: &[u8]) -> io::Result<Vec<u8>> {
+    let deadline = Instant::now() + Duration::from_secs(1);
+    arbiter.transact(frame.into(), b'\n', deadline)
+}
+
+#[test]
+fn loom_resend_after_enqueue_timeout_gets_its_response() {
+    model(|| {
+        let (arbiter, requests) = arbiter(Some(Duration::from_millis(1)));
+        let worker = thread::spawn(move || serve(requests, 1));
+        let response = loop {
+            match transact(&arbiter, b"A\n") {
+                Err(err)
+                    if matches!(
+                        ArbiterError::of(&err),
+                        Some(ArbiterError::EnqueueTimeout { .. })
+                    ) =>
+                {
+                    // The worker was busy, send it again
+                    thread::yield_now();
+                }
+                result => break result,
+            }
+        };
+        assert_eq!(response.unwrap(), b"A\n");
+        assert_eq!(worker.join().unwrap().len(), 1);
+    });
+}
+
+#[test]
+fn loom_concurrent_callers_get_their_own_responses() {
+    model(|| {
+        let (arbiter, requests) = arbiter(None);
+        let worker = thread::spawn(move || serve(requests, 2));
+        let other = arbiter.clone();
+        let caller = thread::spawn(move || transact(&other, b"B\n").unwrap());
+        assert_eq!(transact(&arbiter, b"A\n").unwrap(), b"A\n");
+        assert_eq!(caller.join().unwrap(), b"B\n");
+        let ids = worker.join().unwrap();
+        assert_ne!(ids[0], ids[1]);
+    });
+}
+
+#[test]
+fn loom_caller_is_released_when_the_worker_drops_the_request() {
+    model(|| {
+        let (arbiter, requests) = arbiter(None);
+        let worker = thread::spawn(move || {
+            // Gone without responding, e.g. panicked
+            drop(requests.recv().unwrap());
+        });
+        assert!(transact(&arbiter, b"A\n").is_err());
+        worker.join().unwrap();
+    });
+}
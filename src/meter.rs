@@ -0,0 +1,76 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_WINDOW: Duration = Duration::from_secs(1);
+
+/// Share of the theoretical throughput of the link used recently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Utilization {
+    /// Transmit direction, in percent.
+    pub tx: f64,
+    /// Receive direction, in percent.
+    pub rx: f64,
+}
+
+/// Counts the bytes moved over the link within a sliding time window.
+pub struct LinkMeter {
+    window: Duration,
+    tx: VecDeque<(Instant, usize)>,
+    rx: VecDeque<(Instant, usize)>,
+}
+
+impl LinkMeter {
+    pub fn new() -> Self {
+        Self {
+            window: DEFAULT_WINDOW,
+            tx: VecDeque::new(),
+            rx: VecDeque::new(),
+        }
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    pub fn record_tx(&mut self, count: usize) {
+        if count > 0 {
+            self.tx.push_back((Instant::now(), count));
+            self.expire();
+        }
+    }
+
+    pub fn record_rx(&mut self, count: usize) {
+        if count > 0 {
+            self.rx.push_back((Instant::now(), count));
+            self.expire();
+        }
+    }
+
+    /// Utilization of a link transferring the given number of characters per second.
+    pub fn utilization(&mut self, char_rate: f64) -> Utilization {
+        self.expire();
+        let capacity = char_rate * self.window.as_secs_f64();
+        let percent = |samples: &VecDeque<(Instant, usize)>| {
+            let bytes: usize = samples.iter().map(|(_, count)| count).sum();
+            100.0 * bytes as f64 / capacity
+        };
+        Utilization {
+            tx: percent(&self.tx),
+            rx: percent(&self.rx),
+        }
+    }
+
+    /// Drop the samples which fell out of the window.
+    fn expire(&mut self) {
+        let Some(start) = Instant::now().checked_sub(self.window) else {
+            return;
+        };
+        for samples in [&mut self.tx, &mut self.rx] {
+            while samples.front().is_some_and(|(time, _)| *time < start) {
+                samples.pop_front();
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::channel::{unbounded, TryRecvError};
+
+use crate::{Arbiter, ModemLine};
+
+/// Ctrl-A, the default escape character.
+const DEFAULT_ESCAPE: u8 = 0x01;
+
+/// How long to wait for received data before checking the input again.
+const RECEIVE_SLICE: Duration = Duration::from_millis(10);
+
+/// Interactive terminal bridging the port to an input and an output, usually
+/// stdin and stdout. Everything read from the input is transmitted and
+/// everything received is written to the output.
+///
+/// The escape character (Ctrl-A by default) followed by a command key
+/// controls the terminal:
+/// - `x` or Ctrl-X: exit
+/// - `d`: toggle DTR
+/// - `r`: toggle RTS
+/// - `b`: send a break
+/// - `e`: toggle local echo
+/// - the escape character again: transmit the escape character itself
+pub struct Miniterm {
+    arbiter: Arbiter,
+    escape: u8,
+    local_echo: bool,
+    dtr: bool,
+    rts: bool,
+}
+
+impl Miniterm {
+    pub fn new(arbiter: Arbiter) -> Self {
+        Self {
+            arbiter,
+            escape: DEFAULT_ESCAPE,
+            local_echo: false,
+            dtr: true,
+            rts: true,
+        }
+    }
+
+    /// Sets the escape character.
+    pub fn with_escape(mut self, escape: u8) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Enables writing the input to the output, for devices which do not echo.
+    pub fn with_local_echo(mut self, local_echo: bool) -> Self {
+        self.local_echo = local_echo;
+        self
+    }
+
+    /// Runs the terminal until the exit command or the end of the input.
+    /// The input is read by a separate thread, which is left blocked on
+    /// the input when the terminal exits.
+    pub fn run(
+        mut self,
+        mut input: impl Read + Send + 'static,
+        mut output: impl Write,
+    ) -> io::Result<()> {
+        let (tx, rx) = unbounded();
+        thread::spawn(move || {
+            let mut buf = [0u8; 256];
+            while let Ok(len @ 1..) = input.read(&mut buf) {
+                if tx.send(buf[..len].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut escaped = false;
+        loop {
+            let received = self
+                .arbiter
+                .receive(None, Some(Instant::now() + RECEIVE_SLICE));
+            if let Ok(Some(data)) = received {
+                output.write_all(&data)?;
+                output.flush()?;
+            }
+
+            let chunk = match rx.try_recv() {
+                Ok(chunk) => chunk,
+                Err(TryRecvError::Empty) => continue,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            };
+            let mut tx_bytes = Vec::with_capacity(chunk.len());
+            for byte in chunk {
+                if escaped {
+                    escaped = false;
+                    if byte == self.escape {
+                        tx_bytes.push(byte);
+                    } else if !self.command(byte, &mut output)? {
+                        return Ok(());
+                    }
+                } else if byte == self.escape {
+                    escaped = true;
+                } else {
+                    tx_bytes.push(byte);
+                }
+            }
+            if tx_bytes.is_empty() {
+                continue;
+            }
+            if self.local_echo {
+                output.write_all(&tx_bytes)?;
+                output.flush()?;
+            }
+            let deadline = Instant::now() + Duration::from_secs(1);
+            if let Err(err) = self.arbiter.transmit(tx_bytes.into(), deadline) {
+                self.notice(&mut output, &format!("transmit failed: {err}"))?;
+            }
+        }
+    }
+
+    /// Executes the command key. Returns false to exit.
+    fn command(&mut self, key: u8, output: &mut impl Write) -> io::Result<bool> {
+        let result = match key {
+            b'x' | b'X' | 0x18 => return Ok(false),
+            b'd' | b'D' => {
+                self.dtr = !self.dtr;
+                let result = self.arbiter.set_modem_line(ModemLine::Dtr, self.dtr);
+                result.map(|_| format!("DTR {}", on_off(self.dtr)))
+            }
+            b'r' | b'R' => {
+                self.rts = !self.rts;
+                let result = self.arbiter.set_modem_line(ModemLine::Rts, self.rts);
+                result.map(|_| format!("RTS {}", on_off(self.rts)))
+            }
+            b'b' | b'B' => self.arbiter.send_break().map(|_| "break sent".into()),
+            b'e' | b'E' => {
+                self.local_echo = !self.local_echo;
+                Ok(format!("local echo {}", on_off(self.local_echo)))
+            }
+            _ => Ok(format!("unknown command 0x{key:02X}")),
+        };
+        let message = result.unwrap_or_else(|err| err.to_string());
+        self.notice(output, &message)?;
+        Ok(true)
+    }
+
+    fn notice(&self, output: &mut impl Write, message: &str) -> io::Result<()> {
+        write!(output, "\r\n*** {message} ***\r\n")?;
+        output.flush()
+    }
+}
+
+fn on_off(state: bool) -> &'static str {
+    if state {
+        "on"
+    } else {
+        "off"
+    }
+}
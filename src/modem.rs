@@ -0,0 +1,172 @@
+use std::{
+    ffi::c_int,
+    io,
+    os::fd::RawFd,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use nix::libc;
+
+nix::ioctl_write_ptr_bad!(tiocmbis, libc::TIOCMBIS, c_int);
+nix::ioctl_write_ptr_bad!(tiocmbic, libc::TIOCMBIC, c_int);
+nix::ioctl_read_bad!(tiocmget, libc::TIOCMGET, c_int);
+
+/// Modem control line driven by the port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModemLine {
+    /// Data Terminal Ready
+    Dtr,
+    /// Request To Send
+    Rts,
+}
+
+impl ModemLine {
+    fn bits(self) -> c_int {
+        match self {
+            ModemLine::Dtr => libc::TIOCM_DTR,
+            ModemLine::Rts => libc::TIOCM_RTS,
+        }
+    }
+}
+
+/// Assert (true) or deassert (false) the modem control line.
+pub fn port_set_modem_line(fd: RawFd, line: ModemLine, level: bool) -> io::Result<()> {
+    let bits = line.bits();
+    if level {
+        unsafe { tiocmbis(fd, &bits) }?;
+    } else {
+        unsafe { tiocmbic(fd, &bits) }?;
+    }
+    Ok(())
+}
+
+/// Returns true if the Data Carrier Detect line is asserted.
+pub fn port_carrier(fd: RawFd) -> io::Result<bool> {
+    let mut bits = 0;
+    unsafe { tiocmget(fd, &mut bits) }?;
+    Ok(bits & libc::TIOCM_CAR != 0)
+}
+
+/// Returns true if the Clear To Send line is asserted.
+pub fn port_cts(fd: RawFd) -> io::Result<bool> {
+    let mut bits = 0;
+    unsafe { tiocmget(fd, &mut bits) }?;
+    Ok(bits & libc::TIOCM_CTS != 0)
+}
+
+/// Treats the loss of the Data Carrier Detect line as a disconnect, for
+/// dial-up links and radio modems where the tty stays open but the link
+/// is gone.
+///
+/// When the carrier drops the hangup bytes are transmitted (e.g. `ATH\r`)
+/// and the port is closed, emitting [`Event::Disconnected`](crate::Event).
+/// Every time the port is reopened the dial bytes are transmitted (e.g.
+/// `ATDT5551234\r`) and if the carrier does not come up within the dial
+/// timeout, the port is closed again.
+#[derive(Debug, Clone)]
+pub struct CarrierWatch {
+    hangup: Option<Arc<[u8]>>,
+    dial: Option<Arc<[u8]>>,
+    dial_timeout: Duration,
+}
+
+impl Default for CarrierWatch {
+    fn default() -> Self {
+        Self {
+            hangup: None,
+            dial: None,
+            dial_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl CarrierWatch {
+    /// Watches the carrier without hanging up or dialing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the bytes transmitted when the carrier is lost.
+    pub fn with_hangup(mut self, hangup: impl AsRef<[u8]>) -> Self {
+        self.hangup = Some(hangup.as_ref().into());
+        self
+    }
+
+    /// Sets the bytes transmitted after the port is opened and the time
+    /// given to the carrier to come up after them (60 seconds by default).
+    pub fn with_dial(mut self, dial: impl AsRef<[u8]>, timeout: Duration) -> Self {
+        self.dial = Some(dial.as_ref().into());
+        self.dial_timeout = timeout;
+        self
+    }
+}
+
+/// What the worker has to do after checking the carrier.
+pub enum CarrierAction {
+    None,
+    /// The port was reopened, transmit the dial bytes.
+    Dial(Arc<[u8]>),
+    /// The link is gone, transmit the hangup bytes and close the port.
+    Hangup(Option<Arc<[u8]>>, &'static str),
+}
+
+/// State of the carrier of the current connection.
+pub struct CarrierState {
+    watch: CarrierWatch,
+    generation: u64,
+    up: bool,
+    dialed: Option<Instant>,
+}
+
+impl CarrierState {
+    pub fn new(watch: CarrierWatch) -> Self {
+        Self {
+            watch,
+            generation: 0,
+            up: false,
+            dialed: None,
+        }
+    }
+
+    /// Update the state with the carrier of the given connection.
+    pub fn update(&mut self, generation: u64, carrier: bool) -> CarrierAction {
+        if generation != self.generation {
+            self.generation = generation;
+            self.up = false;
+            self.dialed = None;
+            if let Some(dial) = &self.watch.dial {
+                if !carrier {
+                    self.dialed = Some(Instant::now());
+                    return CarrierAction::Dial(dial.clone());
+                }
+            }
+        }
+        let hangup = self.watch.hangup.clone();
+        match (self.up, carrier) {
+            (false, true) => {
+                log::info!("Carrier detected");
+                self.up = true;
+                self.dialed = None;
+                CarrierAction::None
+            }
+            (true, false) => {
+                self.up = false;
+                CarrierAction::Hangup(hangup, "Carrier lost")
+            }
+            _ if self
+                .dialed
+                .is_some_and(|dialed| dialed.elapsed() >= self.watch.dial_timeout) =>
+            {
+                self.dialed = None;
+                CarrierAction::Hangup(hangup, "No carrier after dialing")
+            }
+            _ => CarrierAction::None,
+        }
+    }
+}
+
+/// Transmit a break, a continuous stream of zero bits for 0.25 to 0.5 seconds.
+pub fn port_send_break(fd: RawFd) -> io::Result<()> {
+    termios::tcsendbreak(fd, 0)
+}
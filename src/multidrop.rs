@@ -0,0 +1,65 @@
+/// Decodes the Rx stream of a port in 9-bit multidrop mode.
+///
+/// The port receives with space parity and marks parity errors (PARMRK), so
+/// a byte with the 9th bit set (an address byte) arrives as `FF 00 <byte>`
+/// and a data byte `FF` arrives escaped as `FF FF`. Address bytes are passed
+/// on as the first byte of the frame they start. With an address filter only
+/// frames starting with the given address are passed on.
+pub struct MultidropDecoder {
+    filter: Option<u8>,
+    state: State,
+    selected: bool,
+}
+
+enum State {
+    Data,
+    /// Got `FF`
+    Escape,
+    /// Got `FF 00`
+    Address,
+}
+
+impl MultidropDecoder {
+    pub fn new(filter: Option<u8>) -> Self {
+        Self {
+            filter,
+            state: State::Data,
+            selected: filter.is_none(),
+        }
+    }
+
+    /// Decode a chunk of the Rx stream.
+    pub fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) {
+        for &byte in input {
+            self.state = match (&self.state, byte) {
+                (State::Data, 0xFF) => State::Escape,
+                (State::Data, _) => {
+                    self.data(byte, output);
+                    State::Data
+                }
+                (State::Escape, 0x00) => State::Address,
+                (State::Escape, 0xFF) => {
+                    self.data(0xFF, output);
+                    State::Data
+                }
+                (State::Escape, _) => {
+                    // Not a marked sequence so pass it through as is
+                    self.data(0xFF, output);
+                    self.data(byte, output);
+                    State::Data
+                }
+                (State::Address, _) => {
+                    self.selected = self.filter.is_none_or(|own| own == byte);
+                    self.data(byte, output);
+                    State::Data
+                }
+            };
+        }
+    }
+
+    fn data(&self, byte: u8, output: &mut Vec<u8>) {
+        if self.selected {
+            output.push(byte);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    io::{self, ErrorKind},
+    time::Instant,
+};
+
+use snow::{HandshakeState, StatelessTransportState};
+
+use crate::{Arbiter, Codec};
+
+/// Largest Noise message, including the authentication tag.
+const MAX_MSG_LEN: usize = 65535;
+/// Size of the authentication tag appended to every encrypted message.
+const TAG_LEN: usize = 16;
+/// Size of the explicit nonce prepended to every encrypted message.
+const NONCE_LEN: usize = 8;
+
+/// Encrypts and authenticates every message with the keys established by
+/// a Noise handshake (see [`Arbiter::noise_handshake`]) before handing it
+/// to the inner codec.
+///
+/// Every frame carries its nonce, so a frame lost on the wire does not break
+/// the session. Frames with a nonce lower than the last accepted one are
+/// rejected as replays.
+pub struct Noise<C> {
+    inner: C,
+    transport: StatelessTransportState,
+    tx_nonce: u64,
+    rx_nonce: u64,
+}
+
+impl<C: Codec> Noise<C> {
+    /// Largest message which can be encrypted into a single frame.
+    pub const MAX_PAYLOAD: usize = MAX_MSG_LEN - TAG_LEN;
+}
+
+impl<C: Codec> Codec for Noise<C> {
+    fn delimiter(&self) -> u8 {
+        self.inner.delimiter()
+    }
+
+    fn encode(&mut self, msg: &[u8]) -> io::Result<Vec<u8>> {
+        let nonce = self.tx_nonce;
+        let mut encrypted = vec![0; NONCE_LEN + msg.len() + TAG_LEN];
+        encrypted[..NONCE_LEN].copy_from_slice(&nonce.to_be_bytes());
+        let len = self
+            .transport
+            .write_message(nonce, msg, &mut encrypted[NONCE_LEN..])
+            .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err.to_string()))?;
+        encrypted.truncate(NONCE_LEN + len);
+        self.tx_nonce += 1;
+        self.inner.encode(&encrypted)
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> io::Result<Vec<u8>> {
+        let encrypted = self.inner.decode(frame)?;
+        if encrypted.len() < NONCE_LEN + TAG_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Noise frame too short",
+            ));
+        }
+        let (nonce, encrypted) = encrypted.split_at(NONCE_LEN);
+        let nonce = u64::from_be_bytes(nonce.try_into().unwrap());
+        if nonce < self.rx_nonce {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Noise frame replayed",
+            ));
+        }
+        let mut msg = vec![0; encrypted.len()];
+        let len = self
+            .transport
+            .read_message(nonce, encrypted, &mut msg)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err.to_string()))?;
+        msg.truncate(len);
+        self.rx_nonce = nonce + 1;
+        Ok(msg)
+    }
+}
+
+impl Arbiter {
+    /// Performs a Noise handshake (e.g. `Noise_XX_25519_ChaChaPoly_BLAKE2s`
+    /// or its PSK variants) over frames of the inner codec and returns the
+    /// codec layer encrypting the session. The handshake state is prepared
+    /// with `snow::Builder`, as either the initiator or the responder.
+    pub fn noise_handshake<C: Codec>(
+        &self,
+        mut handshake: HandshakeState,
+        mut inner: C,
+        deadline: Instant,
+    ) -> io::Result<Noise<C>> {
+        let mut buf = vec![0; MAX_MSG_LEN];
+        let handshake_err =
+            |err: snow::Error| io::Error::new(ErrorKind::InvalidData, err.to_string());
+        while !handshake.is_handshake_finished() {
+            if handshake.is_my_turn() {
+                let len = handshake
+                    .write_message(&[], &mut buf)
+                    .map_err(handshake_err)?;
+                self.transmit_encoded(&mut inner, &buf[..len], deadline)?;
+            } else {
+                let msg = self.receive_decoded(&mut inner, deadline)?;
+                handshake
+                    .read_message(&msg, &mut buf)
+                    .map_err(handshake_err)?;
+            }
+        }
+        let transport = handshake
+            .into_stateless_transport_mode()
+            .map_err(handshake_err)?;
+        Ok(Noise {
+            inner,
+            transport,
+            tx_nonce: 0,
+            rx_nonce: 0,
+        })
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+/// Round-trip times of the exchanges of [`Arbiter::ping`](crate::Arbiter::ping),
+/// e.g. to pick the deadlines for a device and link when commissioning them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PingStats {
+    /// Number of exchanges made.
+    pub sent: usize,
+    /// Number of exchanges answered with something other than the expected
+    /// response.
+    pub mismatched: usize,
+    /// Round-trip times of the exchanges answered with the expected
+    /// response, in the order they were made.
+    pub rtts: Vec<Duration>,
+}
+
+impl PingStats {
+    /// Number of exchanges answered with the expected response.
+    pub fn answered(&self) -> usize {
+        self.rtts.len()
+    }
+
+    /// Number of exchanges not answered before their timeout.
+    pub fn lost(&self) -> usize {
+        self.sent - self.answered() - self.mismatched
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.rtts.iter().min().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.rtts.iter().max().copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let total: Duration = self.rtts.iter().sum();
+        Some(total / u32::try_from(self.rtts.len()).ok().filter(|&n| n > 0)?)
+    }
+
+    /// Standard deviation of the round-trip times, i.e. the jitter.
+    pub fn std_dev(&self) -> Option<Duration> {
+        let mean = self.mean()?.as_secs_f64();
+        let variance = self
+            .rtts
+            .iter()
+            .map(|rtt| (rtt.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / self.rtts.len() as f64;
+        Some(Duration::from_secs_f64(variance.sqrt()))
+    }
+
+    /// The round-trip time the given fraction of the answered exchanges did
+    /// not exceed, e.g. 0.99 for the 99th percentile, a good start for the
+    /// timeout of the requests to the device.
+    pub fn percentile(&self, fraction: f64) -> Option<Duration> {
+        percentile(self.rtts.iter().copied(), fraction)
+    }
+}
+
+/// The duration the given fraction of the durations does not exceed.
+pub fn percentile(durations: impl Iterator<Item = Duration>, fraction: f64) -> Option<Duration> {
+    let mut sorted: Vec<_> = durations.collect();
+    sorted.sort();
+    let rank = (fraction.clamp(0.0, 1.0) * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.saturating_sub(1)).copied()
+}
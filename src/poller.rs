@@ -0,0 +1,268 @@
+use std::{
+    io,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Arbiter, Event, PeerStats};
+
+/// Handler of the responses to a query.
+pub type ResponseHandler = Box<dyn FnMut(io::Result<Vec<u8>>) + Send>;
+
+/// Master of a multi-drop bus, e.g. RS-485, which polls the slaves by
+/// cycling through the queries, each at its own interval:
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use serial_arbiter::{Arbiter, Poller};
+/// # let arbiter = Arbiter::new();
+/// let mut poller = Poller::new(arbiter)
+///     .with_gap(Duration::from_millis(5))
+///     .with_sla(Duration::from_millis(50), 3)
+///     .query("boiler", "01 TEMP?\r", b'\r', Duration::from_secs(1), |response| {
+///         println!("Boiler: {response:?}");
+///     })
+///     .query("pump", "02 STATUS?\r", b'\r', Duration::from_secs(10), |response| {
+///         println!("Pump: {response:?}");
+///     });
+/// poller.run();
+/// ```
+///
+/// Every query is a transaction, so it never interleaves with requests of
+/// other clones of the arbiter. A query which fails, e.g. as the slave did
+/// not respond, is passed the error and polled again at its next interval.
+///
+/// The response times and misses are tracked per peer, which is the name
+/// given to its queries. With a service level set by [`Poller::with_sla`],
+/// the poller emits [`Event::PeerDegraded`], [`Event::PeerLost`] and
+/// [`Event::PeerRecovered`] as the peers change their state.
+///
+/// A query which fails while another peer answers its queries is blamed on
+/// its peer rather than the port: it does not count for the circuit breaker,
+/// the watchdog and the adaptive timeout of the arbiter, so one dead slave
+/// does not make the port fail fast or reopen for the others. Once no other
+/// peer answers either, the failures count as usual.
+pub struct Poller {
+    arbiter: Arbiter,
+    queries: Vec<Query>,
+    peers: Vec<Peer>,
+    timeout: Duration,
+    gap: Duration,
+    sla: Option<Sla>,
+}
+
+struct Query {
+    peer: usize,
+    request: Arc<[u8]>,
+    until: u8,
+    interval: Duration,
+    next: Instant,
+    handler: ResponseHandler,
+}
+
+struct Peer {
+    stats: PeerStats,
+    state: PeerState,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PeerState {
+    Healthy,
+    Degraded,
+    Lost,
+}
+
+/// Thresholds of the service level of the peers.
+struct Sla {
+    max_response_time: Duration,
+    max_misses: u32,
+}
+
+impl Poller {
+    /// Creates a poller without queries. Slaves get 1 second to respond.
+    pub fn new(arbiter: Arbiter) -> Self {
+        Self {
+            arbiter,
+            queries: Vec::new(),
+            peers: Vec::new(),
+            timeout: Duration::from_secs(1),
+            gap: Duration::ZERO,
+            sla: None,
+        }
+    }
+
+    /// Sets the time given to every slave to respond.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the idle time of the bus between a response and the next query,
+    /// e.g. for the turnaround of the transceivers.
+    pub fn with_gap(mut self, gap: Duration) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// Sets the service level of the peers. A peer is degraded once its
+    /// smoothed response time exceeds `max_response_time` and lost once it
+    /// misses `max_misses` queries in a row.
+    pub fn with_sla(mut self, max_response_time: Duration, max_misses: u32) -> Self {
+        self.sla = Some(Sla {
+            max_response_time,
+            max_misses: max_misses.max(1),
+        });
+        self
+    }
+
+    /// Adds the query of the peer transmitted every interval, first right
+    /// away. The handler gets the response terminated by the `until` byte,
+    /// or the error of the transaction.
+    pub fn query(
+        mut self,
+        peer: &str,
+        request: impl AsRef<[u8]>,
+        until: u8,
+        interval: Duration,
+        handler: impl FnMut(io::Result<Vec<u8>>) + Send + 'static,
+    ) -> Self {
+        let peer = match self.peers.iter().position(|known| known.stats.peer == peer) {
+            Some(index) => index,
+            None => {
+                self.peers.push(Peer::new(peer));
+                self.peers.len() - 1
+            }
+        };
+        self.queries.push(Query {
+            peer,
+            request: request.as_ref().into(),
+            until,
+            interval,
+            next: Instant::now(),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// The statistics of all peers, in the order of their first queries.
+    pub fn stats(&self) -> Vec<PeerStats> {
+        self.peers.iter().map(|peer| peer.stats.clone()).collect()
+    }
+
+    /// Polls the slaves forever.
+    pub fn run(&mut self) {
+        loop {
+            self.run_until(Instant::now() + Duration::from_secs(3600));
+        }
+    }
+
+    /// Polls the slaves until the deadline. A query in progress at the
+    /// deadline is completed.
+    pub fn run_until(&mut self, deadline: Instant) {
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            let Some(query) = self.queries.iter_mut().min_by_key(|query| query.next) else {
+                thread::sleep(deadline - now);
+                return;
+            };
+            if query.next > now {
+                thread::sleep(query.next.min(deadline) - now);
+                continue;
+            }
+            query.next += query.interval;
+            if query.next <= now {
+                // Fell behind, e.g. as slaves timed out, so do not burst
+                query.next = now + query.interval;
+            }
+
+            // Another peer answering tells the port works
+            let isolated = self.peers.iter().enumerate().any(|(index, peer)| {
+                index != query.peer
+                    && peer.stats.responses > 0
+                    && peer.stats.consecutive_misses == 0
+            });
+            let response = self.arbiter.transact_inner(
+                self.arbiter.conn.next_transaction_id(),
+                query.request.clone(),
+                query.until,
+                now + self.timeout,
+                false,
+                isolated,
+            );
+            let peer = &mut self.peers[query.peer];
+            let event = match response {
+                Ok(_) => peer.answered(now.elapsed(), self.sla.as_ref()),
+                Err(_) => peer.missed(self.sla.as_ref()),
+            };
+            if let Some(event) = event {
+                self.arbiter.conn.events.emit(event);
+            }
+            (query.handler)(response);
+            thread::sleep(self.gap);
+        }
+    }
+}
+
+impl Peer {
+    fn new(name: &str) -> Self {
+        Self {
+            stats: PeerStats {
+                peer: name.to_string(),
+                responses: 0,
+                misses: 0,
+                consecutive_misses: 0,
+                last_response_time: None,
+                smoothed_response_time: None,
+            },
+            state: PeerState::Healthy,
+        }
+    }
+
+    /// Account a response, returning the event if the peer changed its state.
+    fn answered(&mut self, time: Duration, sla: Option<&Sla>) -> Option<Event> {
+        let stats = &mut self.stats;
+        stats.responses += 1;
+        stats.consecutive_misses = 0;
+        stats.last_response_time = Some(time);
+        let smoothed = match stats.smoothed_response_time {
+            Some(smoothed) => (smoothed * 7 + time) / 8,
+            None => time,
+        };
+        stats.smoothed_response_time = Some(smoothed);
+
+        let state = match sla {
+            Some(sla) if smoothed > sla.max_response_time => PeerState::Degraded,
+            _ => PeerState::Healthy,
+        };
+        self.transition(state)
+    }
+
+    /// Account a miss, returning the event if the peer changed its state.
+    fn missed(&mut self, sla: Option<&Sla>) -> Option<Event> {
+        self.stats.misses += 1;
+        self.stats.consecutive_misses += 1;
+        match sla {
+            Some(sla) if self.stats.consecutive_misses >= sla.max_misses => {
+                self.transition(PeerState::Lost)
+            }
+            _ => None,
+        }
+    }
+
+    fn transition(&mut self, state: PeerState) -> Option<Event> {
+        if state == self.state {
+            return None;
+        }
+        self.state = state;
+        let stats = self.stats.clone();
+        Some(match state {
+            PeerState::Healthy => Event::PeerRecovered(stats),
+            PeerState::Degraded => Event::PeerDegraded(stats),
+            PeerState::Lost => Event::PeerLost(stats),
+        })
+    }
+}
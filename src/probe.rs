@@ -0,0 +1,88 @@
+use std::{
+    fs::{self, OpenOptions},
+    io,
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+use nix::fcntl::OFlag;
+use termios::Termios;
+
+use crate::{
+    serial_port::{baud_rate, port_set_termios},
+    setserial::port_serial_info,
+    SerialInfo,
+};
+
+/// The identity of a USB-serial adapter, read from sysfs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbInfo {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+/// What [`Arbiter::probe`](crate::Arbiter::probe) found out about a port.
+#[derive(Debug, Clone)]
+pub struct PortProbe {
+    /// The device node the path leads to, e.g. `/dev/ttyUSB0` for a link
+    /// under `/dev/serial/by-id`.
+    pub device: PathBuf,
+    /// The termios settings the port has.
+    pub termios: Termios,
+    /// The baud rate, None if it is not one of the standard ones.
+    pub baud: Option<u32>,
+    /// The low level settings of the serial driver, None for the devices
+    /// without them, such as most USB-serial adapters.
+    pub serial: Option<SerialInfo>,
+    /// The adapter, None if the port is not a USB device.
+    pub usb: Option<UsbInfo>,
+}
+
+/// Open the port, read what it is and close it again.
+pub fn port_probe(path: &Path) -> io::Result<PortProbe> {
+    let device = fs::canonicalize(path)?;
+    let port = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags((OFlag::O_NOCTTY | OFlag::O_NONBLOCK).bits())
+        .open(&device)?;
+    let fd = port.as_raw_fd();
+    let termios = Termios::from_fd(fd)?;
+    if termios.c_cflag & termios::HUPCL != 0 {
+        // Closing must not drop DTR and RTS, which resets some devices
+        let mut keep_lines = termios;
+        keep_lines.c_cflag &= !termios::HUPCL;
+        port_set_termios(fd, &keep_lines)?;
+    }
+    Ok(PortProbe {
+        baud: baud_rate(termios::cfgetospeed(&termios)),
+        serial: port_serial_info(fd).ok(),
+        usb: usb_info(&device),
+        device,
+        termios,
+    })
+}
+
+/// Find the USB device above the tty in sysfs.
+fn usb_info(device: &Path) -> Option<UsbInfo> {
+    let tty = Path::new("/sys/class/tty").join(device.file_name()?);
+    let sysfs = fs::canonicalize(tty.join("device")).ok()?;
+    let usb = sysfs
+        .ancestors()
+        .find(|dir| dir.join("idVendor").exists())?;
+    let attribute = |name: &str| {
+        let value = fs::read_to_string(usb.join(name)).ok()?;
+        Some(value.trim().to_string())
+    };
+    let id = |name: &str| u16::from_str_radix(&attribute(name)?, 16).ok();
+    Some(UsbInfo {
+        vendor_id: id("idVendor")?,
+        product_id: id("idProduct")?,
+        manufacturer: attribute("manufacturer"),
+        product: attribute("product"),
+        serial_number: attribute("serial"),
+    })
+}
@@ -0,0 +1,59 @@
+use std::{
+    io::{self, ErrorKind},
+    time::Duration,
+};
+
+/// Decides when a failed transaction is worth another attempt.
+///
+/// Every retry flushes the Rx buffer before retransmitting so stale partial
+/// responses from the previous attempt cannot be mistaken for a new response.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub attempts: usize,
+    /// Pause between a failed attempt and the next one. Slept on the clock
+    /// of the arbiter, so a [`ManualClock`](crate::ManualClock) skips it,
+    /// while the timeout of each attempt is real time like all deadlines,
+    /// see [`Clock`](crate::Clock).
+    pub backoff: Duration,
+    /// Returns true if the error is worth retrying.
+    pub retriable: fn(&io::Error) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::ZERO,
+            retriable: is_transient,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy making the given total number of attempts.
+    pub fn new(attempts: usize) -> Self {
+        Self {
+            attempts,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the pause between attempts.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets the classification of retriable errors.
+    pub fn with_retriable(mut self, retriable: fn(&io::Error) -> bool) -> Self {
+        self.retriable = retriable;
+        self
+    }
+}
+
+/// Default classification: timeouts and interrupts are retried,
+/// everything else (e.g. a missing device) fails immediately.
+pub fn is_transient(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::Interrupted)
+}
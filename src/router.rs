@@ -0,0 +1,92 @@
+use std::{
+    io::{self, ErrorKind},
+    time::Instant,
+};
+
+use crate::channel::Sender;
+
+use crate::rx_buffer::RxBuffer;
+
+/// Number of unmatched frames buffered for the receiver of
+/// [`Arbiter::set_router`](crate::Arbiter::set_router) before new ones are dropped.
+pub const UNMATCHED_CAPACITY: usize = 256;
+
+/// Extracts the correlation key (e.g. a JSON-RPC id or a Modbus transaction
+/// id) from a complete frame. Returns None if the frame carries no key.
+pub type KeyExtractor = Box<dyn Fn(&[u8]) -> Option<Vec<u8>> + Send>;
+
+/// Routes complete frames from the RX buffer to the requests waiting for them.
+pub struct Router {
+    delimiter: u8,
+    extractor: KeyExtractor,
+    sink: Sender<Vec<u8>>,
+    waiting: Vec<Waiter>,
+}
+
+/// A request waiting for the frame with the given key.
+struct Waiter {
+    key: Vec<u8>,
+    deadline: Instant,
+    response: Sender<io::Result<Vec<u8>>>,
+}
+
+impl Router {
+    pub fn new(delimiter: u8, extractor: KeyExtractor, sink: Sender<Vec<u8>>) -> Self {
+        Self {
+            delimiter,
+            extractor,
+            sink,
+            waiting: Vec::new(),
+        }
+    }
+
+    /// Register a request waiting for the frame with the given key.
+    pub fn wait(&mut self, key: Vec<u8>, deadline: Instant, response: Sender<io::Result<Vec<u8>>>) {
+        self.waiting.push(Waiter {
+            key,
+            deadline,
+            response,
+        });
+    }
+
+    /// Consume all complete frames from the RX buffer and deliver each one
+    /// either to the oldest request waiting for its key or to the default sink.
+    pub fn dispatch(&mut self, buff: &mut RxBuffer) {
+        while let Some(pos) = buff.position(self.delimiter) {
+            let frame = buff.take(pos + 1).data;
+            let waiter = (self.extractor)(&frame)
+                .and_then(|key| self.waiting.iter().position(|w| w.key == key));
+            match waiter {
+                Some(idx) => {
+                    let waiter = self.waiting.remove(idx);
+                    let _ = waiter.response.try_send(Ok(frame));
+                }
+                None => {
+                    // Dropped if the receiver is not drained
+                    let _ = self.sink.try_send(frame);
+                }
+            }
+        }
+    }
+
+    /// Fail all requests whose deadline has passed.
+    pub fn expire(&mut self, now: Instant) {
+        self.waiting.retain(|waiter| {
+            if waiter.deadline <= now {
+                let _ = waiter.response.try_send(Err(ErrorKind::TimedOut.into()));
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+impl Drop for Router {
+    fn drop(&mut self) {
+        // Do not leave anyone waiting for a frame that will never be routed
+        for waiter in self.waiting.drain(..) {
+            let _ = waiter.response.try_send(Err(ErrorKind::Interrupted.into()));
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+const SYSFS_GPIO: &str = "/sys/class/gpio";
+
+/// GPIO line driving the DE/RE pin of an RS-485 transceiver.
+///
+/// For boards where the driver-enable pin is wired to a GPIO instead of RTS.
+/// The line is asserted right before transmitting and released once the
+/// transmitted data has physically left the UART.
+pub struct DirectionGpio {
+    value: File,
+    active_low: bool,
+}
+
+impl DirectionGpio {
+    /// Uses the sysfs GPIO interface for the given line number. The line is
+    /// exported and configured as an output if necessary.
+    pub fn sysfs(line: u32, active_low: bool) -> io::Result<Self> {
+        let dir = PathBuf::from(format!("{SYSFS_GPIO}/gpio{line}"));
+        if !dir.exists() {
+            fs::write(format!("{SYSFS_GPIO}/export"), line.to_string())?;
+            // Udev may need a moment to grant permissions on the new line
+            thread::sleep(Duration::from_millis(100));
+        }
+        fs::write(dir.join("direction"), "out")?;
+        Self::from_value_path(dir.join("value"), active_low)
+    }
+
+    /// Uses an already configured sysfs `value` file of an output line.
+    pub fn from_value_path(path: impl AsRef<Path>, active_low: bool) -> io::Result<Self> {
+        let value = OpenOptions::new().write(true).open(path)?;
+        let mut gpio = Self { value, active_low };
+        gpio.set(false)?;
+        Ok(gpio)
+    }
+
+    /// Drive the line to enable (true) or disable (false) the transmitter.
+    pub(crate) fn set(&mut self, transmit: bool) -> io::Result<()> {
+        let level = if transmit != self.active_low {
+            b"1"
+        } else {
+            b"0"
+        };
+        self.value.write_all(level)
+    }
+}
@@ -0,0 +1,321 @@
+use std::{collections::VecDeque, ops::Range, sync::Arc, time::Instant};
+
+use crate::{multidrop::MultidropDecoder, rx_filter::RxFilter, trace::RequestTrace};
+
+/// Received data together with the time each chunk was read from the port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timestamped {
+    /// The received bytes.
+    pub data: Vec<u8>,
+    /// Offset into `data` at which each chunk starts and the time it was read.
+    pub chunks: Vec<(usize, Instant)>,
+    /// Generation of the connection the data was received on, see
+    /// [`Arbiter::generation`](crate::Arbiter::generation). If the data
+    /// spans a reconnect (see [`ReconnectRx::Keep`]) this is the newer one.
+    pub generation: u64,
+}
+
+/// What happens to the received but not yet delivered data when the
+/// connection fails and the port is reopened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReconnectRx {
+    /// Keep the data, so it is followed by the data received after the
+    /// reconnect. Note that a frame cut by the disconnect gets merged with
+    /// the start of the next frame.
+    #[default]
+    Keep,
+    /// Drop the data.
+    Discard,
+    /// Deliver the data on its own, tagged with the generation of the
+    /// previous connection, by the next receive which does not require
+    /// a complete frame (e.g. [`Arbiter::receive_timestamped`](crate::Arbiter::receive_timestamped)).
+    Separate,
+}
+
+impl Timestamped {
+    /// Time when the first byte was read from the port.
+    pub fn first_arrival(&self) -> Option<Instant> {
+        self.chunks.first().map(|(_, time)| *time)
+    }
+
+    /// Time when the last byte was read from the port.
+    pub fn last_arrival(&self) -> Option<Instant> {
+        self.chunks.last().map(|(_, time)| *time)
+    }
+}
+
+/// Default size of a single read from the port.
+const DEFAULT_READ_CHUNK: usize = 1024 * 1024;
+
+/// Transform of the received byte stream, writing the output of every chunk
+/// of input into the vector.
+pub type RxTransform = Box<dyn FnMut(&[u8], &mut Vec<u8>) + Send>;
+
+/// The RX FIFO buffer of the worker thread.
+///
+/// Every chunk appended to the buffer is stamped with the time of arrival.
+/// The stamps follow the data when it is consumed from the buffer.
+#[derive(Default)]
+pub struct RxBuffer {
+    data: VecDeque<u8>,
+    /// Offset into `data` at which each chunk starts and the time it was read.
+    chunks: VecDeque<(usize, Instant)>,
+    /// Decoder of the 9-bit multidrop Rx stream.
+    multidrop: Option<MultidropDecoder>,
+    /// Transform applied to every chunk before it is buffered.
+    transform: Option<RxTransform>,
+    /// Number of bytes read from the port and not yet accounted for.
+    unaccounted: usize,
+    /// Generation of the connection the data is being received on.
+    generation: u64,
+    /// Copy of the bytes read from the port, kept while capturing.
+    captured: Option<Vec<u8>>,
+    /// Drops the matching frames as soon as they are complete.
+    filter: Option<RxFilter>,
+    /// Buffer the port is read into, as large as a read may be.
+    read_buf: Vec<u8>,
+    /// Maximum number of bytes read from the port at a time.
+    read_budget: Option<usize>,
+    /// Trace of the request being served, which counts the bytes read.
+    trace: Option<Arc<RequestTrace>>,
+}
+
+impl RxBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a chunk which has just been read from the port.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        let time = Instant::now();
+        self.unaccounted += bytes.len();
+        if let Some(trace) = &self.trace {
+            trace.received(bytes.len());
+        }
+        if let Some(captured) = &mut self.captured {
+            captured.extend_from_slice(bytes);
+        }
+        let decoded;
+        let bytes = match &mut self.multidrop {
+            None => bytes,
+            Some(decoder) => {
+                let mut output = Vec::with_capacity(bytes.len());
+                decoder.decode(bytes, &mut output);
+                decoded = output;
+                &decoded
+            }
+        };
+        let transformed;
+        let bytes = match &mut self.transform {
+            None => bytes,
+            Some(transform) => {
+                let mut output = Vec::with_capacity(bytes.len());
+                transform(bytes, &mut output);
+                transformed = output;
+                &transformed
+            }
+        };
+        if bytes.is_empty() {
+            return;
+        }
+        let len = self.data.len();
+        self.chunks.push_back((len, time));
+        self.data.extend(bytes);
+        self.apply_filter(len);
+    }
+
+    /// Set the transform applied to every chunk before it is buffered.
+    pub fn set_transform(&mut self, transform: Option<RxTransform>) {
+        self.transform = transform;
+    }
+
+    /// Set the filter of the frames which are dropped as soon as they are
+    /// complete, and apply it to the data already buffered.
+    pub fn set_filter(&mut self, filter: Option<RxFilter>) {
+        self.filter = filter;
+        self.apply_filter(0);
+    }
+
+    /// Drop the filtered frames completed by the data starting at `from`.
+    fn apply_filter(&mut self, from: usize) {
+        let Some(mut filter) = self.filter.take() else {
+            return;
+        };
+        let delimiter = filter.delimiter();
+        // The first frame may have started before the new data
+        let mut start = self
+            .data
+            .range(..from)
+            .rposition(|&byte| byte == delimiter)
+            .map_or(0, |pos| pos + 1);
+        let mut pos = from;
+        while let Some(offset) = self.data.range(pos..).position(|&byte| byte == delimiter) {
+            let end = pos + offset + 1;
+            let frame: Vec<u8> = self.data.range(start..end - 1).copied().collect();
+            if filter.matches(&frame) {
+                self.remove(start..end);
+                pos = start;
+            } else {
+                start = end;
+                pos = end;
+            }
+        }
+        self.filter = Some(filter);
+    }
+
+    /// Set how many bytes a single read from the port may return and how many
+    /// may be read at a time before other work gets its turn.
+    pub fn set_read_limits(&mut self, chunk_size: usize, budget: Option<usize>) {
+        self.read_buf = vec![0; chunk_size.max(1)];
+        self.read_budget = budget.map(|budget| budget.max(1));
+    }
+
+    /// Maximum number of bytes read from the port at a time.
+    pub fn read_budget(&self) -> Option<usize> {
+        self.read_budget
+    }
+
+    /// Take the buffer to read the port into, to be returned with
+    /// [`RxBuffer::put_read_buf`].
+    pub fn take_read_buf(&mut self) -> Vec<u8> {
+        match std::mem::take(&mut self.read_buf) {
+            buf if buf.is_empty() => vec![0; DEFAULT_READ_CHUNK],
+            buf => buf,
+        }
+    }
+
+    pub fn put_read_buf(&mut self, buf: Vec<u8>) {
+        self.read_buf = buf;
+    }
+
+    pub fn set_trace(&mut self, trace: Option<Arc<RequestTrace>>) {
+        self.trace = trace;
+    }
+
+    /// Returns the number of bytes read from the port since the last call.
+    pub fn take_unaccounted(&mut self) -> usize {
+        std::mem::take(&mut self.unaccounted)
+    }
+
+    /// Start or stop keeping a copy of the bytes read from the port.
+    pub fn set_capture(&mut self, capture: bool) {
+        match capture {
+            true => {
+                self.captured.get_or_insert_with(Vec::new);
+            }
+            false => self.captured = None,
+        }
+    }
+
+    /// Returns the bytes read from the port since the last call, if capturing.
+    pub fn take_captured(&mut self) -> Vec<u8> {
+        self.captured
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Set the decoder applied to every chunk before it is buffered.
+    pub fn set_multidrop(&mut self, decoder: Option<MultidropDecoder>) {
+        self.multidrop = decoder;
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.chunks.clear();
+    }
+
+    /// Position of the first occurrence of the given byte.
+    pub fn position(&self, byte: u8) -> Option<usize> {
+        self.data.iter().position(|x| x == &byte)
+    }
+
+    pub fn contains(&self, byte: u8) -> bool {
+        self.data.contains(&byte)
+    }
+
+    /// Position of the end of the first occurrence of the pattern.
+    pub fn find(&mut self, pattern: &[u8]) -> Option<usize> {
+        if pattern.is_empty() {
+            return Some(0);
+        }
+        self.data
+            .make_contiguous()
+            .windows(pattern.len())
+            .position(|window| window == pattern)
+            .map(|pos| pos + pattern.len())
+    }
+
+    /// Copy of the bytes from the given offset on, which are not consumed.
+    pub fn peek(&self, from: usize) -> Vec<u8> {
+        self.data
+            .range(from.min(self.data.len())..)
+            .copied()
+            .collect()
+    }
+
+    /// Consume the given count of bytes from the front of the buffer.
+    pub fn take(&mut self, count: usize) -> Timestamped {
+        let count = count.min(self.data.len());
+        let chunks = self
+            .chunks
+            .iter()
+            .take_while(|(start, _)| *start < count)
+            .copied()
+            .collect();
+        let data = self.remove(0..count);
+        Timestamped {
+            data,
+            chunks,
+            generation: self.generation,
+        }
+    }
+
+    /// Consume all bytes from the buffer.
+    pub fn take_all(&mut self) -> Timestamped {
+        self.take(self.data.len())
+    }
+
+    /// Remove the given range of bytes from the buffer.
+    pub fn remove(&mut self, range: Range<usize>) -> Vec<u8> {
+        let Range { start, end } = range;
+        let end = end.min(self.data.len());
+        if start >= end {
+            return Vec::new();
+        }
+        let removed = end - start;
+        let len = self.data.len();
+        let mut chunks = VecDeque::with_capacity(self.chunks.len());
+        for (idx, &(chunk_start, time)) in self.chunks.iter().enumerate() {
+            let chunk_end = self.chunks.get(idx + 1).map_or(len, |(next, _)| *next);
+            if chunk_start >= start && chunk_end <= end {
+                // The whole chunk is removed
+                continue;
+            }
+            let chunk_start = if chunk_start >= end {
+                chunk_start - removed
+            } else {
+                chunk_start.min(start)
+            };
+            chunks.push_back((chunk_start, time));
+        }
+        self.chunks = chunks;
+        self.data.drain(start..end).collect()
+    }
+}
@@ -0,0 +1,61 @@
+/// Predicate selecting the frames to be dropped.
+pub type FramePredicate = Box<dyn FnMut(&[u8]) -> bool + Send>;
+
+/// Drops known frames, e.g. the keep-alives a device sends periodically,
+/// as soon as they are received, so they never reach the receive calls,
+/// the router or the frame callback. See [`Arbiter::set_rx_filter`](crate::Arbiter::set_rx_filter).
+///
+/// The frames are terminated by the delimiter and are matched without it,
+/// either exactly or by a predicate:
+///
+/// ```
+/// # use serial_arbiter::RxFilter;
+/// let filter = RxFilter::new(b'\n')
+///     .frame("PING\r")
+///     .predicate(|frame| frame.starts_with(b"+HEARTBEAT"));
+/// ```
+pub struct RxFilter {
+    delimiter: u8,
+    frames: Vec<Vec<u8>>,
+    predicate: Option<FramePredicate>,
+}
+
+impl RxFilter {
+    /// Creates a filter of the frames terminated by the delimiter, which
+    /// drops nothing until frames or a predicate are added.
+    pub fn new(delimiter: u8) -> Self {
+        Self {
+            delimiter,
+            frames: Vec::new(),
+            predicate: None,
+        }
+    }
+
+    /// Drops the frames equal to the given bytes.
+    pub fn frame(mut self, frame: impl AsRef<[u8]>) -> Self {
+        self.frames.push(frame.as_ref().to_vec());
+        self
+    }
+
+    /// Drops the frames for which the predicate returns true. Replaces any
+    /// previously set predicate.
+    pub fn predicate(mut self, predicate: impl FnMut(&[u8]) -> bool + Send + 'static) -> Self {
+        self.predicate = Some(Box::new(predicate));
+        self
+    }
+
+    /// The byte terminating the frames.
+    pub fn delimiter(&self) -> u8 {
+        self.delimiter
+    }
+
+    /// Returns true if the frame, without the delimiter, is to be dropped.
+    pub(crate) fn matches(&mut self, frame: &[u8]) -> bool {
+        if self.frames.iter().any(|known| known == frame) {
+            return true;
+        }
+        self.predicate
+            .as_mut()
+            .is_some_and(|predicate| predicate(frame))
+    }
+}
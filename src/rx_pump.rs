@@ -0,0 +1,95 @@
+use std::{
+    fs::File,
+    io,
+    os::fd::AsRawFd,
+    sync::{atomic::Ordering, Arc},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use nix::{
+    fcntl::{fcntl, FcntlArg, OFlag},
+    unistd::pipe2,
+};
+
+use crate::{
+    rx_buffer::RxBuffer,
+    serial_port::{port_pump, port_read},
+    sync::{AtomicBool, Mutex},
+    ThreadTuning,
+};
+
+/// Size of the pipe, the most an unprivileged process gets by default.
+const PIPE_SIZE: i32 = 1024 * 1024;
+/// Interval of collecting the data while waiting for the thread to stop.
+const STOP_POLL: Duration = Duration::from_millis(1);
+
+/// Reads the port on a dedicated thread and hands the data over a pipe, so
+/// a fast stream of incoming data neither waits for the transmissions nor
+/// delays them. The pipe is read like the port itself, with the same poll
+/// and read calls.
+pub struct RxPump {
+    pipe: Arc<Mutex<File>>,
+    running: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl RxPump {
+    /// Starts reading the port. The thread stops by itself once the port
+    /// is closed or fails, which the reader of the pipe sees as a hangup.
+    pub fn start(port: &Arc<Mutex<File>>, tuning: &ThreadTuning) -> io::Result<Self> {
+        let (read_end, write_end) = pipe2(OFlag::O_NONBLOCK | OFlag::O_CLOEXEC)?;
+        // Best effort, the default of 64 KiB is enough for the slower ports
+        let _ = fcntl(write_end.as_raw_fd(), FcntlArg::F_SETPIPE_SZ(PIPE_SIZE));
+        let mut pipe = File::from(write_end);
+        let mut file = port.lock().unwrap().try_clone()?;
+        let port = Arc::downgrade(port);
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = thread::spawn({
+            let running = running.clone();
+            let tuning = tuning.clone();
+            move || {
+                if let Err(err) = tuning.apply() {
+                    log::warn!("Cannot tune the Rx pump: {err}");
+                }
+                // Hold the port open no longer than the connection does
+                let result = port_pump(&mut file, &mut pipe, || {
+                    running.load(Ordering::Relaxed) && port.strong_count() > 0
+                });
+                if let Err(err) = result {
+                    log::warn!("Rx pump stopped: {err}");
+                }
+            }
+        });
+        Ok(Self {
+            pipe: Arc::new(Mutex::new(File::from(read_end))),
+            running,
+            thread,
+        })
+    }
+
+    /// The read end of the pipe.
+    pub fn pipe(&self) -> Arc<Mutex<File>> {
+        self.pipe.clone()
+    }
+
+    /// Stops the thread, collecting everything it has read to the buffer.
+    pub fn stop(self, buff: &mut RxBuffer) {
+        self.running.store(false, Ordering::Relaxed);
+        let mut pipe = self.pipe.lock().unwrap();
+        loop {
+            let finished = self.thread.is_finished();
+            while port_read(&mut pipe, buff).is_ok_and(|read| read > 0) {}
+            if finished {
+                return;
+            }
+            thread::sleep(STOP_POLL);
+        }
+    }
+}
+
+impl Drop for RxPump {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
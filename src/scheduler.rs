@@ -0,0 +1,69 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Frames transmitted periodically by the worker between the requests.
+#[derive(Default)]
+pub struct Scheduler {
+    entries: Vec<Entry>,
+    next_id: u64,
+    /// Minimum idle time of the line before a scheduled frame.
+    gap: Duration,
+}
+
+struct Entry {
+    id: u64,
+    frame: Arc<[u8]>,
+    interval: Duration,
+    next: Instant,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a frame sent every interval, first right away. Returns its id.
+    pub fn add(&mut self, frame: Arc<[u8]>, interval: Duration, now: Instant) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(Entry {
+            id,
+            frame,
+            interval,
+            next: now,
+        });
+        id
+    }
+
+    /// Remove the frame with the given id. Returns false if there is none.
+    pub fn remove(&mut self, id: u64) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.id != id);
+        self.entries.len() != len
+    }
+
+    pub fn set_gap(&mut self, gap: Duration) {
+        self.gap = gap;
+    }
+
+    /// Returns the most overdue frame if the line has been idle long enough
+    /// since the last transmission, and schedules its next transmission.
+    pub fn next_due(&mut self, now: Instant, last_tx: Option<Instant>) -> Option<Arc<[u8]>> {
+        if last_tx.is_some_and(|last_tx| now < last_tx + self.gap) {
+            return None;
+        }
+        let entry = self
+            .entries
+            .iter_mut()
+            .filter(|entry| entry.next <= now)
+            .min_by_key(|entry| entry.next)?;
+        entry.next += entry.interval;
+        if entry.next <= now {
+            // Fell behind, e.g. during a long request, so do not burst
+            entry.next = now + entry.interval;
+        }
+        Some(entry.frame.clone())
+    }
+}
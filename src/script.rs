@@ -0,0 +1,152 @@
+use std::{
+    io::{self, ErrorKind},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use regex::bytes::Regex;
+
+use crate::{Arbiter, ArbiterError};
+
+/// A step of a [`Script`].
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Transmit the bytes.
+    Send(Arc<[u8]>),
+    /// Receive until the pattern matches the data received since the
+    /// previous match. Everything up to the end of the match is consumed.
+    Expect(Regex),
+    /// Pause before the next step.
+    Delay(Duration),
+    /// Abort the script if the pattern matches while waiting for any of the
+    /// following expectations, e.g. on `ERROR`.
+    AbortOn(Regex),
+}
+
+/// An expect/send script, e.g. for provisioning a device through its
+/// command line:
+///
+/// ```no_run
+/// # use serial_arbiter::{Arbiter, Script, regex::bytes::Regex};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let arbiter = Arbiter::new();
+/// let script = Script::new()
+///     .abort_on(Regex::new("ERROR")?)
+///     .send("AT\r")
+///     .expect(Regex::new("OK\r\n")?)
+///     .send("AT+CGSN\r")
+///     .expect(Regex::new(r"\d{15}")?);
+/// let matches = arbiter.run_script(&script)?;
+/// let imei = String::from_utf8_lossy(&matches[1]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Script {
+    steps: Vec<Step>,
+    timeout: Duration,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Self {
+            steps: Vec::new(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Script {
+    /// Creates an empty script with a 5 second timeout of every step.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the time given to every send and expect step.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Appends a step.
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Appends a [`Step::Send`].
+    pub fn send(self, tx_bytes: impl AsRef<[u8]>) -> Self {
+        self.step(Step::Send(tx_bytes.as_ref().into()))
+    }
+
+    /// Appends a [`Step::Expect`].
+    pub fn expect(self, pattern: Regex) -> Self {
+        self.step(Step::Expect(pattern))
+    }
+
+    /// Appends a [`Step::Delay`].
+    pub fn delay(self, delay: Duration) -> Self {
+        self.step(Step::Delay(delay))
+    }
+
+    /// Appends a [`Step::AbortOn`].
+    pub fn abort_on(self, pattern: Regex) -> Self {
+        self.step(Step::AbortOn(pattern))
+    }
+
+    /// The steps of the script.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+impl Arbiter {
+    /// Runs the script step by step and returns the bytes matched by every
+    /// expect step, in order.
+    ///
+    /// An expectation not met in time fails with `ErrorKind::TimedOut` and
+    /// an abort pattern matching fails with [`ArbiterError::Aborted`].
+    /// Other clones of the arbiter can still make requests between the
+    /// steps.
+    pub fn run_script(&self, script: &Script) -> io::Result<Vec<Vec<u8>>> {
+        let mut aborts: Vec<&Regex> = Vec::new();
+        let mut matches = Vec::new();
+        let mut received = Vec::new();
+        for (index, step) in script.steps.iter().enumerate() {
+            match step {
+                Step::Send(tx_bytes) => {
+                    self.transmit(tx_bytes.clone(), Instant::now() + script.timeout)?;
+                }
+                Step::Expect(pattern) => {
+                    let deadline = Instant::now() + script.timeout;
+                    loop {
+                        if let Some(abort) = aborts.iter().find(|abort| abort.is_match(&received)) {
+                            let err = ArbiterError::Aborted {
+                                step: index,
+                                pattern: abort.as_str().to_string(),
+                                received,
+                            };
+                            return Err(self.tagged(err.into()));
+                        }
+                        if let Some(found) = pattern.find(&received) {
+                            matches.push(found.as_bytes().to_vec());
+                            received.drain(..found.end());
+                            break;
+                        }
+                        if !self.wait_readable(deadline)? {
+                            let msg = format!("Step {index} expecting {pattern} timed out");
+                            return Err(self.tagged(io::Error::new(ErrorKind::TimedOut, msg)));
+                        }
+                        if let Some(data) = self.receive(None, Some(Instant::now()))? {
+                            received.extend_from_slice(&data);
+                        }
+                    }
+                }
+                Step::Delay(delay) => thread::sleep(*delay),
+                Step::AbortOn(pattern) => aborts.push(pattern),
+            }
+        }
+        Ok(matches)
+    }
+}
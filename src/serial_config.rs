@@ -0,0 +1,173 @@
+use std::io::{self, ErrorKind};
+
+use termios::{os::linux::CRTSCTS, Termios};
+
+use crate::baud_speed;
+
+/// Number of data bits of a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "fleet", derive(serde::Deserialize))]
+pub enum DataBits {
+    #[cfg_attr(feature = "fleet", serde(rename = "5"))]
+    Five,
+    #[cfg_attr(feature = "fleet", serde(rename = "6"))]
+    Six,
+    #[cfg_attr(feature = "fleet", serde(rename = "7"))]
+    Seven,
+    #[cfg_attr(feature = "fleet", serde(rename = "8"))]
+    Eight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "fleet",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+    /// The parity bit is always 1.
+    Mark,
+    /// The parity bit is always 0.
+    Space,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "fleet", derive(serde::Deserialize))]
+pub enum StopBits {
+    #[cfg_attr(feature = "fleet", serde(rename = "1"))]
+    One,
+    #[cfg_attr(feature = "fleet", serde(rename = "2"))]
+    Two,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "fleet",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FlowControl {
+    None,
+    /// XON/XOFF characters in the data stream (`IXON`, `IXOFF`).
+    Software,
+    /// The RTS and CTS lines (`CRTSCTS`).
+    Hardware,
+}
+
+/// The line settings applied every time the port is opened, see
+/// [`Arbiter::set_config`](crate::Arbiter::set_config):
+///
+/// ```no_run
+/// # use serial_arbiter::{Arbiter, Parity, SerialConfig};
+/// let arbiter = Arbiter::new();
+/// let config = SerialConfig::new().with_baud(19200).with_parity(Parity::Even);
+/// arbiter.open_with_config("/dev/ttyUSB0", config)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// Every setting left out stays as the device has it. How the received
+/// characters with a parity error are handled is set separately, see
+/// [`Arbiter::set_parity_error_input`](crate::Arbiter::set_parity_error_input).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerialConfig {
+    pub baud: Option<u32>,
+    pub data_bits: Option<DataBits>,
+    pub parity: Option<Parity>,
+    pub stop_bits: Option<StopBits>,
+    pub flow_control: Option<FlowControl>,
+}
+
+impl SerialConfig {
+    /// Creates a config which changes nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The baud rate, one of the standard ones, see [`baud_speed`].
+    /// Setting the config fails for any other, see [`SerialConfig::validate`].
+    pub fn with_baud(mut self, baud: u32) -> Self {
+        self.baud = Some(baud);
+        self
+    }
+
+    pub fn with_data_bits(mut self, data_bits: DataBits) -> Self {
+        self.data_bits = Some(data_bits);
+        self
+    }
+
+    pub fn with_parity(mut self, parity: Parity) -> Self {
+        self.parity = Some(parity);
+        self
+    }
+
+    pub fn with_stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = Some(stop_bits);
+        self
+    }
+
+    pub fn with_flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.flow_control = Some(flow_control);
+        self
+    }
+
+    /// Fails with `ErrorKind::InvalidInput` for a config which cannot be
+    /// applied, i.e. a baud rate which is not a standard one.
+    pub fn validate(&self) -> io::Result<()> {
+        self.baud.map_or(Ok(()), |baud| speed(baud).map(|_| ()))
+    }
+
+    /// Apply the config to the termios settings.
+    pub fn apply(&self, termios: &mut Termios) -> io::Result<()> {
+        use termios::os::linux::CMSPAR;
+
+        if let Some(baud) = self.baud {
+            termios::cfsetspeed(termios, speed(baud)?)?;
+        }
+        if let Some(data_bits) = self.data_bits {
+            termios.c_cflag &= !termios::CSIZE;
+            termios.c_cflag |= match data_bits {
+                DataBits::Five => termios::CS5,
+                DataBits::Six => termios::CS6,
+                DataBits::Seven => termios::CS7,
+                DataBits::Eight => termios::CS8,
+            };
+        }
+        if let Some(parity) = self.parity {
+            termios.c_cflag &= !(termios::PARENB | termios::PARODD | CMSPAR);
+            termios.c_cflag |= match parity {
+                Parity::None => 0,
+                Parity::Odd => termios::PARENB | termios::PARODD,
+                Parity::Even => termios::PARENB,
+                Parity::Mark => termios::PARENB | CMSPAR | termios::PARODD,
+                Parity::Space => termios::PARENB | CMSPAR,
+            };
+        }
+        match self.stop_bits {
+            Some(StopBits::One) => termios.c_cflag &= !termios::CSTOPB,
+            Some(StopBits::Two) => termios.c_cflag |= termios::CSTOPB,
+            None => {}
+        }
+        if let Some(flow_control) = self.flow_control {
+            termios.c_cflag &= !CRTSCTS;
+            termios.c_iflag &= !(termios::IXON | termios::IXOFF | termios::IXANY);
+            match flow_control {
+                FlowControl::None => {}
+                FlowControl::Software => termios.c_iflag |= termios::IXON | termios::IXOFF,
+                FlowControl::Hardware => termios.c_cflag |= CRTSCTS,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn speed(baud: u32) -> io::Result<termios::speed_t> {
+    baud_speed(baud).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unsupported baud rate {baud}"),
+        )
+    })
+}
@@ -1,30 +1,182 @@
-use std::{collections::VecDeque, fs::File, io::{self, Error, Read, Write}, os::fd::{AsRawFd, BorrowedFd, FromRawFd}, path::Path, time::Instant};
+use std::{fs::File, io::{self, Error, Read, Write}, os::{fd::{AsRawFd, BorrowedFd, FromRawFd, RawFd}, unix::fs::MetadataExt}, path::Path, time::{Duration, Instant}};
 
-use nix::{errno::Errno, poll::{PollFd, PollFlags, PollTimeout}};
+use nix::{errno::Errno, poll::{PollFd, PollFlags, PollTimeout}, sys::{signal::SigSet, time::TimeSpec}};
 use termios::Termios;
 
+use crate::error::ArbiterError;
+use crate::modem::port_cts;
+use crate::rx_buffer::RxBuffer;
+use crate::serial_config::SerialConfig;
+use crate::setserial::{port_set_serial, SerialOverrides};
+
+
+/// Closure adjusting the termios settings right before they are applied to the port.
+pub type TermiosHook = Box<dyn FnMut(&mut Termios) + Send>;
+
+
+/// Interval of checking the transmit queue while it is over the limit, see [`port_send`].
+const TX_QUEUE_POLL: Duration = Duration::from_millis(1);
+
+
+/// Number of wake-ups in a row after which a port which is ready but yields no
+/// data is considered revoked, e.g. a USB adapter which is going away.
+const MAX_IDLE_WAKEUPS: u32 = 100;
+
+
+/// Interval of checking whether the pump is still needed, see [`port_pump`].
+const PUMP_POLL: Duration = Duration::from_millis(10);
+
+
+/// Size of a single read of the pump, see [`port_pump`].
+const PUMP_CHUNK: usize = 64 * 1024;
+
+
+/// The port keeps waking up without any data, so it has to be closed rather
+/// than polled again, see [`is_revoked`].
+#[derive(Debug)]
+struct Revoked(&'static str);
+
+impl std::fmt::Display for Revoked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for Revoked {}
+
+fn revoked(reason: &'static str) -> Error {
+    Error::other(Revoked(reason))
+}
+
+
+/// Returns true if the error means that the device of the port is gone or failed
+/// for good: it was disconnected (POLLHUP), is in an error state (POLLERR, EIO),
+/// no longer exists (ENODEV, ENXIO) or keeps waking up without any data.
+pub fn is_revoked(err: &Error) -> bool {
+    let errno = err.raw_os_error().map(Errno::from_raw);
+    matches!(errno, Some(Errno::EIO | Errno::ENODEV | Errno::ENXIO))
+        || matches!(ArbiterError::of(err), Some(ArbiterError::Disconnected | ArbiterError::LinkError))
+        || err.get_ref().is_some_and(|inner| inner.is::<Revoked>())
+}
+
+
+/// Counts the wake-ups of a wait on the port which brought no progress.
+#[derive(Default)]
+struct IdleWakeups(u32);
+
+impl IdleWakeups {
+    /// Account a wake-up which transferred the given number of bytes. Fails once the
+    /// port woke up too many times in a row without transferring anything.
+    fn account(&mut self, transferred: usize) -> io::Result<()> {
+        if transferred > 0 {
+            self.0 = 0;
+            return Ok(());
+        }
+        self.0 += 1;
+        if self.0 >= MAX_IDLE_WAKEUPS {
+            return Err(revoked("The port keeps waking up without any data"));
+        }
+        Ok(())
+    }
+}
+
+
+/// How the terminal line discipline processes the data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "fleet", derive(serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum LineMode {
+    /// Raw mode as set up by `cfmakeraw`. Bytes are passed through unmodified.
+    #[default]
+    Raw,
+    /// Canonical mode. The kernel line discipline assembles the input into
+    /// lines (including its line editing and CR/NL mapping) and data becomes
+    /// readable only once a whole line has arrived. Local echo is disabled.
+    Canonical,
+}
+
+
+/// What the kernel does with a break condition or with a byte received with a parity or
+/// framing error, see [`Arbiter::set_break_input`](crate::Arbiter::set_break_input) and
+/// [`Arbiter::set_parity_error_input`](crate::Arbiter::set_parity_error_input).
+///
+/// The kernel marks both or neither, so while either is marked a break is read as
+/// `\xFF \0 \0` and an erroneous byte `X` as `\xFF \0 X`, and a genuine `\xFF` byte
+/// is doubled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputPolicy {
+    /// Drop it (IGNBRK, IGNPAR).
+    Ignore,
+    /// Deliver it marked (PARMRK).
+    Mark,
+    /// Deliver it as is: a break as a `\0` byte, and the byte with a parity error without
+    /// checking the parity at all (INPCK off).
+    Raw,
+}
+
+
+/// Settings applied every time the port is opened.
+#[derive(Debug, Clone, Default)]
+pub struct PortOptions {
+    pub mode: LineMode,
+    /// Open with O_SYNC, O_DSYNC and O_RSYNC.
+    pub sync_io: bool,
+    /// Open for reading only and leave the termios settings untouched.
+    pub read_only: bool,
+    /// Set up the port for 9-bit multidrop communication.
+    pub multidrop: bool,
+    /// Handling of a break condition, unchanged if None.
+    pub breaks: Option<InputPolicy>,
+    /// Handling of the bytes with parity errors, unchanged if None.
+    pub parity_errors: Option<InputPolicy>,
+    /// Ignore the modem control lines (CLOCAL) or honor the carrier, unchanged if None.
+    pub clocal: Option<bool>,
+    /// Drop DTR and RTS on the last close (HUPCL), unchanged if None.
+    pub hupcl: Option<bool>,
+    /// Baud rate, character format and flow control.
+    pub config: SerialConfig,
+    /// Latency timer of an FTDI adapter, in milliseconds.
+    pub latency_timer: Option<u8>,
+    /// Low level settings of the serial driver.
+    pub serial: SerialOverrides,
+}
+
+
+/// Failure of [`port_open`], telling the open(2) of the device apart from setting it up.
+pub enum PortOpenError {
+    /// The device could not be opened.
+    Open(io::Error),
+    /// The device opened but setting it up failed, and it was closed again.
+    Setup(io::Error),
+}
+
+
+impl From<PortOpenError> for io::Error {
+    fn from(err: PortOpenError) -> Self {
+        match err {
+            PortOpenError::Open(err) | PortOpenError::Setup(err) => err,
+        }
+    }
+}
+
 
 /// Open the file under the given path with flags specific for non blocking driect i/o access.
+///
+/// The optional hook runs after the line mode is set up and before the settings are applied.
+/// Returns the opened file together with the termios settings the port had before opening.
 /// 
 /// # Safety
 /// 
 /// The fd passed in is an owned file descriptor and it is open because
 /// we get the file descriptor from the fcntl::open function call.
-pub fn port_open(path: impl AsRef<Path>) -> io::Result<File> {
+pub fn port_open(path: impl AsRef<Path>, options: &PortOptions, hook: Option<&mut TermiosHook>) -> Result<(File, Termios), PortOpenError> {
     use nix::fcntl::OFlag;
     use nix::sys::stat::Mode;
 
-    let oflag = 
-        // Open for reading and writing.
-        OFlag::O_RDWR | 
+    let mut oflag = 
+        // Open for reading and writing, or for reading only when tapping a port owned by someone else.
+        if options.read_only { OFlag::O_RDONLY } else { OFlag::O_RDWR } | 
         // The file offset shall be set to the end of the file prior to each write.
         OFlag::O_APPEND | 
-        // Write I/O operations shall complete as defined by synchronized I/O data integrity completion
-        OFlag::O_DSYNC | 
-        // Read I/O operations shall complete as defined by synchronized I/O data integrity completion
-        OFlag::O_RSYNC |
-        // Write I/O operations shall complete as defined by synchronized I/O file integrity completion.
-        OFlag::O_SYNC |
         // open() shall not cause the terminal device to become the controlling terminal for the process.
         OFlag::O_NOCTTY |
         // File I/O is done directly to/from user-space buffers.
@@ -34,10 +186,39 @@ pub fn port_open(path: impl AsRef<Path>) -> io::Result<File> {
         OFlag::O_NDELAY;
         // The application need not specify the O_TTY_INIT flag when opening pseudo-terminals
 
-    let fd = nix::fcntl::open(path.as_ref(), oflag, Mode::empty())?;
+    if options.sync_io {
+        oflag |=
+            // Write I/O operations shall complete as defined by synchronized I/O data integrity completion
+            OFlag::O_DSYNC | 
+            // Read I/O operations shall complete as defined by synchronized I/O data integrity completion
+            OFlag::O_RSYNC |
+            // Write I/O operations shall complete as defined by synchronized I/O file integrity completion.
+            OFlag::O_SYNC;
+    }
+
+    let fd = nix::fcntl::open(path.as_ref(), oflag, Mode::empty())
+        .map_err(|errno| PortOpenError::Open(errno.into()))?;
+    // Owned right away, so the port is closed whichever step of the setup fails
+    let file = unsafe {
+        File::from_raw_fd(fd)
+    };
+    port_setup(file, path.as_ref(), options, hook).map_err(PortOpenError::Setup)
+}
+
+
+/// Set up the port just opened by [`port_open`]. Returns it together with the termios
+/// settings it had before.
+fn port_setup(file: File, path: &Path, options: &PortOptions, hook: Option<&mut TermiosHook>) -> io::Result<(File, Termios)> {
+    let fd = file.as_raw_fd();
 
     let mut termios = Termios::from_fd(fd)?;
     termios::tcgetattr(fd, &mut termios)?;
+    let original = termios;
+
+    if options.read_only {
+        // Never change the settings of a port which is only tapped
+        return Ok((file, original));
+    }
 
     // println!("Input modes: ");
     // println!("    BRKINT  [{}] Signal interrupt on break.", termios.c_iflag & termios::BRKINT as u32);
@@ -89,14 +270,117 @@ pub fn port_open(path: impl AsRef<Path>) -> io::Result<File> {
     // termios.c_lflag = 0x00000A30;
     // termios.c_cc = [3, 28, 127, 21, 4, 0, 1, 0, 17, 19, 26, 0, 18, 15, 23, 22, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
 
-    // termios::cfsetspeed(&mut termios, 19200)?;
-    termios::cfmakeraw(&mut termios);
+    match options.mode {
+        LineMode::Raw => {
+            termios::cfmakeraw(&mut termios);
+        },
+        LineMode::Canonical => {
+            termios.c_lflag |= termios::ICANON;
+            termios.c_lflag &= !(termios::ECHO | termios::ECHOE | termios::ECHOK | termios::ECHONL);
+        },
+    }
+    termios_input(&mut termios, options.breaks, options.parity_errors);
+    match options.clocal {
+        Some(true) => termios.c_cflag |= termios::CLOCAL,
+        Some(false) => termios.c_cflag &= !termios::CLOCAL,
+        None => {}
+    }
+    termios_hupcl(&mut termios, options.hupcl);
+    options.config.apply(&mut termios)?;
+    if options.multidrop {
+        termios_multidrop(&mut termios, true);
+    }
+    if options.serial.custom_divisor.is_some() {
+        // The custom divisor takes the place of the 38400 baud setting
+        termios::cfsetspeed(&mut termios, termios::B38400)?;
+    }
+    if let Some(hook) = hook {
+        hook(&mut termios);
+    }
     termios::tcsetattr(fd, termios::TCSANOW, &termios)?;
 
-    let file = unsafe {
-        File::from_raw_fd(fd)
+    if !options.serial.is_empty() {
+        if let Err(err) = port_set_serial(fd, &options.serial) {
+            // E.g. EPERM without CAP_SYS_ADMIN. Do not leave the settings half applied.
+            let _ = termios::tcsetattr(fd, termios::TCSANOW, &original);
+            return Err(err);
+        }
+    }
+
+    if let Some(latency) = options.latency_timer {
+        // Best effort. Without the permission to write the attribute the port works anyway.
+        let _ = port_set_latency_timer(path, latency);
+    }
+
+    Ok((file, original))
+}
+
+
+/// Make an already open port usable by the worker. It has to be non-blocking, or reads
+/// would hang once the received data is drained, so O_NONBLOCK is set if it is missing.
+/// With `raw` the port is switched to the raw mode, otherwise its settings are only
+/// checked not to echo the received data back to the device. Returns the termios
+/// settings the port had before.
+pub fn port_adopt(port: &File, raw: bool) -> io::Result<Termios> {
+    use nix::fcntl::{fcntl, FcntlArg, OFlag};
+
+    let fd = port.as_raw_fd();
+    let unsuitable = |reason: String| -> Error {
+        ArbiterError::UnsuitableFd { reason }.into()
     };
-    Ok(file)
+    let mut termios = Termios::from_fd(fd).map_err(|err| {
+        unsuitable(format!("Not a terminal: {err}"))
+    })?;
+    let original = termios;
+    if !raw && termios.c_lflag & termios::ECHO != 0 {
+        return Err(unsuitable("It echoes the received data back to the device".into()));
+    }
+
+    let flags = OFlag::from_bits_truncate(fcntl(fd, FcntlArg::F_GETFL)?);
+    if !flags.contains(OFlag::O_NONBLOCK) {
+        fcntl(fd, FcntlArg::F_SETFL(flags | OFlag::O_NONBLOCK)).map_err(|err| {
+            unsuitable(format!("Cannot make it non-blocking: {err}"))
+        })?;
+    }
+
+    if raw {
+        termios::cfmakeraw(&mut termios);
+        termios::tcsetattr(fd, termios::TCSANOW, &termios).map_err(|err| {
+            unsuitable(format!("Cannot switch it to the raw mode: {err}"))
+        })?;
+    }
+    Ok(original)
+}
+
+
+/// Set whether the modem control lines are dropped when the port is closed the last time.
+/// None leaves the flag as it is.
+pub fn termios_hupcl(termios: &mut Termios, hupcl: Option<bool>) {
+    match hupcl {
+        Some(true) => termios.c_cflag |= termios::HUPCL,
+        Some(false) => termios.c_cflag &= !termios::HUPCL,
+        None => {}
+    }
+}
+
+
+/// Returns true if the port ignores the modem control lines (CLOCAL).
+pub fn port_clocal(fd: RawFd) -> io::Result<bool> {
+    let termios = Termios::from_fd(fd)?;
+    Ok(termios.c_cflag & termios::CLOCAL != 0)
+}
+
+
+/// Returns true if the modem control lines are dropped on the last close (HUPCL).
+pub fn port_hupcl(fd: RawFd) -> io::Result<bool> {
+    let termios = Termios::from_fd(fd)?;
+    Ok(termios.c_cflag & termios::HUPCL != 0)
+}
+
+
+/// Apply the given termios settings to the port.
+pub fn port_set_termios(fd: RawFd, termios: &Termios) -> io::Result<()> {
+    termios::tcsetattr(fd, termios::TCSANOW, termios)
 }
 
 
@@ -105,6 +389,8 @@ pub fn port_open(path: impl AsRef<Path>) -> io::Result<File> {
 pub enum PollKind {
     ForRead,
     ForWrite,
+    /// For write without waking up on incoming data, e.g. when it is collected by another thread.
+    WriteOnly,
 }
 
 
@@ -130,14 +416,6 @@ pub fn port_poll(port: &File, poll: PollKind, deadline: Option<Instant>) -> io::
     let fd = unsafe {
         BorrowedFd::borrow_raw(port.as_raw_fd())
     };
-    let timeout = match deadline {
-        Some(deadline) => {
-            let now = Instant::now();
-            let time_left = deadline.saturating_duration_since(now);
-            PollTimeout::try_from(time_left).unwrap_or(PollTimeout::ZERO)
-        },
-        None => PollTimeout::ZERO,
-    };
     let input_flags = match poll {
         PollKind::ForRead => {
             PollFlags::POLLIN |
@@ -146,14 +424,36 @@ pub fn port_poll(port: &File, poll: PollKind, deadline: Option<Instant>) -> io::
             PollFlags::POLLRDBAND
         },
         PollKind::ForWrite => {
+            // Also wake up on incoming data, so it can be collected while waiting
+            // for the peer, which may itself wait for us to collect it.
+            PollFlags::POLLIN |
             PollFlags::POLLPRI |
             PollFlags::POLLOUT |
             PollFlags::POLLWRNORM |
             PollFlags::POLLWRBAND
         },
+        PollKind::WriteOnly => {
+            PollFlags::POLLOUT |
+            PollFlags::POLLWRNORM |
+            PollFlags::POLLWRBAND
+        },
     };
     let mut pollfd = [PollFd::new(fd, input_flags)];
-    let poll_result = nix::poll::poll(&mut pollfd, timeout);
+    // Block all signals while waiting. Pending signals get handled once the call returns,
+    // so they neither cut the wait short nor get lost.
+    let sigmask = SigSet::all();
+    let poll_result = loop {
+        let time_left = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => Duration::ZERO,
+        };
+        let timeout = TimeSpec::from_duration(time_left);
+        match nix::poll::ppoll(&mut pollfd, Some(timeout), Some(sigmask)) {
+            // Interrupted anyway (e.g. by SIGSTOP), so wait for the rest of the time
+            Err(Errno::EINTR) => continue,
+            result => break result,
+        }
+    };
     match poll_result {
         // Upon failure, poll() shall return -1 and set errno to indicate the error.
         Err(errno) => {
@@ -180,17 +480,17 @@ pub fn port_poll(port: &File, poll: PollKind, deadline: Option<Instant>) -> io::
 
             // Check for device disconnection
             if revents.intersects(PollFlags::POLLHUP) {
-                return Err(Error::other("POLLHUP: Device has been disconnected"));
+                return Err(ArbiterError::Disconnected.into());
             }
 
             // Check for invalid file descriptor
             if revents.intersects(PollFlags::POLLNVAL) {
-                return Err(Error::other("POLLNVAL: Invalid fd member"));
+                return Err(ArbiterError::InvalidFd.into());
             }
 
             // Check for poll errors
             if revents.intersects(PollFlags::POLLERR) {
-                return Err(Error::other("POLLERR: An error has occurred"));
+                return Err(ArbiterError::LinkError.into());
             }
 
             // Success - Write ready
@@ -219,69 +519,85 @@ pub fn port_poll(port: &File, poll: PollKind, deadline: Option<Instant>) -> io::
 }
 
 
-/// Read some data from the port. EOF, Interrupt and TimedOut errors are
-/// treated as not an error and an Ok variant is returned in such cases.
-pub fn port_read(port: &mut File, data: &mut VecDeque<u8>) -> io::Result<()> {
-    let mut buf = [0; 1024 * 1024];
-    loop {
-        match port.read(&mut buf) {
+/// Read some data from the port and return the number of bytes read. EOF, Interrupt
+/// and TimedOut errors are treated as not an error and an Ok variant is returned in such cases.
+///
+/// Reads in chunks of the read buffer of the Rx buffer until a chunk comes out short, i.e.
+/// what was waiting is read, or the read budget is spent, leaving the rest of the data in
+/// the port for later.
+pub fn port_read(port: &mut File, data: &mut RxBuffer) -> io::Result<usize> {
+    let mut buf = data.take_read_buf();
+    let budget = data.read_budget().unwrap_or(usize::MAX);
+    let mut total = 0;
+    let result = loop {
+        if total >= budget {
+            // Budget spent - The rest is read on the next turn
+            break Ok(total)
+        }
+        let len = buf.len().min(budget - total);
+        match port.read(&mut buf[..len]) {
             Ok(0) => {
                 // EOF - No more data
-                return Ok(())
+                break Ok(total)
             }
             Ok(n) => {
-                // OK - Data was read
+                // OK - Data was read. The chunk is stamped with the time of arrival.
                 data.extend(&buf[0..n]);
+                total += n;
+                if n < len {
+                    // Short read - What was waiting is read, newer data waits for the next turn
+                    break Ok(total)
+                }
             }
             Err(err) => match err.kind() {
                 io::ErrorKind::Interrupted => {
                     // Read interrupt - Ignored. This is not an error for our use case.
-                    return Ok(())
+                    break Ok(total)
                 },
                 io::ErrorKind::TimedOut => {
                     // Read timeout - Ignored. This is not an error for our use case.
-                    return Ok(())
+                    break Ok(total)
                 },
                 io::ErrorKind::WouldBlock => {
                     // Would block - Ignored. This is not an error for our use case.
-                    return Ok(())
+                    break Ok(total)
                 }
                 _ => {
                     // I/O Error
-                    return Err(err)
+                    break Err(err)
                 },
             },
         }
-    }
+    };
+    data.put_read_buf(buf);
+    result
 }
 
 
-/// Write some data to the port. EOF, Interrupt and TimedOut errors are
-/// treated as not an error and an Ok variant is returned in such cases.
-pub fn port_write(port: &mut File, data: &mut VecDeque<u8>) -> io::Result<()> {
-    let buf = data.make_contiguous();
-    match port.write(buf) {
+/// Write some data to the port and return the number of bytes written. EOF, Interrupt
+/// and TimedOut errors are treated as not an error and zero bytes written is returned.
+pub fn port_write(port: &mut File, data: &[u8]) -> io::Result<usize> {
+    match port.write(data) {
         Ok(0) => {
             // EOF - Ingored. This is not an error for our use case.
-            Ok(())
+            Ok(0)
         },
         Ok(n) => {
             // OK - Wrote some data
-            let _ = data.drain(0..n);
-            Ok(())
+            Ok(n)
         }
         Err(err) => match err.kind() {
             io::ErrorKind::Interrupted => {
                 // Write interrupt - Ignored. This is not an error for our use case.
-                Ok(())
+                Ok(0)
             },
             io::ErrorKind::TimedOut => {
                 // Write timeout - Ignored. This is not an error for our use case.
-                Ok(())
+                Ok(0)
             },
             io::ErrorKind::WouldBlock => {
                 // Would block - Ignored. This is not an error for our use case.
-                Ok(())
+                Ok(0)
             }
             _ => {
                 // I/O Error
@@ -292,46 +608,125 @@ pub fn port_write(port: &mut File, data: &mut VecDeque<u8>) -> io::Result<()> {
 }
 
 
-/// Send all data to the port or timeout
-pub fn port_send(port: &mut File, send: &[u8], recv: &mut VecDeque<u8>, deadline: Instant) -> io::Result<()> {
-    let mut send = VecDeque::from(send.to_vec());
+/// Send all data to the port or timeout.
+///
+/// The data received meanwhile is collected into the Rx buffer. The deadline is checked
+/// before every write, so neither collecting the data nor a busy port can extend the
+/// transmission past the deadline. Nothing is written once the deadline has passed.
+///
+/// The data is written straight from the given slice, as much as the port accepts at
+/// a time, so even multi-megabyte payloads are not copied.
+///
+/// With a queue limit, writing pauses while the kernel holds that many bytes queued for
+/// transmission (TIOCOUTQ), so the send completes only when at most the limit is left to
+/// trickle out. The limit is ignored if the driver does not report its queue.
+///
+/// The data received meanwhile is collected to `recv`, unless it is None because another
+/// thread reads the port, see [`port_pump`].
+pub fn port_send(port: &mut File, send: &[u8], mut recv: Option<&mut RxBuffer>, queue_limit: Option<usize>, deadline: Instant) -> io::Result<()> {
+    let mut sent = 0;
+    let mut idle = IdleWakeups::default();
+    let poll = match recv {
+        Some(_) => PollKind::ForWrite,
+        None => PollKind::WriteOnly,
+    };
 
     loop {
-        // Check if the port is ready
-        match port_poll(port, PollKind::ForWrite, Some(deadline))? {
+        // Check if we are done
+        if sent == send.len() {
+            return Ok(());
+        }
+
+        // Check if deadline has passed
+        if deadline <= Instant::now() {
+            return Err(send_timed_out(port, sent, None));
+        }
+
+        // Check if the port is ready. The output queue is looked at for the queue limit, or
+        // else only once the port is slow to get ready, to tell a stalled line from a slow one.
+        let (queued, ready) = match queue_limit {
+            Some(_) => (port_outq(port.as_raw_fd()).ok(), port_poll(port, poll, Some(deadline))?),
+            None => match port_poll(port, poll, Some(deadline.min(Instant::now() + TX_QUEUE_POLL)))? {
+                PollResult::TimedOut => (port_outq(port.as_raw_fd()).ok(), port_poll(port, poll, Some(deadline))?),
+                ready => (None, ready),
+            },
+        };
+        match ready {
             PollResult::TimedOut => {
-                // Deadline is reached. Ignore, we will check deadline manually.
-                // return Err(io::ErrorKind::TimedOut.into());
+                // Deadline is reached
+                return Err(send_timed_out(port, sent, queued));
             },
             PollResult::ReadReady => {
-                // The port has out of band data in rx buffer
-                port_read(port, recv)?;
+                // The port has data in rx buffer
+                match recv.as_deref_mut() {
+                    Some(recv) => idle.account(port_read(port, recv)?)?,
+                    None => idle.account(0)?,
+                }
             },
             PollResult::WriteReady => {
-                // The port is ready for sending data
-                port_write(port, &mut send)?;
+                // The port is ready for sending data, as much as the queue limit leaves room for
+                let room = match (queue_limit, queued) {
+                    (Some(limit), Some(queued)) => limit.saturating_sub(queued),
+                    _ => send.len(),
+                };
+                if room == 0 {
+                    // Collect incoming data while the queue drains
+                    let wait = deadline.min(Instant::now() + TX_QUEUE_POLL);
+                    match recv.as_deref_mut() {
+                        Some(recv) => {
+                            if port_poll(port, PollKind::ForRead, Some(wait))? == PollResult::ReadReady {
+                                idle.account(port_read(port, recv)?)?;
+                            }
+                        }
+                        None => std::thread::sleep(wait.saturating_duration_since(Instant::now())),
+                    }
+                    continue;
+                }
+                let end = send.len().min(sent + room);
+                let written = port_write(port, &send[sent..end])?;
+                sent += written;
+                idle.account(written)?;
             },
             PollResult::Undocumented => {
                 // The poll result has an undocumented value
                 // eprintln!("WARNING: The result value of the `poll` syscall is unexpected / undocumented");
+                idle.account(0)?;
             }
         }
+    }
+}
 
-        // Check if we are done
-        if send.is_empty() {
-            return Ok(());
-        }
 
-        // Check if deadline has passed
-        if deadline <= Instant::now() {
-            return Err(io::ErrorKind::TimedOut.into());
-        }
+/// The error of a transmission which did not complete before the deadline.
+///
+/// The port is reported as blocked by the flow control rather than as timed
+/// out if the data queued for transmission did not drain at all while
+/// waiting (`queued` is the size of the queue when the wait started), or if
+/// the hardware flow control is enabled and the CTS line is deasserted.
+/// A slow but moving transmission is a generic timeout.
+fn send_timed_out(port: &File, sent: usize, queued: Option<usize>) -> io::Error {
+    let fd = port.as_raw_fd();
+    let cts = port_cts(fd).ok();
+    let rtscts = Termios::from_fd(fd).is_ok_and(|termios| termios.c_cflag & termios::os::linux::CRTSCTS != 0);
+    let stalled = match (queued, port_outq(fd)) {
+        (Some(before), Ok(now)) => now > 0 && now >= before,
+        _ => false,
+    };
+    if stalled || (rtscts && cts == Some(false)) {
+        ArbiterError::FlowControlBlocked { sent, cts }.into()
+    } else {
+        io::ErrorKind::TimedOut.into()
     }
 }
 
 
 /// Receive data from the port until a given byte or until deadline.
-pub fn port_recv(port: &mut File, buff: &mut VecDeque<u8>, until: Option<u8>, deadline: Option<Instant>) -> io::Result<()> {
+///
+/// Without a deadline only the data which is already there is collected, up to the read
+/// budget of the Rx buffer, so a device which never stops sending cannot hold it forever.
+pub fn port_recv(port: &mut File, buff: &mut RxBuffer, until: Option<u8>, deadline: Option<Instant>) -> io::Result<()> {
+    let mut idle = IdleWakeups::default();
+    let mut total = 0;
     loop {
         // Check if the port is ready
         match port_poll(port, PollKind::ForRead, deadline)? {
@@ -339,21 +734,362 @@ pub fn port_recv(port: &mut File, buff: &mut VecDeque<u8>, until: Option<u8>, de
                 return Ok(());
             },
             PollResult::ReadReady => {
-                port_read(port, buff)?;
+                let read = port_read(port, buff)?;
+                idle.account(read)?;
+                total += read;
+                if deadline.is_none() && buff.read_budget().is_some_and(|budget| total >= budget) {
+                    // Budget spent - The rest is collected on the next turn
+                    return Ok(());
+                }
             },
             PollResult::WriteReady => {
                 // eprintln!("WARNING: PollKind was ForRead but got PollResult WriteReady");
+                idle.account(0)?;
             }
             PollResult::Undocumented => {
                 // The poll result has an undocumented value
                 // eprintln!("WARNING: The result value of the `poll` syscall is unexpected / undocumented");
+                idle.account(0)?;
             }
         }
 
         if let Some(delimiter) = until {
-            if buff.make_contiguous().contains(&delimiter) {
+            if buff.contains(delimiter) {
                 return Ok(());
             }
         }
     }
 }
+
+
+/// Collect the data which is already waiting at the port, like `port_recv` without a deadline,
+/// but give way as soon as `preempt` returns true. It is asked after every wake-up, so other
+/// work need not wait while a device keeps sending.
+pub fn port_collect(port: &mut File, buff: &mut RxBuffer, mut preempt: impl FnMut() -> bool) -> io::Result<()> {
+    let mut idle = IdleWakeups::default();
+    let mut total = 0;
+    loop {
+        match port_poll(port, PollKind::ForRead, None)? {
+            PollResult::TimedOut => {
+                return Ok(());
+            },
+            PollResult::ReadReady => {
+                let read = port_read(port, buff)?;
+                idle.account(read)?;
+                total += read;
+                if buff.read_budget().is_some_and(|budget| total >= budget) {
+                    // Budget spent - The rest is collected on the next turn
+                    return Ok(());
+                }
+            },
+            PollResult::WriteReady | PollResult::Undocumented => {
+                idle.account(0)?;
+            }
+        }
+
+        if preempt() {
+            return Ok(());
+        }
+    }
+}
+
+
+/// Copy the data received by the port to the pipe for as long as `running` returns true,
+/// for the port to be read on another thread. The pipe is written as far as it has room,
+/// so a reader which falls behind holds the data back in the port rather than losing it.
+/// Whatever was read is written out before returning, so the reader has to keep reading
+/// until this returns.
+pub fn port_pump(port: &mut File, pipe: &mut File, running: impl Fn() -> bool) -> io::Result<()> {
+    let mut buf = vec![0; PUMP_CHUNK];
+    let mut idle = IdleWakeups::default();
+    while running() {
+        match port_poll(port, PollKind::ForRead, Some(Instant::now() + PUMP_POLL))? {
+            PollResult::TimedOut => {}
+            PollResult::ReadReady => {
+                let read = match port.read(&mut buf) {
+                    Ok(n) => n,
+                    Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted) => 0,
+                    Err(err) => return Err(err),
+                };
+                idle.account(read)?;
+                let mut written = 0;
+                while written < read {
+                    if port_poll(pipe, PollKind::WriteOnly, Some(Instant::now() + PUMP_POLL))? == PollResult::WriteReady {
+                        written += port_write(pipe, &buf[written..read])?;
+                    }
+                }
+            },
+            PollResult::WriteReady | PollResult::Undocumented => {
+                idle.account(0)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+
+/// Receive data from the port until the buffer holds at least `len` bytes or until deadline.
+pub fn port_recv_len(port: &mut File, buff: &mut RxBuffer, len: usize, deadline: Instant) -> io::Result<()> {
+    let mut idle = IdleWakeups::default();
+    while buff.len() < len {
+        // Check if the port is ready
+        match port_poll(port, PollKind::ForRead, Some(deadline))? {
+            PollResult::TimedOut => {
+                return Ok(());
+            },
+            PollResult::ReadReady => {
+                idle.account(port_read(port, buff)?)?;
+            },
+            PollResult::WriteReady => {
+                // eprintln!("WARNING: PollKind was ForRead but got PollResult WriteReady");
+                idle.account(0)?;
+            }
+            PollResult::Undocumented => {
+                // The poll result has an undocumented value
+                // eprintln!("WARNING: The result value of the `poll` syscall is unexpected / undocumented");
+                idle.account(0)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+
+/// Wait until all data written to the port has been transmitted.
+pub fn port_drain(port: &File) -> io::Result<()> {
+    termios::tcdrain(port.as_raw_fd())
+}
+
+
+nix::ioctl_read_bad!(tiocoutq, nix::libc::TIOCOUTQ, std::ffi::c_int);
+
+
+/// Wait until the data written to the port has been transmitted, collecting the incoming
+/// data meanwhile unless `recv` is None.
+///
+/// The output queue is watched until it is empty, which the deadline is kept for. Then only
+/// the few characters in the FIFO of the UART are left, which `tcdrain` waits for.
+pub fn port_flush(port: &mut File, mut recv: Option<&mut RxBuffer>, deadline: Instant) -> io::Result<()> {
+    let queued = port_outq(port.as_raw_fd())?;
+    loop {
+        if port_outq(port.as_raw_fd())? == 0 {
+            return port_drain(port);
+        }
+        if deadline <= Instant::now() {
+            return Err(send_timed_out(port, 0, Some(queued)));
+        }
+        let wait = deadline.min(Instant::now() + TX_QUEUE_POLL);
+        match recv.as_deref_mut() {
+            Some(recv) => {
+                if port_poll(port, PollKind::ForRead, Some(wait))? == PollResult::ReadReady {
+                    port_read(port, recv)?;
+                }
+            }
+            None => std::thread::sleep(wait.saturating_duration_since(Instant::now())),
+        }
+    }
+}
+
+/// Number of bytes written to the port and not transmitted yet.
+pub fn port_outq(fd: RawFd) -> io::Result<usize> {
+    let mut count = 0;
+    unsafe { tiocoutq(fd, &mut count) }?;
+    Ok(count as usize)
+}
+
+
+/// Set up the termios flags handling the break conditions and the bytes with parity errors.
+/// A None policy leaves the flags it concerns as they are.
+pub fn termios_input(termios: &mut Termios, breaks: Option<InputPolicy>, parity_errors: Option<InputPolicy>) {
+    if let Some(breaks) = breaks {
+        // Never send SIGINT to the foreground process group on a break
+        termios.c_iflag &= !(termios::IGNBRK | termios::BRKINT);
+        if breaks == InputPolicy::Ignore {
+            termios.c_iflag |= termios::IGNBRK;
+        }
+    }
+    if let Some(parity_errors) = parity_errors {
+        termios.c_iflag &= !(termios::IGNPAR | termios::INPCK);
+        match parity_errors {
+            InputPolicy::Ignore => termios.c_iflag |= termios::INPCK | termios::IGNPAR,
+            InputPolicy::Mark => termios.c_iflag |= termios::INPCK,
+            InputPolicy::Raw => {}
+        }
+    }
+    let marked = [breaks, parity_errors].contains(&Some(InputPolicy::Mark));
+    let unmarked = [breaks, parity_errors].iter().all(|policy| policy.is_some_and(|policy| policy != InputPolicy::Mark));
+    if marked {
+        // A stripped \xFF byte could not be told from a mark
+        termios.c_iflag |= termios::PARMRK;
+        termios.c_iflag &= !termios::ISTRIP;
+    } else if unmarked {
+        termios.c_iflag &= !termios::PARMRK;
+    }
+}
+
+
+/// Set up (or tear down) the termios flags for 9-bit multidrop communication.
+/// 
+/// The 9th bit is carried by the parity bit in stick parity mode. Data is received with
+/// space parity and parity errors are marked, so the bytes with the 9th bit set stand out.
+pub fn termios_multidrop(termios: &mut Termios, enabled: bool) {
+    use termios::os::linux::CMSPAR;
+
+    let cflags = termios::PARENB | CMSPAR;
+    let iflags = termios::INPCK | termios::PARMRK;
+    if enabled {
+        termios.c_cflag |= cflags;
+        termios.c_cflag &= !termios::PARODD;
+        termios.c_iflag |= iflags;
+        termios.c_iflag &= !(termios::IGNPAR | termios::ISTRIP);
+    } else {
+        termios.c_cflag &= !(cflags | termios::PARODD);
+        termios.c_iflag &= !iflags;
+    }
+}
+
+
+/// Apply or remove the 9-bit multidrop settings on an open port.
+pub fn port_set_multidrop(port: &File, enabled: bool) -> io::Result<()> {
+    let fd = port.as_raw_fd();
+    let mut termios = Termios::from_fd(fd)?;
+    termios_multidrop(&mut termios, enabled);
+    termios::tcsetattr(fd, termios::TCSADRAIN, &termios)
+}
+
+
+/// Set the 9th bit of the bytes transmitted from now on (mark or space parity).
+/// Bytes which are already queued are transmitted with the previous setting.
+pub fn port_set_9th_bit(port: &File, bit: bool) -> io::Result<()> {
+    let fd = port.as_raw_fd();
+    let mut termios = Termios::from_fd(fd)?;
+    if bit {
+        termios.c_cflag |= termios::PARODD;
+    } else {
+        termios.c_cflag &= !termios::PARODD;
+    }
+    termios::tcsetattr(fd, termios::TCSADRAIN, &termios)
+}
+
+
+/// The standard baud rates and their termios speed codes.
+const BAUD_RATES: [(termios::speed_t, u32); 30] = {
+    use termios::os::linux;
+    [
+        (termios::B50, 50),
+        (termios::B75, 75),
+        (termios::B110, 110),
+        (termios::B134, 134),
+        (termios::B150, 150),
+        (termios::B200, 200),
+        (termios::B300, 300),
+        (termios::B600, 600),
+        (termios::B1200, 1200),
+        (termios::B1800, 1800),
+        (termios::B2400, 2400),
+        (termios::B4800, 4800),
+        (termios::B9600, 9600),
+        (termios::B19200, 19200),
+        (termios::B38400, 38400),
+        (linux::B57600, 57600),
+        (linux::B115200, 115200),
+        (linux::B230400, 230400),
+        (linux::B460800, 460800),
+        (linux::B500000, 500000),
+        (linux::B576000, 576000),
+        (linux::B921600, 921600),
+        (linux::B1000000, 1000000),
+        (linux::B1152000, 1152000),
+        (linux::B1500000, 1500000),
+        (linux::B2000000, 2000000),
+        (linux::B2500000, 2500000),
+        (linux::B3000000, 3000000),
+        (linux::B3500000, 3500000),
+        (linux::B4000000, 4000000),
+    ]
+};
+
+
+/// Translate the termios speed code to the baud rate.
+pub fn baud_rate(speed: termios::speed_t) -> Option<u32> {
+    BAUD_RATES.iter().find(|(code, _)| *code == speed).map(|(_, baud)| *baud)
+}
+
+
+/// Translate the baud rate to the termios speed code, e.g. for `cfsetspeed`.
+/// Returns None if the baud rate is not one of the standard ones.
+pub fn baud_speed(baud: u32) -> Option<termios::speed_t> {
+    BAUD_RATES.iter().find(|(_, rate)| *rate == baud).map(|(code, _)| *code)
+}
+
+
+/// The baud rate of the port. None if it is not one of the standard ones.
+pub fn port_baud(port: &File) -> io::Result<Option<u32>> {
+    let termios = Termios::from_fd(port.as_raw_fd())?;
+    Ok(baud_rate(termios::cfgetospeed(&termios)))
+}
+
+
+/// Number of characters per second the port transfers with its current settings,
+/// counting the start, parity and stop bits. None if the baud rate is unknown.
+pub fn port_char_rate(port: &File) -> io::Result<Option<f64>> {
+    let termios = Termios::from_fd(port.as_raw_fd())?;
+    let Some(baud) = baud_rate(termios::cfgetospeed(&termios)) else {
+        return Ok(None);
+    };
+    let data_bits = match termios.c_cflag & termios::CSIZE {
+        termios::CS5 => 5,
+        termios::CS6 => 6,
+        termios::CS7 => 7,
+        _ => 8,
+    };
+    let parity_bits = if termios.c_cflag & termios::PARENB != 0 { 1 } else { 0 };
+    let stop_bits = if termios.c_cflag & termios::CSTOPB != 0 { 2 } else { 1 };
+    let char_bits = 1 + data_bits + parity_bits + stop_bits;
+    Ok(Some(baud as f64 / char_bits as f64))
+}
+
+
+/// Check if the device of the port is still alive: the file reports no hangup or error
+/// and the device node under the path is still the device the port was opened on.
+/// Some USB-serial drivers keep accepting writes on the file of an unplugged device,
+/// while its node is removed or recreated for a newly attached device.
+pub fn port_is_alive(fd: RawFd, path: &Path) -> io::Result<bool> {
+    // Hangups and errors are always reported, even if no events are requested
+    let mut pollfd = [PollFd::new(unsafe { BorrowedFd::borrow_raw(fd) }, PollFlags::empty())];
+    nix::poll::poll(&mut pollfd, PollTimeout::ZERO)?;
+    let failed = PollFlags::POLLHUP | PollFlags::POLLERR | PollFlags::POLLNVAL;
+    if pollfd[0].revents().is_some_and(|revents| revents.intersects(failed)) {
+        return Ok(false);
+    }
+
+    let opened = nix::sys::stat::fstat(fd)?;
+    match std::fs::metadata(path) {
+        Ok(current) => {
+            Ok(current.ino() == opened.st_ino && current.rdev() == opened.st_rdev)
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            Ok(false)
+        },
+        Err(err) => {
+            Err(err)
+        },
+    }
+}
+
+
+/// Set the latency timer of an FTDI USB-serial adapter through sysfs.
+/// The adapter holds received data for up to this many milliseconds (16 by default)
+/// before passing it to the host. Returns false if the device is not an FTDI adapter.
+pub fn port_set_latency_timer(path: &Path, latency: u8) -> io::Result<bool> {
+    let device = std::fs::canonicalize(path)?;
+    let Some(name) = device.file_name() else {
+        return Ok(false);
+    };
+    let attribute = Path::new("/sys/bus/usb-serial/devices").join(name).join("latency_timer");
+    if !attribute.exists() {
+        return Ok(false);
+    }
+    std::fs::write(attribute, latency.to_string())?;
+    Ok(true)
+}
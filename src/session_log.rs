@@ -0,0 +1,325 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Longest time a repeated line is held back before the number of its
+/// repetitions is written.
+const MAX_REPEAT_HOLD: Duration = Duration::from_secs(10);
+
+/// How the traffic is written to the session log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Printable ASCII as is, everything else escaped, e.g. `OK\r\n` or `\x02`.
+    #[default]
+    Text,
+    /// Every byte as two hex digits, e.g. `4F 4B 0D 0A`.
+    Hex,
+}
+
+/// Direction of the logged traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Tx,
+    Rx,
+}
+
+/// Writes the traffic of a port to a file, one line per chunk:
+/// the UNIX time in microseconds, the direction and the data, e.g.
+/// `1760522400.123456 TX AT\r\n`.
+///
+/// Once the file grows over the size limit it is rotated: the file is renamed
+/// with a `.1` suffix (shifting older files up to the number kept) and a new
+/// file is started.
+///
+/// To keep a device which repeats the same frame thousands of times a second
+/// from filling the disk, the log can write one line per frame and collapse
+/// the repeated lines into `last message repeated N times`
+/// ([`SessionLog::with_frames`], [`SessionLog::with_dedup`]), and cap the
+/// number of lines written per time window ([`SessionLog::with_rate_limit`]).
+pub struct SessionLog {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    format: LogFormat,
+    max_size: u64,
+    keep: usize,
+    /// Lines end at this byte rather than with every chunk.
+    delimiter: Option<u8>,
+    /// Unfinished frames of both directions.
+    partial: [Vec<u8>; 2],
+    dedup: bool,
+    /// The last line written, with its direction and tag.
+    last: Option<(Direction, Option<String>, Vec<u8>)>,
+    /// Repetitions of the last line not written, and the time of the first one.
+    repeats: Option<(u64, Instant)>,
+    rate_limit: Option<RateLimit>,
+}
+
+/// Cap on the number of lines written per time window.
+struct RateLimit {
+    max_lines: u32,
+    window: Duration,
+    window_start: Instant,
+    lines: u32,
+    suppressed: u64,
+}
+
+impl SessionLog {
+    /// Creates the log, appending to the file if it exists.
+    /// Rotates at 10 MiB keeping 5 old files by default.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            written,
+            format: LogFormat::default(),
+            max_size: 10 * 1024 * 1024,
+            keep: 5,
+            delimiter: None,
+            partial: [Vec::new(), Vec::new()],
+            dedup: false,
+            last: None,
+            repeats: None,
+            rate_limit: None,
+        })
+    }
+
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the size in bytes after which the file is rotated.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets the number of rotated files kept. Zero drops the old log on rotation.
+    pub fn with_keep(mut self, keep: usize) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// Writes one line per frame ending with the delimiter (included) rather
+    /// than one per chunk. A frame is written once it is complete.
+    pub fn with_frames(mut self, delimiter: u8) -> Self {
+        self.delimiter = Some(delimiter);
+        self
+    }
+
+    /// Writes a line identical to the previous one (including the direction
+    /// and the tag) only as `last message repeated N times`, once another
+    /// line comes or at least every 10 seconds.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Writes at most `max_lines` lines of traffic per time window. The lines
+    /// over the limit are dropped and counted in a `N lines suppressed` line
+    /// written when the next window starts.
+    pub fn with_rate_limit(mut self, max_lines: u32, window: Duration) -> Self {
+        self.rate_limit = Some(RateLimit {
+            max_lines,
+            window,
+            window_start: Instant::now(),
+            lines: 0,
+            suppressed: 0,
+        });
+        self
+    }
+
+    /// Append a chunk of the traffic.
+    pub fn write(&mut self, direction: Direction, data: &[u8]) -> io::Result<()> {
+        self.write_tagged(direction, None, data)
+    }
+
+    /// Append a chunk of the traffic of the request with the given tag,
+    /// which is written in brackets before the data.
+    pub fn write_tagged(
+        &mut self,
+        direction: Direction,
+        request: Option<&str>,
+        data: &[u8],
+    ) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let Some(delimiter) = self.delimiter else {
+            return self.write_frame(direction, request, data);
+        };
+        let index = match direction {
+            Direction::Tx => 0,
+            Direction::Rx => 1,
+        };
+        let mut partial = std::mem::take(&mut self.partial[index]);
+        let mut result = Ok(());
+        for frame in data.split_inclusive(|&byte| byte == delimiter) {
+            partial.extend_from_slice(frame);
+            if frame.ends_with(&[delimiter]) {
+                result = result.and(self.write_frame(direction, request, &partial));
+                partial.clear();
+            }
+        }
+        self.partial[index] = partial;
+        result
+    }
+
+    /// Write a line of traffic, unless it repeats the last one or is over
+    /// the rate limit.
+    fn write_frame(
+        &mut self,
+        direction: Direction,
+        request: Option<&str>,
+        data: &[u8],
+    ) -> io::Result<()> {
+        if self.dedup {
+            let repeated =
+                self.last
+                    .as_ref()
+                    .is_some_and(|(last_direction, last_request, last_data)| {
+                        *last_direction == direction
+                            && last_request.as_deref() == request
+                            && last_data == data
+                    });
+            if repeated {
+                let (count, since) = self.repeats.get_or_insert((0, Instant::now()));
+                *count += 1;
+                if since.elapsed() < MAX_REPEAT_HOLD {
+                    return Ok(());
+                }
+            }
+            self.write_repeats()?;
+            if repeated {
+                return Ok(());
+            }
+        }
+        if let Some(limit) = &mut self.rate_limit {
+            if limit.window_start.elapsed() >= limit.window {
+                let suppressed = std::mem::take(&mut limit.suppressed);
+                limit.window_start = Instant::now();
+                limit.lines = 0;
+                if suppressed > 0 {
+                    self.write_note(&format!("{suppressed} lines suppressed"))?;
+                }
+            }
+        }
+        if let Some(limit) = &mut self.rate_limit {
+            if limit.lines >= limit.max_lines {
+                limit.suppressed += 1;
+                return Ok(());
+            }
+            limit.lines += 1;
+        }
+        if self.dedup {
+            self.last = Some((direction, request.map(str::to_string), data.to_vec()));
+        }
+        self.write_traffic(direction, request, data)
+    }
+
+    /// Write the number of repetitions of the last line held back, if any.
+    fn write_repeats(&mut self) -> io::Result<()> {
+        match self.repeats.take() {
+            Some((count, _)) => self.write_note(&format!("last message repeated {count} times")),
+            None => Ok(()),
+        }
+    }
+
+    /// Write a line about the log itself.
+    fn write_note(&mut self, note: &str) -> io::Result<()> {
+        let mut line = timestamp();
+        write!(line, " -- {note}").unwrap();
+        self.write_line(line)
+    }
+
+    fn write_traffic(
+        &mut self,
+        direction: Direction,
+        request: Option<&str>,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let tag = match direction {
+            Direction::Tx => "TX",
+            Direction::Rx => "RX",
+        };
+        let mut line = format!("{} {tag} ", timestamp());
+        if let Some(request) = request {
+            write!(line, "[{request}] ").unwrap();
+        }
+        match self.format {
+            LogFormat::Text => {
+                for &byte in data {
+                    match byte {
+                        b'\r' => line.push_str("\\r"),
+                        b'\n' => line.push_str("\\n"),
+                        b'\t' => line.push_str("\\t"),
+                        b'\\' => line.push_str("\\\\"),
+                        0x20..=0x7E => line.push(byte as char),
+                        _ => write!(line, "\\x{byte:02X}").unwrap(),
+                    }
+                }
+            }
+            LogFormat::Hex => {
+                for (idx, byte) in data.iter().enumerate() {
+                    let sep = if idx == 0 { "" } else { " " };
+                    write!(line, "{sep}{byte:02X}").unwrap();
+                }
+            }
+        }
+        self.write_line(line)
+    }
+
+    fn write_line(&mut self, mut line: String) -> io::Result<()> {
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.written += line.len() as u64;
+        if self.written >= self.max_size {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |idx: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{idx}"));
+            PathBuf::from(name)
+        };
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for idx in (1..self.keep).rev() {
+                let _ = fs::rename(rotated(idx), rotated(idx + 1));
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Drop for SessionLog {
+    fn drop(&mut self) {
+        let _ = self.write_repeats();
+    }
+}
+
+/// The current UNIX time in microseconds, e.g. `1760522400.123456`.
+fn timestamp() -> String {
+    let time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}.{:06}", time.as_secs(), time.subsec_micros())
+}
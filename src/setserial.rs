@@ -0,0 +1,108 @@
+use std::{
+    ffi::{c_char, c_int, c_uint, c_ulong, c_ushort},
+    io,
+    mem::MaybeUninit,
+    os::fd::RawFd,
+};
+
+use nix::libc;
+
+/// Mask of the flags selecting the speed of the 38400 baud setting.
+const ASYNC_SPD_MASK: c_int = 0x1030;
+/// The 38400 baud setting uses `baud_base / custom_divisor` instead.
+const ASYNC_SPD_CUST: c_int = 0x0030;
+
+/// `struct serial_struct` from `linux/serial.h`.
+#[repr(C)]
+struct SerialStruct {
+    kind: c_int,
+    line: c_int,
+    port: c_uint,
+    irq: c_int,
+    flags: c_int,
+    xmit_fifo_size: c_int,
+    custom_divisor: c_int,
+    baud_base: c_int,
+    close_delay: c_ushort,
+    io_type: c_char,
+    reserved_char: [c_char; 1],
+    hub6: c_int,
+    closing_wait: c_ushort,
+    closing_wait2: c_ushort,
+    iomem_base: *mut u8,
+    iomem_reg_shift: c_ushort,
+    port_high: c_uint,
+    iomap_base: c_ulong,
+}
+
+nix::ioctl_read_bad!(tiocgserial, libc::TIOCGSERIAL, SerialStruct);
+nix::ioctl_write_ptr_bad!(tiocsserial, libc::TIOCSSERIAL, SerialStruct);
+
+/// Low level settings of the serial driver, as shown by `setserial`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialInfo {
+    /// Type of the UART, e.g. 4 for a 16550A (`PORT_16550A`).
+    pub uart_type: i32,
+    /// Frequency of the UART clock divided by 16, i.e. the highest baud rate.
+    pub baud_base: i32,
+    /// Divisor of `baud_base` used instead of 38400 baud when `spd_cust` is set.
+    pub custom_divisor: i32,
+    /// The `ASYNC_*` flags of the port.
+    pub flags: i32,
+}
+
+impl SerialInfo {
+    /// Returns true if the 38400 baud setting is replaced by the custom divisor.
+    pub fn spd_cust(&self) -> bool {
+        self.flags & ASYNC_SPD_MASK == ASYNC_SPD_CUST
+    }
+
+    /// The baud rate resulting from the custom divisor.
+    pub fn custom_baud(&self) -> Option<f64> {
+        (self.custom_divisor > 0).then(|| self.baud_base as f64 / self.custom_divisor as f64)
+    }
+}
+
+/// Settings of the serial driver applied every time the port is opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerialOverrides {
+    pub custom_divisor: Option<u32>,
+    pub uart_type: Option<i32>,
+}
+
+impl SerialOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.custom_divisor.is_none() && self.uart_type.is_none()
+    }
+}
+
+fn get_serial(fd: RawFd) -> io::Result<SerialStruct> {
+    let mut serial = MaybeUninit::<SerialStruct>::zeroed();
+    unsafe { tiocgserial(fd, serial.as_mut_ptr()) }?;
+    Ok(unsafe { serial.assume_init() })
+}
+
+/// Read the low level settings of the serial driver.
+pub fn port_serial_info(fd: RawFd) -> io::Result<SerialInfo> {
+    let serial = get_serial(fd)?;
+    Ok(SerialInfo {
+        uart_type: serial.kind,
+        baud_base: serial.baud_base,
+        custom_divisor: serial.custom_divisor,
+        flags: serial.flags,
+    })
+}
+
+/// Apply the overrides of the low level settings of the serial driver.
+pub fn port_set_serial(fd: RawFd, overrides: &SerialOverrides) -> io::Result<()> {
+    let mut serial = get_serial(fd)?;
+    if let Some(divisor) = overrides.custom_divisor {
+        serial.custom_divisor = divisor as c_int;
+        serial.flags = (serial.flags & !ASYNC_SPD_MASK) | ASYNC_SPD_CUST;
+    }
+    if let Some(uart_type) = overrides.uart_type {
+        serial.kind = uart_type;
+    }
+    unsafe { tiocsserial(fd, &serial) }?;
+    Ok(())
+}
@@ -0,0 +1,12 @@
+use std::time::Duration;
+
+/// What happens to the requests pending when the arbiter shuts down, see
+/// [`Arbiter::shutdown`](crate::Arbiter::shutdown).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownPolicy {
+    /// Fail them with [`ArbiterError::ShuttingDown`](crate::ArbiterError::ShuttingDown).
+    FailPending,
+    /// Serve those made before the shutdown, starting none of them after
+    /// the grace period, and fail the rest.
+    Drain(Duration),
+}
@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use nix::time::{clock_gettime, ClockId};
+
+/// Suspends shorter than this are not reported, e.g. the clocks read in
+/// between a preemption.
+const MIN_SUSPEND: Duration = Duration::from_secs(1);
+
+/// Detects that the system was suspended, as the time spent suspended counts
+/// in `CLOCK_BOOTTIME` but not in `CLOCK_MONOTONIC`, and is otherwise the
+/// same for both.
+pub struct SuspendDetector {
+    offset: Duration,
+}
+
+impl SuspendDetector {
+    pub fn new() -> Self {
+        Self {
+            offset: clock_offset(),
+        }
+    }
+
+    /// Returns the time spent suspended since the last call, if any.
+    pub fn check(&mut self) -> Option<Duration> {
+        let offset = clock_offset();
+        let suspended = offset.saturating_sub(self.offset);
+        if suspended < MIN_SUSPEND {
+            return None;
+        }
+        self.offset = offset;
+        Some(suspended)
+    }
+}
+
+/// Time of `CLOCK_BOOTTIME` ahead of `CLOCK_MONOTONIC`.
+fn clock_offset() -> Duration {
+    let read = |clock| clock_gettime(clock).map(Duration::from).unwrap_or_default();
+    read(ClockId::CLOCK_BOOTTIME).saturating_sub(read(ClockId::CLOCK_MONOTONIC))
+}
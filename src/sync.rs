@@ -0,0 +1,202 @@
+//! Locks, atomics and channels shared between the worker and the callers,
+//! swapped for the loom model checker when built with `RUSTFLAGS="--cfg loom"`:
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg loom" cargo test --lib --release loom
+//! ```
+
+#[cfg(loom)]
+pub use loom::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize},
+    Mutex,
+};
+#[cfg(not(loom))]
+pub use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize},
+    Mutex,
+};
+
+/// The channels of [`crate::channel`] under loom. Loom has no time, so a
+/// timeout is taken to run out whenever the call would have to wait.
+#[cfg(loom)]
+pub mod channel {
+    use std::{collections::VecDeque, time::Duration};
+
+    use loom::sync::{Arc, Condvar, Mutex};
+
+    use crate::channel::SendTimeoutError;
+    pub use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+
+    struct State<T> {
+        queue: VecDeque<T>,
+        /// None for an unbounded channel.
+        cap: Option<usize>,
+        senders: usize,
+        receiver: bool,
+        /// Receivers blocked in `recv`, which a zero capacity channel hands
+        /// the messages over to.
+        waiting: usize,
+    }
+
+    impl<T> State<T> {
+        fn has_room(&self) -> bool {
+            match self.cap {
+                None => true,
+                Some(cap) => self.queue.len() < cap.max(self.waiting),
+            }
+        }
+    }
+
+    struct Shared<T> {
+        state: Mutex<State<T>>,
+        changed: Condvar,
+    }
+
+    pub struct Sender<T>(Arc<Shared<T>>);
+
+    pub struct Receiver<T>(Arc<Shared<T>>);
+
+    fn channel<T>(cap: Option<usize>) -> (Sender<T>, Receiver<T>) {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                cap,
+                senders: 1,
+                receiver: true,
+                waiting: 0,
+            }),
+            changed: Condvar::new(),
+        });
+        (Sender(shared.clone()), Receiver(shared))
+    }
+
+    pub fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+        channel(Some(cap))
+    }
+
+    pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+        channel(None)
+    }
+
+    impl<T> Sender<T> {
+        pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+            let mut state = self.0.state.lock().unwrap();
+            loop {
+                if !state.receiver {
+                    return Err(SendError(msg));
+                }
+                if state.has_room() {
+                    state.queue.push_back(msg);
+                    self.0.changed.notify_all();
+                    return Ok(());
+                }
+                state = self.0.changed.wait(state).unwrap();
+            }
+        }
+
+        pub fn send_timeout(&self, msg: T, _timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+            self.try_send(msg).map_err(|err| match err {
+                TrySendError::Full(msg) => SendTimeoutError::Timeout(msg),
+                TrySendError::Disconnected(msg) => SendTimeoutError::Disconnected(msg),
+            })
+        }
+
+        pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+            let mut state = self.0.state.lock().unwrap();
+            if !state.receiver {
+                return Err(TrySendError::Disconnected(msg));
+            }
+            if !state.has_room() {
+                return Err(TrySendError::Full(msg));
+            }
+            state.queue.push_back(msg);
+            self.0.changed.notify_all();
+            Ok(())
+        }
+    }
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            self.0.state.lock().unwrap().senders += 1;
+            Sender(self.0.clone())
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            self.0.state.lock().unwrap().senders -= 1;
+            self.0.changed.notify_all();
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub fn recv(&self) -> Result<T, RecvError> {
+            let mut state = self.0.state.lock().unwrap();
+            state.waiting += 1;
+            self.0.changed.notify_all();
+            let result = loop {
+                if let Some(msg) = state.queue.pop_front() {
+                    break Ok(msg);
+                }
+                if state.senders == 0 {
+                    break Err(RecvError);
+                }
+                state = self.0.changed.wait(state).unwrap();
+            };
+            state.waiting -= 1;
+            self.0.changed.notify_all();
+            result
+        }
+
+        pub fn recv_timeout(&self, _timeout: Duration) -> Result<T, RecvTimeoutError> {
+            self.try_recv().map_err(|err| match err {
+                TryRecvError::Empty => RecvTimeoutError::Timeout,
+                TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+            })
+        }
+
+        pub fn try_recv(&self) -> Result<T, TryRecvError> {
+            let mut state = self.0.state.lock().unwrap();
+            match state.queue.pop_front() {
+                Some(msg) => {
+                    self.0.changed.notify_all();
+                    Ok(msg)
+                }
+                None if state.senders == 0 => Err(TryRecvError::Disconnected),
+                None => Err(TryRecvError::Empty),
+            }
+        }
+    }
+
+    impl<T> IntoIterator for Receiver<T> {
+        type Item = T;
+        type IntoIter = IntoIter<T>;
+
+        fn into_iter(self) -> IntoIter<T> {
+            IntoIter(self)
+        }
+    }
+
+    /// Receives the messages until all the senders are gone.
+    pub struct IntoIter<T>(Receiver<T>);
+
+    impl<T> Iterator for IntoIter<T> {
+        type Item = T;
+
+        fn next(&mut self) -> Option<T> {
+            self.0.recv().ok()
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            let mut state = self.0.state.lock().unwrap();
+            state.receiver = false;
+            let queue = std::mem::take(&mut state.queue);
+            drop(state);
+            self.0.changed.notify_all();
+            // The messages may hold the senders of other channels
+            drop(queue);
+        }
+    }
+}
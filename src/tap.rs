@@ -0,0 +1,102 @@
+use std::{
+    io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{Arbiter, Timestamped};
+
+/// # Read-only Serial Port Tap
+///
+/// Sniffs a serial port which is owned by another application, e.g. to
+/// monitor a console while a flasher runs. The port is opened for reading
+/// only and its termios settings are never changed, so the owner of the
+/// port is not disturbed. Only the receiving part of the [`Arbiter`] API
+/// is available.
+///
+/// Keep in mind that the kernel delivers every received byte to only one
+/// reader, so the tap and the owner of the port compete for the data.
+#[derive(Clone)]
+pub struct Tap {
+    arbiter: Arbiter,
+}
+
+impl Default for Tap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tap {
+    /// Creates a new tap.
+    pub fn new() -> Self {
+        let arbiter = Arbiter::new();
+        arbiter.conn.set_read_only(true);
+        Self { arbiter }
+    }
+
+    /// Closes the serial port
+    pub fn close(&self) {
+        self.arbiter.close();
+    }
+
+    /// Returns true if the connection is open
+    pub fn is_open(&self) -> bool {
+        self.arbiter.is_open()
+    }
+
+    /// Returns true if the connection is open and the device is alive.
+    pub fn is_alive(&self) -> bool {
+        self.arbiter.is_alive()
+    }
+
+    /// Opens the serial port for reading.
+    pub fn open(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.arbiter.open(path)
+    }
+
+    /// Clear the Rx buffer.
+    pub fn clear_rx_buff(&self) -> io::Result<()> {
+        self.arbiter.clear_rx_buff()
+    }
+
+    /// Receives data from the serial port
+    pub fn receive(
+        &self,
+        until: Option<u8>,
+        deadline: Option<Instant>,
+    ) -> io::Result<Option<Vec<u8>>> {
+        self.arbiter.receive(until, deadline)
+    }
+
+    /// Blocks until at least one byte is available to be received or until
+    /// the deadline. Nothing is consumed. Returns true if data is available.
+    pub fn wait_readable(&self, deadline: Instant) -> io::Result<bool> {
+        self.arbiter.wait_readable(deadline)
+    }
+
+    /// Receives data from the serial port and converts to a String
+    pub fn receive_string(
+        &self,
+        until: Option<u8>,
+        deadline: Option<Instant>,
+    ) -> io::Result<Option<String>> {
+        self.arbiter.receive_string(until, deadline)
+    }
+
+    /// Receives data from the serial port together with the time
+    /// each chunk of the data was read from the port.
+    pub fn receive_timestamped(
+        &self,
+        until: Option<u8>,
+        deadline: Option<Instant>,
+    ) -> io::Result<Option<Timestamped>> {
+        self.arbiter.receive_timestamped(until, deadline)
+    }
+
+    /// Change the duration of cooloff after disconnecting due to an error
+    /// and before a new connection attempt is made.
+    pub fn set_cooloff_duration(&self, cooloff: Option<Duration>) {
+        self.arbiter.set_cooloff_duration(cooloff);
+    }
+}
@@ -0,0 +1,64 @@
+use std::io;
+
+use nix::{
+    errno::Errno,
+    libc,
+    sched::{sched_setaffinity, CpuSet},
+    unistd::{gettid, Pid},
+};
+
+/// Scheduling of the threads of the arbiter, i.e. the worker and the Rx pump,
+/// for latency-sensitive deployments on busy machines:
+///
+/// ```no_run
+/// # use serial_arbiter::{Arbiter, ThreadTuning};
+/// # let arbiter = Arbiter::new();
+/// // Keep the worker on CPU 3, away from the other load, and ahead of it
+/// arbiter.set_thread_tuning(ThreadTuning::new().with_cpus([3]).with_nice(-5))?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// Lowering the niceness below 0 takes `CAP_SYS_NICE` or a raised
+/// `RLIMIT_NICE`, otherwise it fails with `EACCES`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadTuning {
+    cpus: Option<Vec<usize>>,
+    nice: Option<i32>,
+}
+
+impl ThreadTuning {
+    /// Leaves the scheduling as inherited from the creator of the arbiter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pins the threads to the CPUs, so the scheduler does not bounce them
+    /// across the cores.
+    pub fn with_cpus(mut self, cpus: impl IntoIterator<Item = usize>) -> Self {
+        self.cpus = Some(cpus.into_iter().collect());
+        self
+    }
+
+    /// Sets the niceness of the threads, from -20 (the highest priority) to 19.
+    pub fn with_nice(mut self, nice: i32) -> Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    /// Apply the settings to the calling thread.
+    pub(crate) fn apply(&self) -> io::Result<()> {
+        if let Some(cpus) = &self.cpus {
+            let mut set = CpuSet::new();
+            for &cpu in cpus {
+                set.set(cpu)?;
+            }
+            sched_setaffinity(Pid::from_raw(0), &set)?;
+        }
+        if let Some(nice) = self.nice {
+            // The niceness of a thread is set by its id on Linux
+            let tid = gettid().as_raw() as libc::id_t;
+            Errno::result(unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    sync::{atomic::Ordering, OnceLock},
+    time::Instant,
+};
+
+use crate::sync::{AtomicBool, AtomicUsize};
+use crate::TimeoutDiagnostics;
+
+/// What happened to a request on its way through the worker, reported when
+/// it times out. Updated by the worker as it goes, since the response is
+/// sent from wherever the request ends.
+pub struct RequestTrace {
+    sent: Instant,
+    started: OnceLock<Instant>,
+    transmitted: AtomicUsize,
+    received: AtomicUsize,
+    port_open: AtomicBool,
+}
+
+impl RequestTrace {
+    pub fn new() -> Self {
+        Self {
+            sent: Instant::now(),
+            started: OnceLock::new(),
+            transmitted: AtomicUsize::new(0),
+            received: AtomicUsize::new(0),
+            port_open: AtomicBool::new(false),
+        }
+    }
+
+    /// The worker picked the request up.
+    pub fn start(&self) {
+        let _ = self.started.set(Instant::now());
+    }
+
+    pub fn port_opened(&self) {
+        self.port_open.store(true, Ordering::Relaxed);
+    }
+
+    pub fn transmitted(&self, count: usize) {
+        self.transmitted.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn received(&self, count: usize) {
+        self.received.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// The diagnostics as of now, which is when the response arrived.
+    pub fn diagnostics(&self) -> TimeoutDiagnostics {
+        let now = Instant::now();
+        let started = self.started.get().copied().unwrap_or(now);
+        TimeoutDiagnostics {
+            queued: started.saturating_duration_since(self.sent),
+            served: now.saturating_duration_since(started),
+            transmitted: self.transmitted.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            port_open: self.port_open.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    io::{self, ErrorKind},
+    thread,
+    time::{Duration, Instant},
+};
+
+use regex::bytes::Regex;
+
+use crate::{Arbiter, Console};
+
+/// The prompt of U-Boot, `=> ` or `U-Boot> ` in older versions.
+const PROMPT: &str = r"(=>|U-Boot>) $";
+/// Interval of the key presses interrupting the autoboot, well below the
+/// usual one second of `bootdelay`.
+const KEY_INTERVAL: Duration = Duration::from_millis(10);
+/// Silence after which the console is assumed to have finished echoing.
+const QUIET: Duration = Duration::from_millis(50);
+/// Aborts the command line of U-Boot.
+const CTRL_C: u8 = 0x03;
+
+/// Automation of the U-Boot boot loader, e.g. for board provisioning:
+///
+/// ```no_run
+/// # use std::time::{Duration, Instant};
+/// # use serial_arbiter::{Arbiter, UBoot};
+/// # fn main() -> std::io::Result<()> {
+/// # let arbiter = Arbiter::new();
+/// # let image = Vec::new();
+/// let uboot = UBoot::new(arbiter);
+/// // Power cycle the board, then
+/// uboot.stop_autoboot(Instant::now() + Duration::from_secs(30))?;
+/// uboot.loady(0x8200_0000, &image)?;
+/// uboot.run_command("bootm 0x82000000")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct UBoot {
+    console: Console,
+    stop_key: Vec<u8>,
+}
+
+impl UBoot {
+    /// Creates the helper for the default prompt of U-Boot.
+    pub fn new(arbiter: Arbiter) -> Self {
+        let prompt = Regex::new(PROMPT).expect("valid prompt pattern");
+        Self::with_console(Console::new(arbiter, prompt))
+    }
+
+    /// Creates the helper for the console, e.g. one with a custom prompt
+    /// set by `CONFIG_SYS_PROMPT`.
+    pub fn with_console(console: Console) -> Self {
+        Self {
+            console,
+            stop_key: b" ".to_vec(),
+        }
+    }
+
+    /// Sets the keys interrupting the autoboot, e.g. the string of
+    /// `CONFIG_AUTOBOOT_STOP_STR`. A space by default.
+    pub fn with_stop_key(mut self, stop_key: impl AsRef<[u8]>) -> Self {
+        self.stop_key = stop_key.as_ref().to_vec();
+        self
+    }
+
+    /// The console of U-Boot.
+    pub fn console(&self) -> &Console {
+        &self.console
+    }
+
+    /// Presses the stop key every 10 ms until U-Boot shows its prompt, to
+    /// catch the short "Hit any key to stop autoboot" window. Call it right
+    /// before or after resetting the board. The keys typed after the prompt
+    /// are discarded, leaving the console ready for commands. Fails with
+    /// `ErrorKind::TimedOut` if the prompt does not show up by the deadline.
+    pub fn stop_autoboot(&self, deadline: Instant) -> io::Result<()> {
+        let arbiter = self.console.arbiter();
+        arbiter.clear_rx_buff()?;
+        let mut received = Vec::new();
+        while !self.console.prompt().is_match(&received) {
+            if Instant::now() >= deadline {
+                let err = io::Error::new(ErrorKind::TimedOut, "U-Boot prompt did not show up");
+                return Err(arbiter.tagged(err));
+            }
+            arbiter.transmit(self.stop_key.as_slice().into(), deadline)?;
+            thread::sleep(KEY_INTERVAL);
+            if let Some(data) = arbiter.receive(None, Some(Instant::now()))? {
+                received.extend_from_slice(&data);
+            }
+        }
+
+        // Drop the keys which made it to the command line
+        arbiter.transmit([CTRL_C].as_slice().into(), deadline)?;
+        self.wait_quiet(deadline)?;
+        self.console.wait_prompt()
+    }
+
+    /// Runs the command and returns its output, see [`Console::run_command`].
+    pub fn run_command(&self, command: &str) -> io::Result<String> {
+        self.console.run_command(command)
+    }
+
+    /// Loads the data to the memory at the address with `loady`, sending it
+    /// over YMODEM. Returns what U-Boot prints after the transfer, i.e. the
+    /// size and the start address of the loaded data.
+    pub fn loady(&self, address: u64, data: &[u8]) -> io::Result<String> {
+        let arbiter = self.console.arbiter();
+        let timeout = self.console.timeout();
+        let command = format!("loady 0x{address:x}");
+        arbiter.clear_rx_buff()?;
+        self.console.send_line(&command, Instant::now() + timeout)?;
+        let received = arbiter.transfer_ymodem("image", data, timeout)?;
+        let deadline = Instant::now() + timeout;
+        let output = self
+            .console
+            .read_until_prompt(&command, received, deadline)?;
+        Ok(String::from_utf8_lossy(&output).trim().to_string())
+    }
+
+    /// Discard the received data until the console is silent for a while.
+    fn wait_quiet(&self, deadline: Instant) -> io::Result<()> {
+        let arbiter = self.console.arbiter();
+        loop {
+            let quiet = (Instant::now() + QUIET).min(deadline);
+            if !arbiter.wait_readable(quiet)? {
+                return Ok(());
+            }
+            arbiter.clear_rx_buff()?;
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use crate::{error::TimeoutDiagnostics, sync::Mutex};
+
+/// What a handle of the arbiter has used the port for, see
+/// [`Arbiter::client_usage`](crate::Arbiter::client_usage).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientUsage {
+    /// Identifies the handle among the handles of the arbiter.
+    pub id: u64,
+    /// The tag of the handle, see [`Arbiter::with_tag`](crate::Arbiter::with_tag).
+    pub tag: Option<Arc<str>>,
+    /// Number of requests made.
+    pub requests: u64,
+    /// Number of the requests which timed out.
+    pub timeouts: u64,
+    /// Number of bytes transmitted while serving the requests.
+    pub transmitted: u64,
+    /// Number of bytes read from the port while serving the requests,
+    /// whether or not they were the response.
+    pub received: u64,
+    /// Time the requests waited for the requests before them.
+    pub queued: Duration,
+    /// Time the worker spent serving the requests.
+    pub served: Duration,
+}
+
+/// The usage of one handle, updated after each of its requests.
+pub struct UsageCounter {
+    usage: Mutex<ClientUsage>,
+}
+
+impl UsageCounter {
+    pub fn record(&self, diagnostics: &TimeoutDiagnostics, timed_out: bool) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.requests += 1;
+        usage.timeouts += timed_out as u64;
+        usage.transmitted += diagnostics.transmitted as u64;
+        usage.received += diagnostics.received as u64;
+        usage.queued += diagnostics.queued;
+        usage.served += diagnostics.served;
+    }
+
+    pub fn usage(&self) -> ClientUsage {
+        self.usage.lock().unwrap().clone()
+    }
+}
+
+/// The usage counters of the handles which are alive.
+#[derive(Default)]
+pub struct UsageRegistry {
+    next_id: u64,
+    counters: Vec<Weak<UsageCounter>>,
+}
+
+impl UsageRegistry {
+    /// Start counting the usage of a new handle.
+    pub fn register(&mut self, tag: Option<Arc<str>>) -> Arc<UsageCounter> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let counter = Arc::new(UsageCounter {
+            usage: Mutex::new(ClientUsage {
+                id,
+                tag,
+                ..ClientUsage::default()
+            }),
+        });
+        self.counters.retain(|counter| counter.strong_count() > 0);
+        self.counters.push(Arc::downgrade(&counter));
+        counter
+    }
+
+    /// The usage of every handle which is alive, oldest first.
+    pub fn usage(&self) -> Vec<ClientUsage> {
+        self.counters
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|counter| counter.usage())
+            .collect()
+    }
+}
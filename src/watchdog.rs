@@ -0,0 +1,48 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::WatchdogReport;
+
+/// Reopens a port on which requests keep timing out, see
+/// [`Arbiter::set_watchdog`](crate::Arbiter::set_watchdog).
+pub struct Watchdog {
+    window: Duration,
+    /// Time of the first timeout since the last success.
+    first_timeout: Option<Instant>,
+    timeouts: usize,
+}
+
+impl Watchdog {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            first_timeout: None,
+            timeouts: 0,
+        }
+    }
+
+    pub fn succeeded(&mut self) {
+        self.first_timeout = None;
+        self.timeouts = 0;
+    }
+
+    /// Account a request which timed out. Returns the report once requests
+    /// have been timing out for the whole window, starting anew.
+    pub fn timed_out(&mut self, now: Instant, err: &io::Error) -> Option<WatchdogReport> {
+        let first_timeout = *self.first_timeout.get_or_insert(now);
+        self.timeouts += 1;
+        let stuck_for = now.saturating_duration_since(first_timeout);
+        if stuck_for < self.window {
+            return None;
+        }
+        let report = WatchdogReport {
+            stuck_for,
+            timeouts: self.timeouts,
+            last_error: err.to_string(),
+        };
+        self.succeeded();
+        Some(report)
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind},
+    time::{Duration, Instant},
+};
+
+use crate::{Arbiter, Event, Progress};
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+/// Sent by the receiver to start a transfer with CRC-16 checksums.
+const CRC_MODE: u8 = b'C';
+/// Padding of the last data block.
+const SUB: u8 = 0x1A;
+/// Attempts to transmit a block before giving up.
+const MAX_ATTEMPTS: usize = 10;
+
+impl Arbiter {
+    /// Sends the data as a file with the YMODEM protocol, in 1 KiB blocks
+    /// with CRC-16 checksums, e.g. to `loady` of U-Boot or to `rb`. The
+    /// receiver has to be started first; anything it prints before it
+    /// requests the transfer is ignored. The timeout bounds every wait for
+    /// the receiver. Emits [`Event::Progress`] after every block.
+    pub fn send_ymodem(&self, name: &str, data: &[u8], timeout: Duration) -> io::Result<()> {
+        self.transfer_ymodem(name, data, timeout).map(drop)
+    }
+
+    /// Sends the data like [`Arbiter::send_ymodem`] and returns what was
+    /// received after the last acknowledgement, e.g. the start of the
+    /// summary printed by the receiver.
+    pub(crate) fn transfer_ymodem(
+        &self,
+        name: &str,
+        data: &[u8],
+        timeout: Duration,
+    ) -> io::Result<Vec<u8>> {
+        let mut receiver = Receiver::new(self, timeout);
+        receiver.wait_for(CRC_MODE)?;
+        let header = format!("{name}\0{}\0", data.len());
+        if header.len() > 128 {
+            let msg = "File name too long for the YMODEM header";
+            return Err(self.tagged(io::Error::new(ErrorKind::InvalidInput, msg)));
+        }
+        self.send_ymodem_block(&mut receiver, 0, header.as_bytes(), 0)?;
+        receiver.wait_for(CRC_MODE)?;
+
+        let start = Instant::now();
+        let mut sent = 0;
+        for (index, chunk) in data.chunks(1024).enumerate() {
+            self.send_ymodem_block(&mut receiver, (index + 1) as u8, chunk, SUB)?;
+            sent += chunk.len();
+            let progress = Progress::new(sent, data.len(), start);
+            self.conn.events.emit(Event::Progress(progress));
+        }
+
+        // Receivers usually reject the first end of file to confirm it
+        self.send_ymodem_acked(&mut receiver, &[&[EOT]], "End of file")?;
+
+        // An empty header ends the batch
+        receiver.wait_for(CRC_MODE)?;
+        self.send_ymodem_block(&mut receiver, 0, &[], 0)?;
+        Ok(receiver.pending.into())
+    }
+
+    /// Transmit a block of the data, padded to the block size.
+    fn send_ymodem_block(
+        &self,
+        receiver: &mut Receiver<'_>,
+        seq: u8,
+        data: &[u8],
+        padding: u8,
+    ) -> io::Result<()> {
+        let (start, size) = match data.len() {
+            0..=128 => (SOH, 128),
+            _ => (STX, 1024),
+        };
+        let padding = vec![padding; size - data.len()];
+        let crc = crc16(padding.iter().copied(), crc16(data.iter().copied(), 0));
+        let parts: [&[u8]; 4] = [&[start, seq, !seq], data, &padding, &crc.to_be_bytes()];
+        self.send_ymodem_acked(receiver, &parts, &format!("Block {seq}"))
+    }
+
+    /// Transmit the frame until the receiver acknowledges it, repeating it
+    /// if the receiver rejects it or does not answer.
+    fn send_ymodem_acked(
+        &self,
+        receiver: &mut Receiver<'_>,
+        frame: &[&[u8]],
+        what: &str,
+    ) -> io::Result<()> {
+        for _ in 0..MAX_ATTEMPTS {
+            // Requests sent before the frame arrived are no answer to it
+            receiver.pending.clear();
+            self.transmit_parts(frame, receiver.deadline())?;
+            loop {
+                match receiver.next() {
+                    Ok(ACK) => return Ok(()),
+                    Ok(CAN) => {
+                        let msg = "Transfer cancelled by the receiver";
+                        let err = io::Error::new(ErrorKind::ConnectionAborted, msg);
+                        return Err(self.tagged(err));
+                    }
+                    Ok(NAK) => break,
+                    // Noise
+                    Ok(_) => continue,
+                    Err(err) if err.kind() == ErrorKind::TimedOut => break,
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        let msg = format!("{what} not acknowledged after {MAX_ATTEMPTS} attempts");
+        Err(self.tagged(io::Error::new(ErrorKind::TimedOut, msg)))
+    }
+}
+
+/// The bytes sent back by the receiver, one at a time.
+struct Receiver<'a> {
+    arbiter: &'a Arbiter,
+    timeout: Duration,
+    pending: VecDeque<u8>,
+}
+
+impl<'a> Receiver<'a> {
+    fn new(arbiter: &'a Arbiter, timeout: Duration) -> Self {
+        Self {
+            arbiter,
+            timeout,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn deadline(&self) -> Instant {
+        Instant::now() + self.timeout
+    }
+
+    /// The next byte from the receiver, which has the timeout to send it.
+    fn next(&mut self) -> io::Result<u8> {
+        let deadline = self.deadline();
+        loop {
+            if let Some(byte) = self.pending.pop_front() {
+                return Ok(byte);
+            }
+            if !self.arbiter.wait_readable(deadline)? {
+                let err =
+                    io::Error::new(ErrorKind::TimedOut, "No response from the YMODEM receiver");
+                return Err(self.arbiter.tagged(err));
+            }
+            if let Some(data) = self.arbiter.receive(None, Some(Instant::now()))? {
+                self.pending.extend(data);
+            }
+        }
+    }
+
+    /// Skip everything until the given byte, failing if the receiver cancels.
+    fn wait_for(&mut self, expected: u8) -> io::Result<()> {
+        let mut cancels = 0;
+        loop {
+            match self.next()? {
+                byte if byte == expected => return Ok(()),
+                // Two in a row cancel the transfer
+                CAN if cancels > 0 => {
+                    let msg = "Transfer cancelled by the receiver";
+                    let err = io::Error::new(ErrorKind::ConnectionAborted, msg);
+                    return Err(self.arbiter.tagged(err));
+                }
+                CAN => cancels += 1,
+                _ => cancels = 0,
+            }
+        }
+    }
+}
+
+/// CRC-16/XMODEM of the data, continuing from the CRC of the preceding data.
+fn crc16(data: impl Iterator<Item = u8>, mut crc: u16) -> u16 {
+    for byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}